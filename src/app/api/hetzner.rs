@@ -1,16 +1,66 @@
-use std::sync::mpsc::{SendError, Sender};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::mpsc::{SendError, Sender},
+    time::Duration,
+};
 
-use super::Endpoint;
-use crate::utils::{generate_application_list, Data, Key, RemoteData};
-use hcloud::apis::{
-    configuration::Configuration, images_api::ListImagesError, servers_api::ListServersError, Error,
+use super::{Endpoint, ServerAction, METRICS_ORIGIN};
+use crate::utils::{
+    generate_application_list, label_value, Application, Data, HealthChecks, JournalEntry, Key,
+    ListProgress, PreShutdown, RemoteData, RestoreSpec, Resume, ServerMetrics, ServerSpec, Step,
+    WorkflowUpdate, METRICS_STEP, METRIC_TYPES,
 };
 #[cfg(not(all(test, mock)))]
-use hcloud::apis::{images_api, servers_api};
-use hcloud::models::{ListImagesResponse, ListServersResponse};
+use hcloud::apis::{actions_api, images_api, servers_api};
+use hcloud::apis::{
+    actions_api::GetActionParams,
+    configuration::Configuration,
+    images_api::{
+        DeleteImageParams, GetImageParams, ListImagesError, ListImagesParams, ReplaceImageParams,
+    },
+    servers_api::{
+        CreateImageFromServerParams, CreateServerParams, DeleteServerParams,
+        GetMetricsForServerParams, GetServerParams, ListServersError, ListServersParams,
+        PowerOffServerParams, PowerOnServerParams, ShutdownServerParams, SoftRebootServerParams,
+    },
+    Error,
+};
+use hcloud::models::{
+    action, create_image_from_server_request, image, replace_image_request, server, Action,
+    CreateImageFromServerRequest, CreateServerRequest, ListImagesResponse, ListServersResponse,
+    Meta, ReplaceImageRequest,
+};
 
 #[cfg(all(test, mock))]
-use test::{images_api, servers_api};
+use test::{actions_api, images_api, servers_api};
+
+/// Delay between two requests while waiting on a remote state change
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const CREATE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const ACTION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Largest page size accepted by the list endpoints
+const PER_PAGE: i32 = 50;
+
+/// Sends updates from a running future back to the app
+struct Reporter<'a> {
+    origin: &'a str,
+    tx: &'a Sender<RemoteData>,
+    ctx: &'a egui::Context,
+}
+impl Reporter<'_> {
+    fn send(&self, data: Data) -> Result<(), String> {
+        let res = self.tx.send(RemoteData::new(data, self.origin));
+        self.ctx.request_repaint();
+        res.map_err(|e| e.to_string())
+    }
+
+    fn step(&self, update: WorkflowUpdate) -> Result<(), String> {
+        self.send(Data::Workflow(update))
+    }
+}
 
 pub struct Hetzner;
 impl Endpoint for Hetzner {
@@ -24,58 +74,952 @@ impl Endpoint for Hetzner {
         let tx = app.tx.clone();
         let ctx = ctx.clone();
 
-        tokio::spawn(Self::application_list_future(api_key, origin, tx, ctx));
+        let app_label = app.app_label.clone();
+
+        tokio::spawn(Self::application_list_future(
+            api_key, app_label, origin, tx, ctx,
+        ));
+    }
+
+    fn req_server_action(
+        &self,
+        app: &mut crate::app::App,
+        server_id: i32,
+        action: ServerAction,
+        origin: &str,
+        ctx: &egui::Context,
+    ) {
+        let api_key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let origin = origin.to_string();
+
+        tokio::spawn(Self::server_action_future(
+            api_key, server_id, action, origin, tx, ctx,
+        ));
+    }
+
+    fn req_crunch(
+        &self,
+        app: &mut crate::app::App,
+        application: &Application,
+        origin: &str,
+        ctx: &egui::Context,
+    ) {
+        let api_key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let origin = origin.to_string();
+        let servers = application
+            .servers
+            .iter()
+            .flatten()
+            .map(|server| (server.id, ServerSpec::from(server)))
+            .collect();
+        let prepare = app.pre_shutdown(application);
+
+        let app_label = app.app_label.clone();
+
+        tokio::spawn(Self::crunch_future(
+            api_key, servers, prepare, app_label, origin, tx, ctx,
+        ));
+    }
+
+    fn req_restore(
+        &self,
+        app: &mut crate::app::App,
+        spec: RestoreSpec,
+        origin: &str,
+        ctx: &egui::Context,
+    ) {
+        let api_key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let origin = origin.to_string();
+        let checks = app
+            .applications()
+            .iter()
+            .find(|application| application.name.as_deref() == Some(&origin))
+            .and_then(|application| {
+                let checks = app.health_checks.get(application.id())?;
+                let id = application.id().to_string();
+                (!checks.checks.is_empty()).then(|| (id, checks.clone()))
+            });
+
+        let app_label = app.app_label.clone();
+
+        tokio::spawn(Self::restore_future(
+            api_key, spec, checks, app_label, origin, tx, ctx,
+        ));
+    }
+
+    fn req_resume(
+        &self,
+        app: &mut crate::app::App,
+        entries: Vec<JournalEntry>,
+        origin: &str,
+        ctx: &egui::Context,
+    ) {
+        let api_key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let origin = origin.to_string();
+
+        tokio::spawn(Self::resume_future(api_key, origin, entries, tx, ctx));
+    }
+
+    fn req_convert_backup(
+        &self,
+        app: &mut crate::app::App,
+        image_id: i32,
+        origin: &str,
+        ctx: &egui::Context,
+    ) {
+        let api_key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let origin = origin.to_string();
+        let app_label = app.app_label.clone();
+
+        tokio::spawn(Self::convert_backup_future(
+            api_key, image_id, app_label, origin, tx, ctx,
+        ));
+    }
+
+    fn req_prune(
+        &self,
+        app: &mut crate::app::App,
+        image_ids: Vec<i32>,
+        origin: &str,
+        ctx: &egui::Context,
+    ) {
+        let api_key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let origin = origin.to_string();
+
+        tokio::spawn(Self::prune_future(api_key, image_ids, origin, tx, ctx));
+    }
+
+    fn req_metrics(
+        &self,
+        app: &mut crate::app::App,
+        application: &Application,
+        minutes: u32,
+        ctx: &egui::Context,
+    ) {
+        let api_key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        let tx = app.tx.clone();
+        let ctx = ctx.clone();
+        let id = application.id().to_string();
+        let server_ids = application.servers.iter().flatten().map(|s| s.id).collect();
+
+        tokio::spawn(Self::metrics_future(
+            api_key, id, server_ids, minutes, tx, ctx,
+        ));
     }
 }
 impl Hetzner {
+    fn config(api_key: &Key) -> Configuration {
+        let mut configuration = Configuration::new();
+        configuration.bearer_access_token = Some(api_key.0.clone());
+        configuration
+    }
+
     async fn application_list_future(
         api_key: Key,
+        app_label: String,
         origin: &'static str,
         tx: Sender<RemoteData>,
         ctx: egui::Context,
     ) -> Result<(), SendError<RemoteData>> {
-        let servers = Self::get_server_list(&api_key).await;
-        let images = Self::get_image_list(&api_key).await;
-
-        let res = match (servers, images) {
-            (Err(e), _) => tx.send(RemoteData::new(Data::Error(e.to_string()), origin)),
-            (_, Err(e)) => tx.send(RemoteData::new(Data::Error(e.to_string()), origin)),
-            (Ok(servers), Ok(images)) => tx.send(RemoteData::new(
-                Data::Application(generate_application_list(&servers, &images)),
-                origin,
+        // Images are listed on a task of their own, at the same time as the servers
+        let images = {
+            let (api_key, tx, ctx) = (api_key.clone(), tx.clone(), ctx.clone());
+            tokio::spawn(async move {
+                let reporter = Reporter {
+                    origin,
+                    tx: &tx,
+                    ctx: &ctx,
+                };
+                let images = Self::get_image_list(&api_key, &reporter).await;
+                images.map_err(|e| e.to_string())
+            })
+        };
+        let reporter = Reporter {
+            origin,
+            tx: &tx,
+            ctx: &ctx,
+        };
+        let servers = Self::get_server_list(&api_key, &reporter)
+            .await
+            .map_err(|e| e.to_string());
+        let images = images.await.unwrap_or_else(|e| Err(e.to_string()));
+
+        // Keep whichever half arrived, so one flaky endpoint doesn't blank the dashboard
+        let data = match (servers, images) {
+            (Ok(servers), Ok(images)) => {
+                Data::Application(generate_application_list(&servers, &images, &app_label))
+            }
+            (Ok(servers), Err(e)) => Data::Partial {
+                applications: generate_application_list(&servers, &Default::default(), &app_label),
+                error: format!("Failed to fetch images: {}", e),
+            },
+            (Err(e), Ok(images)) => Data::Partial {
+                applications: generate_application_list(&Default::default(), &images, &app_label),
+                error: format!("Failed to fetch servers: {}", e),
+            },
+            (Err(servers), Err(images)) => Data::Error(format!(
+                "Failed to fetch servers: {}; failed to fetch images: {}",
+                servers, images
             )),
         };
+        let res = tx.send(RemoteData::new(data, origin));
+        ctx.request_repaint();
+        res
+    }
+
+    async fn convert_backup_future(
+        api_key: Key,
+        image_id: i32,
+        app_label: String,
+        origin: String,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Result<(), SendError<RemoteData>> {
+        // Labeled like crunch snapshots, so the snapshot stays with its application
+        let request = ReplaceImageRequest {
+            description: Some(format!("{} (from backup {})", origin, image_id)),
+            labels: Some(HashMap::from([(app_label, label_value(&origin))])),
+            r#type: Some(replace_image_request::Type::Snapshot),
+        };
+        let params = ReplaceImageParams {
+            id: image_id,
+            replace_image_request: Some(request),
+        };
+        let data = match images_api::replace_image(&Self::config(&api_key), params).await {
+            Ok(res) => match res.image {
+                Some(image) => Data::Image(*image),
+                None => Data::Error(format!("Backup {} was not converted", image_id)),
+            },
+            Err(e) => Data::Error(e.to_string()),
+        };
+        let res = tx.send(RemoteData::new(data, &origin));
+        ctx.request_repaint();
+        res
+    }
+
+    async fn prune_future(
+        api_key: Key,
+        image_ids: Vec<i32>,
+        origin: String,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Result<(), SendError<RemoteData>> {
+        let config = Self::config(&api_key);
+        let mut deleted = Vec::new();
+        let mut errors = Vec::new();
+        for id in image_ids {
+            // Protection may have been enabled since the preview was made
+            let image = images_api::get_image(&config, GetImageParams { id }).await;
+            match image.map(|res| res.image) {
+                Ok(Some(image)) if image.protection.delete => {
+                    errors.push(format!("Snapshot {} is protected, skipped", id));
+                    continue;
+                }
+                Ok(Some(_)) => (),
+                Ok(None) => continue,
+                Err(e) => {
+                    errors.push(format!("Snapshot {}: {}", id, e));
+                    continue;
+                }
+            }
+            match images_api::delete_image(&config, DeleteImageParams { id }).await {
+                Ok(()) => deleted.push(id),
+                Err(e) => errors.push(format!("Failed to delete snapshot {}: {}", id, e)),
+            }
+        }
+
+        let mut res = tx.send(RemoteData::new(Data::Pruned(deleted), &origin));
+        if !errors.is_empty() {
+            res = res.and(tx.send(RemoteData::new(Data::Error(errors.join("; ")), &origin)));
+        }
+        ctx.request_repaint();
+        res
+    }
+
+    async fn metrics_future(
+        api_key: Key,
+        application: String,
+        server_ids: Vec<i32>,
+        minutes: u32,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Result<(), SendError<RemoteData>> {
+        let config = Self::config(&api_key);
+        let end = chrono::Utc::now();
+        // A little more than asked for, so the oldest sample isn't cut off
+        let start = end
+            - chrono::Duration::minutes(minutes as i64)
+            - chrono::Duration::seconds(2 * METRICS_STEP);
+        let mut metrics = Ok(Vec::new());
+        for id in server_ids {
+            let params = GetMetricsForServerParams {
+                id,
+                r#type: METRIC_TYPES.to_string(),
+                start: start.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                end: end.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                step: Some(METRICS_STEP.to_string()),
+            };
+            let server = match servers_api::get_metrics_for_server(&config, params).await {
+                Ok(res) => ServerMetrics::from_metrics(id, &res.metrics),
+                Err(e) => Err(format!("Failed to fetch metrics of server {}: {}", id, e)),
+            };
+            metrics = metrics.and_then(|mut metrics: Vec<ServerMetrics>| {
+                metrics.push(server?);
+                Ok(metrics)
+            });
+        }
+
+        let data = Data::Metrics {
+            application,
+            metrics,
+        };
+        let res = tx.send(RemoteData::new(data, METRICS_ORIGIN));
+        ctx.request_repaint();
+        res
+    }
+
+    async fn server_action_future(
+        api_key: Key,
+        server_id: i32,
+        action: ServerAction,
+        origin: String,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Result<(), SendError<RemoteData>> {
+        let reporter = Reporter {
+            origin: &origin,
+            tx: &tx,
+            ctx: &ctx,
+        };
+        let config = Self::config(&api_key);
+        let tracked = async {
+            let action = Self::post_server_action(&api_key, server_id, action).await?;
+            reporter.send(Data::Action(action.clone()))?;
+            // Failed actions are reported through their final progress update
+            Self::await_action(&config, action.id, ACTION_TIMEOUT, &reporter)
+                .await
+                .map(|_| ())
+        };
+
+        match tracked.await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let res = tx.send(RemoteData::new(Data::Error(e), &origin));
+                ctx.request_repaint();
+                res
+            }
+        }
+    }
+
+    async fn crunch_future(
+        api_key: Key,
+        servers: Vec<(i32, ServerSpec)>,
+        prepare: PreShutdown,
+        app_label: String,
+        origin: String,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Result<(), SendError<RemoteData>> {
+        let config = Self::config(&api_key);
+        let mut last_id = 0;
+
+        let reporter = Reporter {
+            origin: &origin,
+            tx: &tx,
+            ctx: &ctx,
+        };
+
+        // Every server is prepared before the first one is shut down
+        if !prepare.is_empty() {
+            for (id, _) in &servers {
+                if let Err(e) = Self::prepare_shutdown(&prepare, *id, &reporter).await {
+                    let res = tx.send(RemoteData::new(Data::Error(e), &origin));
+                    ctx.request_repaint();
+                    return res;
+                }
+            }
+        }
+
+        for (id, spec) in servers {
+            if let Err(e) = Self::crunch_server(&config, id, &spec, &app_label, &reporter).await {
+                let res = tx.send(RemoteData::new(Data::Error(e), &origin));
+                ctx.request_repaint();
+                return res;
+            }
+            last_id = id;
+        }
+
+        let res = tx.send(RemoteData::new(
+            Data::Workflow(WorkflowUpdate::new(Step::Done, last_id)),
+            &origin,
+        ));
+        ctx.request_repaint();
+        res
+    }
+
+    /// Saves the game and runs the shutdown hooks while the server is still running
+    async fn prepare_shutdown(
+        prepare: &PreShutdown,
+        id: i32,
+        reporter: &Reporter<'_>,
+    ) -> Result<(), String> {
+        let host = prepare.hosts.get(&id).cloned().flatten().ok_or(format!(
+            "Server {} has no public IPv4 address to prepare its shutdown, crunch aborted",
+            id
+        ))?;
+        if let Some(rcon) = &prepare.rcon {
+            reporter.step(WorkflowUpdate::new(Step::Rcon, id))?;
+            let mut sent = Ok(());
+            let saved = rcon
+                .run(&host, |output| {
+                    sent = sent.clone().and(reporter.send(Data::Rcon(output)));
+                })
+                .await;
+            sent.and(saved)
+                .map_err(|e| format!("{}, crunch aborted", e))?;
+        }
+        if let Some(hooks) = &prepare.hooks {
+            reporter.step(WorkflowUpdate::new(Step::Hooks, id))?;
+            hooks
+                .run(&host)
+                .await
+                .map_err(|e| format!("{}, crunch aborted", e))?;
+        }
+        Ok(())
+    }
+
+    /// Runs every step of a crunch for a single server, reporting each step as it starts.
+    /// The server is only deleted once its snapshot has been confirmed as available.
+    async fn crunch_server(
+        config: &Configuration,
+        id: i32,
+        spec: &ServerSpec,
+        app_label: &str,
+        reporter: &Reporter<'_>,
+    ) -> Result<(), String> {
+        Self::shutdown(config, id, reporter).await?;
+        let (image_id, action_id) = Self::snapshot(config, id, spec, app_label, reporter).await?;
+        Self::confirm_snapshot(config, id, image_id, Some(action_id), reporter).await?;
+        Self::delete(config, id, image_id, reporter).await
+    }
+
+    async fn shutdown(
+        config: &Configuration,
+        id: i32,
+        reporter: &Reporter<'_>,
+    ) -> Result<(), String> {
+        let action = servers_api::shutdown_server(config, ShutdownServerParams { id })
+            .await
+            .map_err(|e| e.to_string())?
+            .action;
+        reporter.step(WorkflowUpdate::new(Step::Shutdown, id).with_action(*action))?;
+
+        reporter.step(WorkflowUpdate::new(Step::AwaitOff, id))?;
+        Self::await_server_status(config, id, server::Status::Off, SHUTDOWN_TIMEOUT).await
+    }
+
+    /// Requests a snapshot, returning the IDs of the new image and the action creating it
+    async fn snapshot(
+        config: &Configuration,
+        id: i32,
+        spec: &ServerSpec,
+        app_label: &str,
+        reporter: &Reporter<'_>,
+    ) -> Result<(i32, i32), String> {
+        let request = CreateImageFromServerRequest {
+            description: Some(spec.name.clone()),
+            labels: Some(spec.labels(app_label, reporter.origin)),
+            r#type: Some(create_image_from_server_request::Type::Snapshot),
+        };
+        let res = servers_api::create_image_from_server(
+            config,
+            CreateImageFromServerParams {
+                id,
+                create_image_from_server_request: Some(request),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let image_id = res.image.ok_or("No snapshot was created")?.id;
+        let action = *res.action.ok_or("No snapshot action was returned")?;
+        let action_id = action.id;
+        reporter.step(
+            WorkflowUpdate::new(Step::Snapshot, id)
+                .with_image(image_id)
+                .with_action(action),
+        )?;
+        Ok((image_id, action_id))
+    }
+
+    /// Waits for the snapshot action to succeed and the image to become available
+    async fn confirm_snapshot(
+        config: &Configuration,
+        id: i32,
+        image_id: i32,
+        action_id: Option<i32>,
+        reporter: &Reporter<'_>,
+    ) -> Result<(), String> {
+        reporter.step(WorkflowUpdate::new(Step::AwaitSnapshot, id).with_image(image_id))?;
+        if let Some(action_id) = action_id {
+            Self::await_action(config, action_id, SNAPSHOT_TIMEOUT, reporter)
+                .await
+                .and_then(Self::succeeded)?;
+        }
+        Self::poll(SNAPSHOT_TIMEOUT, "snapshot", || async move {
+            let image = images_api::get_image(config, GetImageParams { id: image_id })
+                .await
+                .map_err(|e| e.to_string())?
+                .image
+                .ok_or_else(|| format!("Snapshot {} not found", image_id))?;
+            Ok((image.status == image::Status::Available).then(|| ()))
+        })
+        .await
+    }
+
+    /// Deletes a server. Must only be called after `confirm_snapshot` succeeded.
+    async fn delete(
+        config: &Configuration,
+        id: i32,
+        image_id: i32,
+        reporter: &Reporter<'_>,
+    ) -> Result<(), String> {
+        let res = servers_api::delete_server(config, DeleteServerParams { id })
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut update = WorkflowUpdate::new(Step::Delete, id).with_image(image_id);
+        if let Some(action) = res.action {
+            update = update.with_action(*action);
+        }
+        reporter.step(update)
+    }
+
+    async fn await_server_status(
+        config: &Configuration,
+        id: i32,
+        status: server::Status,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        Self::poll(timeout, "server status", || async move {
+            let server = servers_api::get_server(config, GetServerParams { id })
+                .await
+                .map_err(|e| e.to_string())?
+                .server
+                .ok_or_else(|| format!("Server {} not found", id))?;
+            Ok((server.status == status).then(|| ()))
+        })
+        .await
+    }
+
+    async fn restore_future(
+        api_key: Key,
+        spec: RestoreSpec,
+        // Along with the ID of their application
+        checks: Option<(String, HealthChecks)>,
+        app_label: String,
+        origin: String,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Result<(), SendError<RemoteData>> {
+        let config = Self::config(&api_key);
+        let reporter = Reporter {
+            origin: &origin,
+            tx: &tx,
+            ctx: &ctx,
+        };
+        let res = match Self::restore_server(&config, &spec, &app_label, &reporter).await {
+            Ok(id) => {
+                // The server is restored either way, a failed check only keeps it from ready
+                if let Some((application, checks)) = checks {
+                    let health = Self::check_health(&config, id, &checks, &reporter).await;
+                    let data = Data::Health {
+                        application,
+                        health,
+                    };
+                    tx.send(RemoteData::new(data, &origin))?;
+                }
+                tx.send(RemoteData::new(
+                    Data::Workflow(WorkflowUpdate::new(Step::Done, id).with_image(spec.image_id)),
+                    &origin,
+                ))
+            }
+            Err(e) => tx.send(RemoteData::new(Data::Error(e), &origin)),
+        };
+        ctx.request_repaint();
+        res
+    }
+
+    /// Runs the health checks on a restored server until they pass or time out
+    async fn check_health(
+        config: &Configuration,
+        id: i32,
+        checks: &HealthChecks,
+        reporter: &Reporter<'_>,
+    ) -> Result<(), String> {
+        reporter.step(WorkflowUpdate::new(Step::HealthCheck, id))?;
+        let server = servers_api::get_server(config, GetServerParams { id })
+            .await
+            .map_err(|e| e.to_string())?
+            .server
+            .ok_or_else(|| format!("Server {} not found", id))?;
+        let host = server.public_net.ipv4.map(|ipv4| ipv4.ip).ok_or(format!(
+            "Server {} has no public IPv4 address to run its health checks",
+            id
+        ))?;
+        checks.run(&host).await
+    }
+
+    /// Creates a server from a snapshot and waits until it is running
+    async fn restore_server(
+        config: &Configuration,
+        spec: &RestoreSpec,
+        app_label: &str,
+        reporter: &Reporter<'_>,
+    ) -> Result<i32, String> {
+        // Keeps the new server in its application, whatever it is named
+        let labels = HashMap::from([(app_label.to_string(), label_value(reporter.origin))]);
+        let request = CreateServerRequest {
+            labels: Some(labels),
+            location: Some(spec.server.location.clone()),
+            start_after_create: Some(true),
+            ..CreateServerRequest::new(
+                spec.image_id.to_string(),
+                spec.server.name.clone(),
+                spec.server.server_type.clone(),
+            )
+        };
+        let res = servers_api::create_server(
+            config,
+            CreateServerParams {
+                create_server_request: Some(request),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let id = res.server.id;
+        reporter.step(
+            WorkflowUpdate::new(Step::Create, id)
+                .with_image(spec.image_id)
+                .with_action(*res.action.clone()),
+        )?;
+
+        reporter.step(WorkflowUpdate::new(Step::AwaitRunning, id).with_image(spec.image_id))?;
+        for action in std::iter::once(*res.action).chain(res.next_actions) {
+            Self::await_action(config, action.id, CREATE_TIMEOUT, reporter)
+                .await
+                .and_then(Self::succeeded)?;
+        }
+        Self::await_server_status(config, id, server::Status::Running, CREATE_TIMEOUT).await?;
+
+        Ok(id)
+    }
+
+    async fn resume_future(
+        api_key: Key,
+        origin: String,
+        entries: Vec<JournalEntry>,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Result<(), SendError<RemoteData>> {
+        let config = Self::config(&api_key);
+        let reporter = Reporter {
+            origin: &origin,
+            tx: &tx,
+            ctx: &ctx,
+        };
+        let mut last_id = 0;
+
+        for entry in entries {
+            if let Err(e) = Self::resume(&config, &entry, &reporter).await {
+                let res = tx.send(RemoteData::new(Data::Error(e), &origin));
+                ctx.request_repaint();
+                return res;
+            }
+            last_id = entry.server_id;
+        }
+
+        let res = tx.send(RemoteData::new(
+            Data::Workflow(WorkflowUpdate::new(Step::Done, last_id)),
+            &origin,
+        ));
         ctx.request_repaint();
         res
     }
 
+    /// Continues an interrupted workflow from the last step recorded in the journal
+    async fn resume(
+        config: &Configuration,
+        entry: &JournalEntry,
+        reporter: &Reporter<'_>,
+    ) -> Result<(), String> {
+        match entry.resume() {
+            Resume::RollBack { server_id } => {
+                let server = servers_api::get_server(config, GetServerParams { id: server_id })
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .server;
+                if matches!(server, Some(server) if server.status == server::Status::Off) {
+                    let action =
+                        Self::post_server_action_with(config, server_id, ServerAction::PowerOn)
+                            .await?;
+                    Self::await_action(config, action.id, ACTION_TIMEOUT, reporter)
+                        .await
+                        .and_then(Self::succeeded)?;
+                }
+                Err(format!(
+                    "Interrupted crunch of server {} was rolled back before its snapshot",
+                    server_id
+                ))
+            }
+            Resume::ConfirmAndDelete {
+                server_id,
+                image_id,
+                action_id,
+            } => {
+                Self::confirm_snapshot(config, server_id, image_id, action_id, reporter).await?;
+                Self::delete(config, server_id, image_id, reporter).await
+            }
+            Resume::VerifyDeleted { server_id } => {
+                match servers_api::get_server(config, GetServerParams { id: server_id }).await {
+                    Err(Error::ResponseError(content)) if content.status == 404 => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                    Ok(res) if res.server.is_none() => Ok(()),
+                    Ok(_) => Err(format!(
+                        "Server {} survived an interrupted deletion, not deleting it again",
+                        server_id
+                    )),
+                }
+            }
+            Resume::AwaitRunning { server_id } => {
+                Self::await_server_status(
+                    config,
+                    server_id,
+                    server::Status::Running,
+                    CREATE_TIMEOUT,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Polls an action until it has finished, reporting its progress along the way
+    async fn await_action(
+        config: &Configuration,
+        id: i32,
+        timeout: Duration,
+        reporter: &Reporter<'_>,
+    ) -> Result<Action, String> {
+        Self::poll(timeout, "action", || async move {
+            let action = *actions_api::get_action(config, GetActionParams { id })
+                .await
+                .map_err(|e| e.to_string())?
+                .action;
+            reporter.send(Data::Action(action.clone()))?;
+            Ok((action.status != action::Status::Running).then(|| action))
+        })
+        .await
+    }
+
+    fn succeeded(action: Action) -> Result<Action, String> {
+        match action.status {
+            action::Status::Error => Err(match action.error {
+                Some(error) => format!("{}: {}", action.command, error.message),
+                None => format!("{} failed", action.command),
+            }),
+            _ => Ok(action),
+        }
+    }
+
+    /// Repeatedly calls `f` until it yields a value, an error or the timeout is reached
+    async fn poll<T, F, Fut>(timeout: Duration, what: &str, mut f: F) -> Result<T, String>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Option<T>, String>>,
+    {
+        let started = tokio::time::Instant::now();
+        loop {
+            if let Some(value) = f().await? {
+                return Ok(value);
+            }
+            if started.elapsed() > timeout {
+                return Err(format!("Timed out waiting for {}", what));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Requests every page of a list endpoint, reporting the number of fetched items after each
+    async fn fetch_all_pages<T, E, F, Fut>(
+        what: &'static str,
+        reporter: &Reporter<'_>,
+        mut fetch: F,
+    ) -> Result<(Vec<T>, Option<Box<Meta>>), E>
+    where
+        F: FnMut(i32) -> Fut,
+        Fut: Future<Output = Result<(Vec<T>, Option<Box<Meta>>), E>>,
+    {
+        let mut items = Vec::new();
+        let mut page = 1;
+        loop {
+            let (mut batch, meta) = fetch(page).await?;
+            items.append(&mut batch);
+
+            let pagination = meta.as_ref().map(|meta| &meta.pagination);
+            let progress = ListProgress {
+                what: what.to_string(),
+                fetched: items.len(),
+                total: pagination
+                    .and_then(|p| p.total_entries)
+                    .map(|total| total as usize),
+            };
+            // Progress is informational only, a closed channel surfaces with the final result
+            let _ = reporter.send(Data::Listing(progress));
+
+            match pagination.and_then(|p| p.next_page) {
+                // Guard against endpoints pointing back at pages already fetched
+                Some(next) if next > page => page = next,
+                _ => return Ok((items, meta)),
+            }
+        }
+    }
+
     async fn get_server_list(
         api_key: &Key,
+        reporter: &Reporter<'_>,
     ) -> Result<ListServersResponse, Error<ListServersError>> {
-        let config: Configuration = {
-            let mut configuration = Configuration::new();
-            configuration.bearer_access_token = Some(api_key.0.clone());
-            configuration
-        };
+        let config = Self::config(api_key);
+        let (servers, meta) = Self::fetch_all_pages("servers", reporter, |page| {
+            let params = ListServersParams {
+                page: Some(page),
+                per_page: Some(PER_PAGE),
+                ..Default::default()
+            };
+            let config = &config;
+            async move {
+                servers_api::list_servers(config, params)
+                    .await
+                    .map(|res| (res.servers, res.meta))
+            }
+        })
+        .await?;
+        Ok(ListServersResponse { meta, servers })
+    }
 
-        servers_api::list_servers(&config, Default::default()).await
+    async fn get_image_list(
+        api_key: &Key,
+        reporter: &Reporter<'_>,
+    ) -> Result<ListImagesResponse, Error<ListImagesError>> {
+        let config = Self::config(api_key);
+        let (images, meta) = Self::fetch_all_pages("images", reporter, |page| {
+            let params = ListImagesParams {
+                page: Some(page),
+                per_page: Some(PER_PAGE),
+                ..Default::default()
+            };
+            let config = &config;
+            async move {
+                images_api::list_images(config, params)
+                    .await
+                    .map(|res| (res.images, res.meta))
+            }
+        })
+        .await?;
+        Ok(ListImagesResponse { images, meta })
     }
 
-    async fn get_image_list(api_key: &Key) -> Result<ListImagesResponse, Error<ListImagesError>> {
-        let config: Configuration = {
-            let mut configuration = Configuration::new();
-            configuration.bearer_access_token = Some(api_key.0.clone());
-            configuration
-        };
+    async fn post_server_action(
+        api_key: &Key,
+        id: i32,
+        action: ServerAction,
+    ) -> Result<Action, String> {
+        Self::post_server_action_with(&Self::config(api_key), id, action).await
+    }
 
-        images_api::list_images(&config, Default::default()).await
+    async fn post_server_action_with(
+        config: &Configuration,
+        id: i32,
+        action: ServerAction,
+    ) -> Result<Action, String> {
+        match action {
+            ServerAction::Shutdown => {
+                servers_api::shutdown_server(config, ShutdownServerParams { id })
+                    .await
+                    .map(|res| *res.action)
+                    .map_err(|e| e.to_string())
+            }
+            ServerAction::PowerOff => {
+                servers_api::power_off_server(config, PowerOffServerParams { id })
+                    .await
+                    .map(|res| *res.action)
+                    .map_err(|e| e.to_string())
+            }
+            ServerAction::PowerOn => {
+                servers_api::power_on_server(config, PowerOnServerParams { id })
+                    .await
+                    .map(|res| *res.action)
+                    .map_err(|e| e.to_string())
+            }
+            ServerAction::Reboot => {
+                servers_api::soft_reboot_server(config, SoftRebootServerParams { id })
+                    .await
+                    .map(|res| *res.action)
+                    .map_err(|e| e.to_string())
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Hetzner;
-    use crate::utils::Key;
+    use super::{Hetzner, Reporter, ServerAction};
+    use crate::utils::{
+        fake_rcon_server, Data, HealthCheck, HealthChecks, Journal, Key, PreShutdown, RconShutdown,
+        RestoreSpec, Secret, ServerSpec, ShutdownHooks, Step, WorkflowUpdate, DEFAULT_APP_LABEL,
+        RCON_PASSWORD,
+    };
+    use std::collections::{HashMap, HashSet};
+
+    /// Servers created through the mock are reported as running, every other one as off
+    const CREATED_SERVER_ID: i32 = 1000;
+    fn app_label() -> String {
+        DEFAULT_APP_LABEL.to_string()
+    }
+
+    /// API key the mocked image endpoint refuses, while servers are still listed
+    const NO_IMAGES_KEY: &str = "no images";
+    /// Number of servers and images the mocked list endpoints spread over their pages
+    const LISTED: i32 = 60;
+    /// Image the mocked image endpoint reports as protected against deletion
+    const PROTECTED_IMAGE_ID: i32 = 13;
+
+    /// Pagination of the mocked list endpoints, along with the IDs on the requested page
+    #[cfg(mock)]
+    fn mock_page(page: Option<i32>, per_page: Option<i32>) -> (hcloud::models::Meta, Vec<i32>) {
+        let page = page.unwrap_or(1);
+        let per_page = per_page.unwrap_or(25);
+        let last_page = (LISTED + per_page - 1) / per_page;
+        let ids = ((page - 1) * per_page + 1..=(page * per_page).min(LISTED)).collect();
+        let pagination = hcloud::models::Pagination {
+            last_page: Some(last_page),
+            next_page: (page < last_page).then(|| page + 1),
+            page,
+            per_page,
+            previous_page: (page > 1).then(|| page - 1),
+            total_entries: Some(LISTED),
+        };
+        (hcloud::models::Meta::new(pagination), ids)
+    }
 
     #[cfg(mock)]
     pub mod servers_api {
@@ -90,7 +1034,7 @@ mod test {
         use http::StatusCode;
         pub async fn list_servers(
             config: &Configuration,
-            _params: ListServersParams,
+            params: ListServersParams,
         ) -> Result<ListServersResponse, Error<ListServersError>> {
             let key = config.bearer_access_token.clone();
             if key.is_none() || key.unwrap().is_empty() {
@@ -100,7 +1044,179 @@ mod test {
                     entity: None,
                 }));
             }
-            Ok(ListServersResponse::default())
+            let (meta, ids) = super::mock_page(params.page, params.per_page);
+            Ok(ListServersResponse {
+                meta: Some(Box::new(meta)),
+                servers: ids
+                    .into_iter()
+                    .map(|id| hcloud::models::Server {
+                        id,
+                        ..crate::utils::empty_server()
+                    })
+                    .collect(),
+            })
+        }
+
+        macro_rules! mock_action {
+            ($name:ident, $params:ident, $response:ident, $error:ident) => {
+                pub async fn $name(
+                    config: &Configuration,
+                    params: hcloud::apis::servers_api::$params,
+                ) -> Result<hcloud::models::$response, Error<hcloud::apis::servers_api::$error>> {
+                    let key = config.bearer_access_token.clone();
+                    if key.is_none() || key.unwrap().is_empty() {
+                        return Err(Error::ResponseError(hcloud::apis::ResponseContent {
+                            status: StatusCode::from_u16(500).unwrap(),
+                            content: "".to_string(),
+                            entity: None,
+                        }));
+                    }
+                    Ok(hcloud::models::$response {
+                        action: Box::new(hcloud::models::Action {
+                            id: params.id,
+                            command: stringify!($name).to_string(),
+                            status: hcloud::models::action::Status::Running,
+                            ..Default::default()
+                        }),
+                    })
+                }
+            };
+        }
+        mock_action!(
+            shutdown_server,
+            ShutdownServerParams,
+            ShutdownServerResponse,
+            ShutdownServerError
+        );
+        mock_action!(
+            power_off_server,
+            PowerOffServerParams,
+            PowerOffServerResponse,
+            PowerOffServerError
+        );
+        mock_action!(
+            power_on_server,
+            PowerOnServerParams,
+            PowerOnServerResponse,
+            PowerOnServerError
+        );
+        mock_action!(
+            soft_reboot_server,
+            SoftRebootServerParams,
+            SoftRebootServerResponse,
+            SoftRebootServerError
+        );
+
+        pub async fn get_server(
+            _config: &Configuration,
+            params: hcloud::apis::servers_api::GetServerParams,
+        ) -> Result<
+            hcloud::models::GetServerResponse,
+            Error<hcloud::apis::servers_api::GetServerError>,
+        > {
+            let status = match params.id {
+                super::CREATED_SERVER_ID => hcloud::models::server::Status::Running,
+                _ => hcloud::models::server::Status::Off,
+            };
+            Ok(hcloud::models::GetServerResponse {
+                server: Some(Box::new(hcloud::models::Server {
+                    id: params.id,
+                    status,
+                    ..crate::utils::empty_server()
+                })),
+            })
+        }
+
+        pub async fn create_server(
+            _config: &Configuration,
+            params: hcloud::apis::servers_api::CreateServerParams,
+        ) -> Result<
+            hcloud::models::CreateServerResponse,
+            Error<hcloud::apis::servers_api::CreateServerError>,
+        > {
+            let request = params.create_server_request.unwrap();
+            Ok(hcloud::models::CreateServerResponse {
+                action: Box::new(hcloud::models::Action {
+                    id: super::CREATED_SERVER_ID,
+                    command: "create_server".to_string(),
+                    status: hcloud::models::action::Status::Running,
+                    ..Default::default()
+                }),
+                next_actions: vec![],
+                root_password: None,
+                server: Box::new(hcloud::models::Server {
+                    id: super::CREATED_SERVER_ID,
+                    name: request.name,
+                    status: hcloud::models::server::Status::Initializing,
+                    ..crate::utils::empty_server()
+                }),
+            })
+        }
+
+        pub async fn create_image_from_server(
+            _config: &Configuration,
+            params: hcloud::apis::servers_api::CreateImageFromServerParams,
+        ) -> Result<
+            hcloud::models::CreateImageFromServerResponse,
+            Error<hcloud::apis::servers_api::CreateImageFromServerError>,
+        > {
+            Ok(hcloud::models::CreateImageFromServerResponse {
+                action: Some(Box::new(hcloud::models::Action {
+                    id: params.id,
+                    command: "create_image".to_string(),
+                    status: hcloud::models::action::Status::Running,
+                    ..Default::default()
+                })),
+                image: Some(Box::new(hcloud::models::Image {
+                    id: params.id,
+                    status: hcloud::models::image::Status::Creating,
+                    ..crate::utils::empty_snapshot()
+                })),
+            })
+        }
+
+        pub async fn delete_server(
+            _config: &Configuration,
+            params: hcloud::apis::servers_api::DeleteServerParams,
+        ) -> Result<
+            hcloud::models::DeleteServerResponse,
+            Error<hcloud::apis::servers_api::DeleteServerError>,
+        > {
+            Ok(hcloud::models::DeleteServerResponse {
+                action: Some(Box::new(hcloud::models::Action {
+                    id: params.id,
+                    command: "delete_server".to_string(),
+                    status: hcloud::models::action::Status::Running,
+                    ..Default::default()
+                })),
+            })
+        }
+
+        pub async fn get_metrics_for_server(
+            _config: &Configuration,
+            _params: hcloud::apis::servers_api::GetMetricsForServerParams,
+        ) -> Result<
+            hcloud::models::GetMetricsForServerResponse,
+            Error<hcloud::apis::servers_api::GetMetricsForServerError>,
+        > {
+            let series = |value: &str| hcloud::models::MetricsTimeSeries {
+                values: vec![
+                    vec![60.into(), value.into()],
+                    vec![120.into(), value.into()],
+                ],
+            };
+            let time_series = std::collections::HashMap::from([
+                ("cpu".to_string(), series("2.5")),
+                ("network.0.bandwidth.in".to_string(), series("100")),
+                ("network.0.bandwidth.out".to_string(), series("50")),
+            ]);
+            Ok(hcloud::models::GetMetricsForServerResponse {
+                metrics: Box::new(hcloud::models::Metrics {
+                    step: 60,
+                    time_series,
+                    ..Default::default()
+                }),
+            })
         }
     }
     #[cfg(mock)]
@@ -116,37 +1232,144 @@ mod test {
         use http::StatusCode;
         pub async fn list_images(
             config: &Configuration,
-            _params: ListImagesParams,
+            params: ListImagesParams,
         ) -> Result<ListImagesResponse, Error<ListImagesError>> {
             let key = config.bearer_access_token.clone();
-            if key.is_none() || key.unwrap().is_empty() {
+            if key.is_none()
+                || key.as_deref() == Some(super::NO_IMAGES_KEY)
+                || key.unwrap().is_empty()
+            {
                 return Err(Error::ResponseError(hcloud::apis::ResponseContent {
                     status: StatusCode::from_u16(500).unwrap(),
                     content: "".to_string(),
                     entity: None,
                 }));
             }
-            Ok(ListImagesResponse::default())
+            let (meta, ids) = super::mock_page(params.page, params.per_page);
+            Ok(ListImagesResponse {
+                meta: Some(Box::new(meta)),
+                images: ids
+                    .into_iter()
+                    .map(|id| hcloud::models::Image {
+                        id,
+                        ..crate::utils::empty_snapshot()
+                    })
+                    .collect(),
+            })
+        }
+
+        pub async fn replace_image(
+            _config: &Configuration,
+            params: hcloud::apis::images_api::ReplaceImageParams,
+        ) -> Result<
+            hcloud::models::ReplaceImageResponse,
+            Error<hcloud::apis::images_api::ReplaceImageError>,
+        > {
+            let request = params.replace_image_request.unwrap_or_default();
+            Ok(hcloud::models::ReplaceImageResponse {
+                image: Some(Box::new(hcloud::models::Image {
+                    id: params.id,
+                    description: request.description.unwrap_or_default(),
+                    labels: request.labels.unwrap_or_default(),
+                    ..crate::utils::empty_snapshot()
+                })),
+            })
+        }
+
+        pub async fn get_image(
+            _config: &Configuration,
+            params: hcloud::apis::images_api::GetImageParams,
+        ) -> Result<hcloud::models::GetImageResponse, Error<hcloud::apis::images_api::GetImageError>>
+        {
+            Ok(hcloud::models::GetImageResponse {
+                image: Some(Box::new(hcloud::models::Image {
+                    id: params.id,
+                    protection: Box::new(hcloud::models::Protection {
+                        delete: params.id == super::PROTECTED_IMAGE_ID,
+                    }),
+                    ..crate::utils::empty_snapshot()
+                })),
+            })
+        }
+
+        pub async fn delete_image(
+            _config: &Configuration,
+            _params: hcloud::apis::images_api::DeleteImageParams,
+        ) -> Result<(), Error<hcloud::apis::images_api::DeleteImageError>> {
+            Ok(())
+        }
+    }
+    #[cfg(mock)]
+    pub mod actions_api {
+        use hcloud::{
+            apis::{
+                actions_api::{GetActionError, GetActionParams},
+                configuration::Configuration,
+                Error,
+            },
+            models::{action::Status, Action, GetActionResponse},
+        };
+        pub async fn get_action(
+            _config: &Configuration,
+            params: GetActionParams,
+        ) -> Result<GetActionResponse, Error<GetActionError>> {
+            Ok(GetActionResponse {
+                action: Box::new(Action {
+                    id: params.id,
+                    progress: 100.0,
+                    status: Status::Success,
+                    ..Default::default()
+                }),
+            })
         }
     }
     #[cfg_attr(not(mock), ignore = "mocking is disabled")]
     #[tokio::test]
     async fn list_servers_mock() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = egui::Context::default();
+        let reporter = Reporter {
+            origin: "",
+            tx: &tx,
+            ctx: &ctx,
+        };
+
         let valid: Key = Key("you".to_string());
-        assert!(Hetzner::get_server_list(&valid).await.is_ok());
+        let servers = Hetzner::get_server_list(&valid, &reporter).await.unwrap();
+        assert_eq!(servers.servers.len(), LISTED as usize);
+        let ids: HashSet<i32> = servers.servers.iter().map(|server| server.id).collect();
+        assert_eq!(ids.len(), LISTED as usize);
+
+        let progress: Vec<usize> = rx
+            .try_iter()
+            .filter_map(|remote| match remote.data {
+                Data::Listing(progress) => Some(progress.fetched),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(progress, vec![50, 60]);
 
         let invalid: Key = Key(String::new());
-        assert!(Hetzner::get_server_list(&invalid).await.is_err());
+        assert!(Hetzner::get_server_list(&invalid, &reporter).await.is_err());
     }
 
     #[cfg_attr(not(mock), ignore = "mocking is disabled")]
     #[tokio::test]
     async fn list_images_mock() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let ctx = egui::Context::default();
+        let reporter = Reporter {
+            origin: "",
+            tx: &tx,
+            ctx: &ctx,
+        };
+
         let valid: Key = Key("you".to_string());
-        assert!(Hetzner::get_image_list(&valid).await.is_ok());
+        let images = Hetzner::get_image_list(&valid, &reporter).await.unwrap();
+        assert_eq!(images.images.len(), LISTED as usize);
 
         let invalid: Key = Key(String::new());
-        assert!(Hetzner::get_image_list(&invalid).await.is_err());
+        assert!(Hetzner::get_image_list(&invalid, &reporter).await.is_err());
     }
 
     #[cfg_attr(not(mock), ignore = "mocking is disabled")]
@@ -156,8 +1379,383 @@ mod test {
         let origin = "";
         let api_key = Key("secret".to_string());
         let ctx = egui::Context::default();
-        let future = Hetzner::application_list_future(api_key, origin, tx, ctx).await;
+        let future = Hetzner::application_list_future(api_key, app_label(), origin, tx, ctx).await;
+        assert!(future.is_ok());
+        let last = rx.try_iter().last().map(|remote| remote.data);
+        assert!(matches!(last, Some(Data::Application(_))));
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn application_list_future_partial() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let api_key = Key(NO_IMAGES_KEY.to_string());
+        let ctx = egui::Context::default();
+        let future =
+            Hetzner::application_list_future(api_key, app_label(), "", tx.clone(), ctx.clone())
+                .await;
+        assert!(future.is_ok());
+        match rx.try_iter().last().map(|remote| remote.data) {
+            Some(Data::Partial {
+                applications,
+                error,
+            }) => {
+                assert!(!applications.is_empty());
+                assert!(error.contains("images"));
+            }
+            other => panic!("expected partial data, got {:?}", other),
+        }
+
+        let api_key = Key(String::new());
+        let future = Hetzner::application_list_future(api_key, app_label(), "", tx, ctx).await;
+        assert!(future.is_ok());
+        let last = rx.try_iter().last().map(|remote| remote.data);
+        assert!(matches!(last, Some(Data::Error(_))));
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn post_server_action_mock() {
+        let valid: Key = Key("you".to_string());
+        for action in ServerAction::ALL {
+            let res = Hetzner::post_server_action(&valid, 42, action).await;
+            assert!(matches!(res, Ok(action) if action.id == 42));
+        }
+
+        let invalid: Key = Key(String::new());
+        assert!(
+            Hetzner::post_server_action(&invalid, 42, ServerAction::Shutdown)
+                .await
+                .is_err()
+        );
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn server_action_future() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let origin = "server".to_string();
+        let api_key = Key("secret".to_string());
+        let ctx = egui::Context::default();
+        let future =
+            Hetzner::server_action_future(api_key, 1, ServerAction::PowerOn, origin, tx, ctx).await;
+        assert!(future.is_ok());
+
+        let progress: Vec<_> = rx.try_iter().collect();
+        assert_eq!(progress.len(), 2);
+        assert!(matches!(&progress[1].data, Data::Action(action) if action.progress == 100.0));
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn crunch_future() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let origin = "application".to_string();
+        let api_key = Key("secret".to_string());
+        let ctx = egui::Context::default();
+        let servers = vec![(
+            7,
+            ServerSpec {
+                name: "crunchy".to_string(),
+                server_type: "cx11".to_string(),
+                location: "nbg1".to_string(),
+            },
+        )];
+        let future = Hetzner::crunch_future(
+            api_key,
+            servers,
+            PreShutdown::default(),
+            app_label(),
+            origin,
+            tx,
+            ctx,
+        )
+        .await;
+        assert!(future.is_ok());
+
+        let steps: Vec<Step> = rx
+            .try_iter()
+            .filter_map(|remote| match remote.data {
+                Data::Workflow(update) => Some(update.step),
+                Data::Action(_) => None,
+                data => panic!("unexpected {:?}", data),
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                Step::Shutdown,
+                Step::AwaitOff,
+                Step::Snapshot,
+                Step::AwaitSnapshot,
+                Step::Delete,
+                Step::Done
+            ]
+        );
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn crunch_future_prepare() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let ctx = egui::Context::default();
+        let spec = ServerSpec {
+            name: "crunchy".to_string(),
+            server_type: "cx11".to_string(),
+            location: "nbg1".to_string(),
+        };
+        let crunch = |servers: Vec<(i32, ServerSpec)>, prepare: PreShutdown| {
+            let (tx, ctx) = (tx.clone(), ctx.clone());
+            async move {
+                let origin = "application".to_string();
+                let key = Key("secret".to_string());
+                Hetzner::crunch_future(key, servers, prepare, app_label(), origin, tx, ctx)
+                    .await
+                    .unwrap();
+            }
+        };
+        let (port, received) = fake_rcon_server().await;
+        let rcon = RconShutdown {
+            port,
+            password: Secret::Unencrypted(Key(RCON_PASSWORD.to_string())),
+            countdown: vec![],
+            commands: vec!["save-all".to_string()],
+            ..Default::default()
+        };
+        let prepare = PreShutdown {
+            rcon: Some(rcon),
+            hooks: None,
+            hosts: HashMap::from([(7, Some("127.0.0.1".to_string())), (8, None)]),
+        };
+
+        crunch(vec![(7, spec.clone())], prepare.clone()).await;
+        let data: Vec<Data> = rx.try_iter().map(|remote| remote.data).collect();
+        assert!(matches!(
+            &data[..3],
+            [Data::Workflow(rcon), Data::Rcon(output), Data::Workflow(shutdown)]
+                if rcon.step == Step::Rcon
+                    && output == "> save-all\necho save-all"
+                    && shutdown.step == Step::Shutdown
+        ));
+        assert!(matches!(data.last(), Some(Data::Workflow(done)) if done.step == Step::Done));
+        assert_eq!(*received.lock().unwrap(), ["save-all"]);
+
+        // Nothing listens on port 1, so the hook fails to connect and no server is touched
+        let hooks = ShutdownHooks {
+            port: 1,
+            commands: vec!["systemctl stop game".to_string()],
+            timeout_secs: 15,
+            ..Default::default()
+        };
+        let failing = PreShutdown {
+            hooks: Some(hooks),
+            ..prepare.clone()
+        };
+        crunch(vec![(7, spec.clone()), (8, spec.clone())], failing).await;
+        let steps: Vec<Step> = rx
+            .try_iter()
+            .filter_map(|remote| match remote.data {
+                Data::Workflow(update) => Some(update.step),
+                Data::Rcon(_) => None,
+                Data::Error(e) => {
+                    assert!(e.contains("crunch aborted"), "{}", e);
+                    None
+                }
+                data => panic!("unexpected {:?}", data),
+            })
+            .collect();
+        assert_eq!(steps, [Step::Rcon, Step::Hooks]);
+
+        // Servers without an address can't be prepared
+        crunch(vec![(8, spec)], prepare).await;
+        let data: Vec<Data> = rx.try_iter().map(|remote| remote.data).collect();
+        assert!(matches!(data.as_slice(), [Data::Error(e)] if e.contains("no public IPv4")));
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn convert_backup_future() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let origin = "backed up".to_string();
+        let api_key = Key("secret".to_string());
+        let ctx = egui::Context::default();
+        let future = Hetzner::convert_backup_future(api_key, 9, app_label(), origin, tx, ctx).await;
+        assert!(future.is_ok());
+
+        match rx.try_recv().map(|remote| remote.data) {
+            Ok(Data::Image(image)) => {
+                assert_eq!(image.id, 9);
+                // Spaces aren't allowed in label values
+                let label = image.labels.get(DEFAULT_APP_LABEL);
+                assert_eq!(label.map(String::as_str), Some("backed-up"));
+            }
+            other => panic!("expected the converted image, got {:?}", other),
+        }
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn prune_future() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let origin = "pruned".to_string();
+        let api_key = Key("secret".to_string());
+        let ctx = egui::Context::default();
+        let ids = vec![11, PROTECTED_IMAGE_ID, 12];
+        let future = Hetzner::prune_future(api_key, ids, origin, tx, ctx).await;
+        assert!(future.is_ok());
+
+        let messages: Vec<Data> = rx.try_iter().map(|remote| remote.data).collect();
+        match messages.as_slice() {
+            [Data::Pruned(deleted), Data::Error(error)] => {
+                assert_eq!(deleted, &vec![11, 12]);
+                assert!(error.contains("protected"));
+            }
+            other => panic!("expected pruned snapshots and an error, got {:?}", other),
+        }
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn metrics_future() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let api_key = Key("secret".to_string());
+        let ctx = egui::Context::default();
+        let application = "label:game".to_string();
+        let future = Hetzner::metrics_future(api_key, application, vec![1, 2], 60, tx, ctx).await;
         assert!(future.is_ok());
-        assert!(rx.recv().is_ok());
+
+        match rx.try_recv().map(|remote| remote.data) {
+            Ok(Data::Metrics {
+                application,
+                metrics: Ok(metrics),
+            }) => {
+                assert_eq!(application, "label:game");
+                assert_eq!(metrics.len(), 2);
+                let latest = metrics[1].latest().unwrap();
+                assert_eq!(latest.cpu, 2.5);
+                assert_eq!(latest.network, 150.0);
+            }
+            other => panic!("expected metrics, got {:?}", other),
+        }
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn restore_future() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let origin = "application".to_string();
+        let api_key = Key("secret".to_string());
+        let ctx = egui::Context::default();
+        let spec = RestoreSpec {
+            image_id: 3,
+            server: ServerSpec {
+                name: "uncrunchy".to_string(),
+                server_type: "cx11".to_string(),
+                location: "nbg1".to_string(),
+            },
+        };
+        let future =
+            Hetzner::restore_future(api_key, spec, None, app_label(), origin, tx, ctx).await;
+        assert!(future.is_ok());
+
+        let updates: Vec<(Step, i32)> = rx
+            .try_iter()
+            .filter_map(|remote| match remote.data {
+                Data::Workflow(update) => Some((update.step, update.server_id)),
+                Data::Action(_) => None,
+                data => panic!("unexpected {:?}", data),
+            })
+            .collect();
+        let id = CREATED_SERVER_ID;
+        assert_eq!(
+            updates,
+            vec![
+                (Step::Create, id),
+                (Step::AwaitRunning, id),
+                (Step::Done, id)
+            ]
+        );
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn restore_future_health() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let spec = RestoreSpec {
+            image_id: 3,
+            server: ServerSpec {
+                name: "uncrunchy".to_string(),
+                server_type: "cx11".to_string(),
+                location: "nbg1".to_string(),
+            },
+        };
+        let checks = HealthChecks {
+            checks: vec![HealthCheck::tcp()],
+            ..Default::default()
+        };
+        let api_key = Key("secret".to_string());
+        let (origin, ctx) = ("application".to_string(), egui::Context::default());
+        let checks = Some(("label:application".to_string(), checks));
+        let future = Hetzner::restore_future(api_key, spec, checks, app_label(), origin, tx, ctx);
+        assert!(future.await.is_ok());
+
+        let data: Vec<Data> = rx
+            .try_iter()
+            .map(|remote| remote.data)
+            .filter(|data| !matches!(data, Data::Action(_)))
+            .collect();
+        let steps: Vec<Step> = data
+            .iter()
+            .filter_map(|data| match data {
+                Data::Workflow(update) => Some(update.step),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                Step::Create,
+                Step::AwaitRunning,
+                Step::HealthCheck,
+                Step::Done
+            ]
+        );
+        // The mocked server has no address to check, which fails the checks but not the restore
+        let failed = data.iter().find_map(|data| match data {
+            Data::Health {
+                application,
+                health: Err(error),
+            } if application == "label:application" => Some(error.clone()),
+            _ => None,
+        });
+        assert!(failed.unwrap().contains("no public IPv4"));
+    }
+
+    #[cfg_attr(not(mock), ignore = "mocking is disabled")]
+    #[tokio::test]
+    async fn resume_future() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let origin = "application".to_string();
+        let api_key = Key("secret".to_string());
+        let ctx = egui::Context::default();
+
+        let mut journal = Journal::default();
+        journal.record(
+            &origin,
+            &WorkflowUpdate::new(Step::AwaitSnapshot, 7).with_image(3),
+        );
+        let entries = journal.entries().to_vec();
+        let future = Hetzner::resume_future(api_key, origin, entries, tx, ctx).await;
+        assert!(future.is_ok());
+
+        let steps: Vec<Step> = rx
+            .try_iter()
+            .filter_map(|remote| match remote.data {
+                Data::Workflow(update) => Some(update.step),
+                Data::Action(_) => None,
+                data => panic!("unexpected {:?}", data),
+            })
+            .collect();
+        assert_eq!(steps, vec![Step::AwaitSnapshot, Step::Delete, Step::Done]);
     }
 }
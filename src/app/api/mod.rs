@@ -1,11 +1,13 @@
 use super::App;
-use crate::utils::Error;
+use crate::utils::{Application, Error, JournalEntry, RestoreSpec};
 use egui::Context;
 use serde::{Deserialize, Serialize};
 
 mod hetzner;
 pub use hetzner::Hetzner;
 
+// Not wired up yet, only `Hetzner` can be configured so far
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize)]
 pub enum Endpoints {
     Unconfigured,
@@ -13,9 +15,57 @@ pub enum Endpoints {
 }
 
 const NO_API_ENDPOINT: &str = "No API endpoint configured";
+/// Origin of fetched metrics, they don't keep their application busy
+pub const METRICS_ORIGIN: &str = "metrics";
+
+/// Power actions that can be performed on a single server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerAction {
+    Shutdown,
+    PowerOff,
+    PowerOn,
+    Reboot,
+}
+impl ServerAction {
+    pub const ALL: [ServerAction; 4] = [
+        ServerAction::Shutdown,
+        ServerAction::PowerOff,
+        ServerAction::PowerOn,
+        ServerAction::Reboot,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ServerAction::Shutdown => "Shut down",
+            ServerAction::PowerOff => "Power off",
+            ServerAction::PowerOn => "Power on",
+            ServerAction::Reboot => "Reboot",
+        }
+    }
+}
 
 pub trait Endpoint {
     fn req_application_list(&self, app: &mut App, origin: &'static str, ctx: &Context);
+    fn req_server_action(
+        &self,
+        app: &mut App,
+        server_id: i32,
+        action: ServerAction,
+        origin: &str,
+        ctx: &Context,
+    );
+    /// Shuts down, snapshots and deletes every server of an application
+    fn req_crunch(&self, app: &mut App, application: &Application, origin: &str, ctx: &Context);
+    /// Provisions a new server from a snapshot
+    fn req_restore(&self, app: &mut App, spec: RestoreSpec, origin: &str, ctx: &Context);
+    /// Continues or rolls back a workflow that was interrupted by the app closing
+    fn req_resume(&self, app: &mut App, entries: Vec<JournalEntry>, origin: &str, ctx: &Context);
+    /// Turns an automatic backup into a snapshot, so it outlives its server
+    fn req_convert_backup(&self, app: &mut App, image_id: i32, origin: &str, ctx: &Context);
+    /// Deletes snapshots, skipping any that are protected against deletion
+    fn req_prune(&self, app: &mut App, image_ids: Vec<i32>, origin: &str, ctx: &Context);
+    /// Fetches CPU and network usage of the last minutes for every server of an application
+    fn req_metrics(&self, app: &mut App, application: &Application, minutes: u32, ctx: &Context);
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +75,46 @@ impl Endpoint for Unconfigured {
         app.error_log.push(Error::new(NO_API_ENDPOINT));
         app.unset_loading(origin);
     }
+    fn req_server_action(
+        &self,
+        app: &mut App,
+        _server_id: i32,
+        _action: ServerAction,
+        origin: &str,
+        _ctx: &Context,
+    ) {
+        app.error_log.push(Error::new(NO_API_ENDPOINT));
+        app.unset_loading(origin);
+    }
+    fn req_crunch(&self, app: &mut App, _application: &Application, origin: &str, _ctx: &Context) {
+        app.error_log.push(Error::new(NO_API_ENDPOINT));
+        app.unset_loading(origin);
+    }
+    fn req_restore(&self, app: &mut App, _spec: RestoreSpec, origin: &str, _ctx: &Context) {
+        app.error_log.push(Error::new(NO_API_ENDPOINT));
+        app.unset_loading(origin);
+    }
+    fn req_resume(&self, app: &mut App, _entries: Vec<JournalEntry>, origin: &str, _ctx: &Context) {
+        app.error_log.push(Error::new(NO_API_ENDPOINT));
+        app.unset_loading(origin);
+    }
+    fn req_convert_backup(&self, app: &mut App, _image_id: i32, origin: &str, _ctx: &Context) {
+        app.error_log.push(Error::new(NO_API_ENDPOINT));
+        app.unset_loading(origin);
+    }
+    fn req_prune(&self, app: &mut App, _image_ids: Vec<i32>, origin: &str, _ctx: &Context) {
+        app.error_log.push(Error::new(NO_API_ENDPOINT));
+        app.unset_loading(origin);
+    }
+    fn req_metrics(
+        &self,
+        app: &mut App,
+        _application: &Application,
+        _minutes: u32,
+        _ctx: &Context,
+    ) {
+        app.error_log.push(Error::new(NO_API_ENDPOINT));
+    }
 }
 
 impl App {
@@ -32,11 +122,45 @@ impl App {
         let endpoint = self.endpoint.clone();
         endpoint.req_application_list(self, origin, ctx);
     }
+    pub fn req_server_action(
+        &mut self,
+        server_id: i32,
+        action: ServerAction,
+        origin: &str,
+        ctx: &Context,
+    ) {
+        let endpoint = self.endpoint.clone();
+        endpoint.req_server_action(self, server_id, action, origin, ctx);
+    }
+    pub fn req_crunch(&mut self, application: &Application, origin: &str, ctx: &Context) {
+        let endpoint = self.endpoint.clone();
+        endpoint.req_crunch(self, application, origin, ctx);
+    }
+    pub fn req_restore(&mut self, spec: RestoreSpec, origin: &str, ctx: &Context) {
+        let endpoint = self.endpoint.clone();
+        endpoint.req_restore(self, spec, origin, ctx);
+    }
+    pub fn req_resume(&mut self, entries: Vec<JournalEntry>, origin: &str, ctx: &Context) {
+        let endpoint = self.endpoint.clone();
+        endpoint.req_resume(self, entries, origin, ctx);
+    }
+    pub fn req_convert_backup(&mut self, image_id: i32, origin: &str, ctx: &Context) {
+        let endpoint = self.endpoint.clone();
+        endpoint.req_convert_backup(self, image_id, origin, ctx);
+    }
+    pub fn req_prune(&mut self, image_ids: Vec<i32>, origin: &str, ctx: &Context) {
+        let endpoint = self.endpoint.clone();
+        endpoint.req_prune(self, image_ids, origin, ctx);
+    }
+    pub fn req_metrics(&mut self, application: &Application, minutes: u32, ctx: &Context) {
+        let endpoint = self.endpoint.clone();
+        endpoint.req_metrics(self, application, minutes, ctx);
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::NO_API_ENDPOINT;
+    use super::{ServerAction, NO_API_ENDPOINT};
     use crate::app::App;
     use egui::Context;
 
@@ -48,4 +172,16 @@ mod test {
         endpoint.req_application_list(&mut app, "", &ctx);
         assert_eq!(app.error_log[0].error, NO_API_ENDPOINT)
     }
+
+    #[test]
+    fn req_server_action_unconfigured() {
+        const ORIGIN: &str = "server";
+        let mut app = App::default();
+        let ctx = Context::default();
+        app.set_loading(ORIGIN);
+        let endpoint = app.endpoint.clone();
+        endpoint.req_server_action(&mut app, 0, ServerAction::Shutdown, ORIGIN, &ctx);
+        assert_eq!(app.error_log[0].error, NO_API_ENDPOINT);
+        assert!(!app.remote_loading.contains(ORIGIN));
+    }
 }
@@ -3,24 +3,33 @@ use std::rc::Rc;
 use super::App;
 use crate::{
     app::api::Hetzner,
-    utils::{Key, Secret},
+    utils::{Key, Secret, DEFAULT_APP_LABEL},
 };
 use egui::{Context, TextEdit, Window};
 
 #[derive(Default, Clone)]
 pub struct ApiPerfsData {
     buf: String,
+    label_buf: String,
     open: bool,
 }
 
 impl App {
     pub fn draw_api_perfs_window(&mut self, ctx: &Context) {
-        let ApiPerfsData { mut open, mut buf } = self.api_perfs.clone();
+        let ApiPerfsData {
+            mut open,
+            mut buf,
+            mut label_buf,
+        } = self.api_perfs.clone();
         Window::new("API Preferences")
             .open(&mut open)
             .show(ctx, |ui| {
                 ui.label("HCloud API Key"); // TODO: Add info about how tokens are stored
                 ui.add(TextEdit::singleline(&mut buf).password(true));
+                ui.label("Application Label").on_hover_text(
+                    "Servers and snapshots sharing this label's value form an application",
+                );
+                ui.add(TextEdit::singleline(&mut label_buf).hint_text(DEFAULT_APP_LABEL));
                 ui.separator();
                 ui.add_enabled_ui(self.enable_submit(), |ui| {
                     if ui.button("Submit").clicked() {
@@ -35,6 +44,7 @@ impl App {
         }
 
         self.api_perfs.buf = buf;
+        self.api_perfs.label_buf = label_buf;
     }
     pub fn open_api_perfs_window(&mut self) {
         if !self.api_perfs.open {
@@ -42,6 +52,7 @@ impl App {
                 Some(Secret::Unencrypted(Key(token))) => token,
                 _ => Default::default(),
             };
+            self.api_perfs.label_buf = self.app_label.clone();
             self.api_perfs.open = true;
         }
     }
@@ -53,7 +64,13 @@ impl App {
     fn submit(&mut self) {
         self.hcloud_api_secret = Some(Secret::Unencrypted(Key(self.api_perfs.buf.to_owned())));
         self.endpoint = Rc::new(Hetzner);
+        self.app_label = match self.api_perfs.label_buf.trim() {
+            "" => DEFAULT_APP_LABEL.to_string(),
+            label => label.to_string(),
+        };
         self.api_perfs.open = false;
+        // Grouping may have changed along with the label
+        self.refresh_requested = true;
     }
 }
 #[cfg(test)]
@@ -97,9 +114,12 @@ mod test {
         let secret = String::from("Some data");
         app.api_perfs = ApiPerfsData {
             buf: secret.clone(),
+            label_buf: " project.app ".to_string(),
             open: true,
         };
         app.submit();
+        assert_eq!(app.app_label, "project.app");
+        assert!(app.refresh_requested);
 
         assert!(!app.api_perfs.open);
         assert!(matches!(
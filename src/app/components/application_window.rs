@@ -1,26 +1,207 @@
 use super::App;
-use crate::utils::{Application, Data, RemoteData};
-use egui::{Context, Window};
+use crate::{
+    app::{api::ServerAction, proxy},
+    utils::{
+        cost::CostBreakdown, Application, Data, GameQuery, HealthCheck, IdleRule, Lifecycle, Plan,
+        PrunePreview, QueryProtocol, RemoteData, Resource, RestoreSpec, ServerSpec, ShutdownHooks,
+        WakeMode, WakeProxy,
+    },
+};
+use egui::{
+    Button, Checkbox, Color32, ComboBox, Context, DragValue, Id, ProgressBar, RichText, TextEdit,
+    Ui, Window,
+};
+use hcloud::models::Image;
+
+/// Requests collected while drawing, dispatched once all windows are drawn
+enum Request {
+    Power(i32, ServerAction),
+    Crunch(Application),
+    Restore(RestoreSpec),
+    ConvertBackup(i32),
+    /// Delete the snapshots a retention policy doesn't keep
+    Prune(PrunePreview),
+    /// Move resources into the named application
+    Regroup(Vec<Resource>, String),
+    /// Drop the manual grouping of resources
+    ResetGrouping(Vec<Resource>),
+}
 
 impl App {
-    pub fn draw_application_windows(&self, ctx: &Context) {
+    pub fn draw_application_windows(&mut self, ctx: &Context) {
+        let mut requests = Vec::new();
+
+        // Taken out for the duration of drawing, so windows can borrow the rest of the app
+        let application_list = self.application_list.take();
         if let Some(RemoteData {
             data: Data::Application(applications),
             ..
-        }) = &self.application_list
+        }) = &application_list
         {
+            let names: Vec<String> = applications
+                .iter()
+                .filter_map(|app| app.name.clone())
+                .collect();
             for application in applications {
-                Self::draw_application_window(application, ctx);
+                self.draw_application_window(application, &names, &mut requests, ctx);
+            }
+        }
+        self.application_list = application_list;
+
+        for (origin, request) in requests {
+            match request {
+                Request::Power(server_id, action) => {
+                    self.set_loading(&origin);
+                    self.req_server_action(server_id, action, &origin, ctx)
+                }
+                // Destructive requests only run once their plan has been confirmed
+                Request::Crunch(application) => {
+                    self.plan = Some(self.plan_crunch(&origin, &application));
+                }
+                Request::Restore(spec) => {
+                    self.plan = Some(self.plan_restore(&origin, &spec));
+                }
+                Request::ConvertBackup(image_id) => {
+                    self.set_loading(&origin);
+                    self.req_convert_backup(image_id, &origin, ctx);
+                }
+                Request::Prune(preview) => {
+                    self.plan = Some(Plan::prune(&origin, &preview));
+                }
+                Request::Regroup(resources, target) => {
+                    for resource in resources {
+                        self.overrides.assign(resource, &target);
+                    }
+                    self.rename_forms.remove(&origin);
+                    self.regroup();
+                }
+                Request::ResetGrouping(resources) => {
+                    for resource in resources {
+                        self.overrides.reset(resource);
+                    }
+                    // Automatic grouping needs the original lists
+                    self.refresh_requested = true;
+                }
             }
         }
     }
 
-    fn draw_application_window(application: &Application, ctx: &Context) {
-        let Application { name, status, .. } = application;
+    /// Applies the manual grouping to the current application list
+    fn regroup(&mut self) {
+        if let Some(RemoteData {
+            data: Data::Application(applications),
+            ..
+        }) = &mut self.application_list
+        {
+            *applications = self.overrides.apply(std::mem::take(applications));
+            self.identities.apply(applications, false);
+        }
+    }
+
+    fn draw_application_window(
+        &mut self,
+        application: &Application,
+        names: &[String],
+        requests: &mut Vec<(String, Request)>,
+        ctx: &Context,
+    ) {
+        let name = application.name.clone().unwrap();
+        let loading = self.remote_loading.contains(&name);
+        let workflow = self.workflows.get(&name).cloned();
+        let pending = self.actions.pending(&name);
+        let lifecycle = Lifecycle::of(application, workflow.as_ref(), pending)
+            .with_health(self.healthy.get(application.id()).copied());
+        let costs = CostBreakdown::of(application, self.vat_rate());
         let mut counter = 0;
 
-        Window::new(name.clone().unwrap()).show(ctx, |ui| {
-            ui.label(status.clone().unwrap());
+        // Keyed by ID, so position and size stay with the application across refreshes
+        let id = Id::new(("application", application.id()));
+        Window::new(name.clone()).id(id).show(ctx, |ui| {
+            Self::lifecycle_badge(ui, lifecycle);
+            let occupancy = self.occupancy.get(application.id());
+            let players = occupancy.map_or(0, |occupancy| occupancy.players());
+            if let Some(occupancy) = occupancy {
+                let motds: Vec<&str> = occupancy
+                    .statuses
+                    .iter()
+                    .filter_map(|(_, status)| Some(status.as_ref().ok()?.motd.as_str()))
+                    .filter(|motd| !motd.is_empty())
+                    .collect();
+                ui.label(format!(
+                    "👥 {}/{} online {}",
+                    players,
+                    occupancy.max_players(),
+                    motds.join(" · ")
+                ));
+            }
+
+            match workflow {
+                Some(update) => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(update.step.label());
+                    });
+                }
+                None if lifecycle.can_crunch() => {
+                    ui.horizontal(|ui| {
+                        let forced = self.forced_crunch.contains(application.id());
+                        let blocked = players > 0 && !forced;
+                        let reason = match self.list_incomplete {
+                            true => "The application list is incomplete",
+                            false => "Players are online",
+                        };
+                        let enabled = !loading && !blocked && !self.list_incomplete;
+                        let crunch = ui
+                            .add_enabled(enabled, Button::new("🗜 Crunch"))
+                            .on_hover_text("Shut down, snapshot and delete all servers")
+                            .on_disabled_hover_text(reason);
+                        if crunch.clicked() {
+                            self.forced_crunch.remove(application.id());
+                            requests.push((name.clone(), Request::Crunch(application.clone())));
+                        }
+                        let mut force = forced;
+                        if players > 0 && ui.checkbox(&mut force, "Force").changed() {
+                            match force {
+                                true => self.forced_crunch.insert(application.id().to_string()),
+                                false => self.forced_crunch.remove(application.id()),
+                            };
+                        }
+                    });
+                }
+                None if lifecycle.can_restore() => {
+                    let loading = loading || self.list_incomplete;
+                    if let Some(spec) = self.restore_form(application, ui, loading) {
+                        requests.push((name.clone(), Request::Restore(spec)));
+                    }
+                }
+                None => (),
+            }
+            for action in self.actions.pending(&name) {
+                ui.add(
+                    ProgressBar::new(action.progress / 100.0)
+                        .text(format!("{} {}%", action.command, action.progress)),
+                );
+            }
+            if let Some(request) = self.grouping_form(application, names, ui, loading) {
+                requests.push((name.clone(), request));
+            }
+            if let Some(request) = self.retention_form(application, ui, loading) {
+                requests.push((name.clone(), request));
+            }
+            self.budget_form(application, ui);
+            self.schedule_form(application, ui);
+            self.idle_form(application, ui);
+            self.players_form(application, ui);
+            self.wake_form(application, ui);
+            self.rcon_form(application, ui);
+            self.hooks_form(application, ui);
+            self.health_form(application, ui);
+            let monthly = costs.total().monthly.get(self.show_gross);
+            ui.collapsing(format!("💶 Costs: {:.2} €/mo", monthly), |ui| {
+                Self::gross_toggle(ui, &mut self.show_gross);
+                Self::cost_grid(ui, "application_costs", &costs, self.show_gross);
+            });
+            ui.separator();
 
             if let Some(servers) = &application.servers {
                 for server in servers {
@@ -33,6 +214,10 @@ impl App {
                         server.datacenter.description.as_str()
                     ));
                     ui.label(format!("Status: {:?}", server.status));
+                    let enabled = !loading && lifecycle.can_power();
+                    if let Some(action) = Self::power_buttons(ui, enabled, loading) {
+                        requests.push((name.clone(), Request::Power(server.id, action)));
+                    }
                     ui.push_id(counter, |ui| {
                         ui.collapsing("details", |ui| ui.label(format!("{:?}", server)));
                     });
@@ -41,6 +226,14 @@ impl App {
                 }
             }
 
+            let enabled = !loading && lifecycle.can_power();
+            for backup in application.backups.iter().flatten() {
+                if let Some(request) = Self::backup_row(application, backup, ui, enabled) {
+                    requests.push((name.clone(), request));
+                }
+                ui.separator();
+            }
+
             if let Some(images) = &application.images {
                 for image in images {
                     ui.label(image.description.clone());
@@ -56,4 +249,694 @@ impl App {
             }
         });
     }
+
+    /// Automatic backup with buttons to restore it as a new server or keep it as a snapshot
+    fn backup_row(
+        application: &Application,
+        backup: &Image,
+        ui: &mut Ui,
+        enabled: bool,
+    ) -> Option<Request> {
+        let server = application
+            .servers
+            .iter()
+            .flatten()
+            .find(|server| Some(server.id) == backup.bound_to)?;
+        let latest = application.latest_backup().map(|latest| latest.id) == Some(backup.id);
+        let mut request = None;
+
+        ui.label(format!(
+            "Backup of {} from {}{}",
+            server.name,
+            backup.created,
+            if latest { " (latest)" } else { "" }
+        ));
+        ui.add_enabled_ui(enabled, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("🔄 Restore")
+                    .on_hover_text("Provision a new server from this backup")
+                    .clicked()
+                {
+                    let mut spec = ServerSpec::from(server);
+                    spec.name = format!("{}-restored", spec.name);
+                    request = Some(Request::Restore(RestoreSpec {
+                        image_id: backup.id,
+                        server: spec,
+                    }));
+                }
+                if ui
+                    .button("📸 Keep as snapshot")
+                    .on_hover_text("Backups are deleted along with their server, snapshots are not")
+                    .clicked()
+                {
+                    request = Some(Request::ConvertBackup(backup.id));
+                }
+            });
+        });
+        request
+    }
+
+    fn lifecycle_badge(ui: &mut Ui, lifecycle: Lifecycle) {
+        let color = match lifecycle {
+            Lifecycle::Ready => Color32::from_rgb(40, 160, 70),
+            Lifecycle::Running => Color32::from_rgb(80, 130, 90),
+            Lifecycle::Stopped => Color32::GRAY,
+            Lifecycle::Crunching | Lifecycle::Restoring => Color32::from_rgb(50, 120, 200),
+            Lifecycle::Crunched => Color32::from_rgb(120, 90, 180),
+            Lifecycle::Degraded => Color32::from_rgb(200, 60, 50),
+            Lifecycle::Orphaned => Color32::from_rgb(200, 150, 30),
+        };
+        let badge = RichText::new(format!(" {} ", lifecycle.label()))
+            .color(Color32::WHITE)
+            .background_color(color)
+            .strong();
+        ui.label(badge);
+    }
+
+    fn power_buttons(ui: &mut Ui, enabled: bool, loading: bool) -> Option<ServerAction> {
+        let mut clicked = None;
+        ui.horizontal(|ui| {
+            if loading {
+                ui.spinner();
+            }
+            ui.add_enabled_ui(enabled, |ui| {
+                for action in ServerAction::ALL {
+                    if ui.button(action.label()).clicked() {
+                        clicked = Some(action);
+                    }
+                }
+            });
+        });
+        clicked
+    }
+
+    /// Manual grouping: rename this application, merge it into another one, or move single
+    /// servers and images elsewhere
+    fn grouping_form(
+        &mut self,
+        application: &Application,
+        names: &[String],
+        ui: &mut Ui,
+        loading: bool,
+    ) -> Option<Request> {
+        let name = application.name.clone()?;
+        let resources = application.resources();
+        let others: Vec<&String> = names.iter().filter(|other| **other != name).collect();
+        let mut request = None;
+
+        ui.add_enabled_ui(!loading, |ui| {
+            ui.collapsing("🗂 Grouping", |ui| {
+                ui.horizontal(|ui| {
+                    let rename = self
+                        .rename_forms
+                        .entry(name.clone())
+                        .or_insert_with(|| name.clone());
+                    ui.add(TextEdit::singleline(rename).desired_width(120.0));
+                    let valid = !rename.is_empty() && *rename != name;
+                    if ui.add_enabled(valid, Button::new("Rename")).clicked() {
+                        request = Some(Request::Regroup(resources.clone(), rename.clone()));
+                    }
+                });
+                if let Some(target) = Self::move_to(ui, "Merge into", &others, None) {
+                    request = Some(Request::Regroup(resources.clone(), target));
+                }
+                ui.separator();
+
+                for server in application.servers.iter().flatten() {
+                    let resource = Resource::Server(server.id);
+                    let label = format!("Server {}", server.name);
+                    if let Some(target) = Self::move_to(ui, &label, &others, Some(&server.name)) {
+                        request = Some(Request::Regroup(vec![resource], target));
+                    }
+                }
+                for image in application.images.iter().flatten() {
+                    let resource = Resource::Image(image.id);
+                    let split = format!("{} {}", name, image.id);
+                    let label = format!("Image {}", image.id);
+                    if let Some(target) = Self::move_to(ui, &label, &others, Some(&split)) {
+                        request = Some(Request::Regroup(vec![resource], target));
+                    }
+                }
+
+                let overridden: Vec<Resource> = resources
+                    .iter()
+                    .copied()
+                    .filter(|resource| self.overrides.get(*resource).is_some())
+                    .collect();
+                if !overridden.is_empty()
+                    && ui
+                        .button("Reset grouping")
+                        .on_hover_text("Group these resources by label and name again")
+                        .clicked()
+                {
+                    request = Some(Request::ResetGrouping(overridden));
+                }
+            });
+        });
+
+        request
+    }
+
+    /// Combo box picking another application, or a new one of the given name
+    fn move_to(ui: &mut Ui, label: &str, others: &[&String], new: Option<&str>) -> Option<String> {
+        let mut selected = None;
+        ComboBox::from_label(label)
+            .selected_text("Move to…")
+            .show_ui(ui, |ui| {
+                for other in others {
+                    if ui.selectable_label(false, other.as_str()).clicked() {
+                        selected = Some(other.to_string());
+                    }
+                }
+                if let Some(new) = new {
+                    if ui
+                        .selectable_label(false, format!("New: {}", new))
+                        .clicked()
+                    {
+                        selected = Some(new.to_string());
+                    }
+                }
+            });
+        selected
+    }
+
+    /// Retention policy of the snapshots, with a preview of what pruning would delete
+    fn retention_form(
+        &mut self,
+        application: &Application,
+        ui: &mut Ui,
+        loading: bool,
+    ) -> Option<Request> {
+        let images = application.images.as_ref()?;
+        let known = self.retention.get(application.id()).cloned();
+        let mut policy = known.clone().unwrap_or_default();
+        let mut request = None;
+
+        ui.collapsing("🧹 Retention", |ui| {
+            egui::Grid::new("retention_form")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Keep last");
+                    ui.add(DragValue::new(&mut policy.keep_last).clamp_range(1..=100));
+                    ui.end_row();
+                    ui.label("Keep weekly for");
+                    ui.add(
+                        DragValue::new(&mut policy.keep_weekly)
+                            .clamp_range(0..=104)
+                            .suffix(" weeks"),
+                    );
+                    ui.end_row();
+                });
+            ui.add(Checkbox::new(
+                &mut policy.automatic,
+                "Prune automatically after each refresh",
+            ));
+            ui.label(RichText::new("The newest and protected snapshots are always kept").italics());
+
+            let preview = policy.preview(images, chrono::Utc::now());
+            ui.label(format!(
+                "{} snapshot(s) to prune, saving {:.2} € per month",
+                preview.prune.len(),
+                preview.monthly_savings
+            ));
+            for image in &preview.prune {
+                ui.label(format!("• {} ({})", image.id, image.created));
+            }
+            let enabled = !loading && !preview.is_empty();
+            if ui.add_enabled(enabled, Button::new("Prune")).clicked() {
+                request = Some(Request::Prune(preview));
+            }
+        });
+
+        // Only store policies that were actually changed
+        if known.as_ref() != Some(&policy) && (known.is_some() || policy != Default::default()) {
+            self.retention.insert(application.id().to_string(), policy);
+        }
+        request
+    }
+
+    /// Monthly budget of the application, along with its month-to-date spend
+    fn budget_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id();
+        let mut budget = self.budgets.get(id);
+        ui.collapsing("💰 Budget", |ui| {
+            Self::budget_editor(ui, &mut budget);
+            if let Some(budget) = budget {
+                let status = self.spend.status(id, budget, chrono::Utc::now());
+                Self::budget_status(ui, &status);
+            }
+        });
+        if budget != self.budgets.get(id) {
+            self.budgets.set(id, budget);
+        }
+    }
+
+    /// Idle rule of the application, along with its latest usage
+    fn idle_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id();
+        let known = self.idle_rules.get(id).copied();
+        let mut rule = known;
+        ui.collapsing("💤 Idle", |ui| {
+            let mut enabled = rule.is_some();
+            if ui
+                .add(Checkbox::new(&mut enabled, "Crunch when idle"))
+                .changed()
+            {
+                rule = enabled.then(IdleRule::default);
+            }
+            let rule = match &mut rule {
+                Some(rule) => rule,
+                None => return,
+            };
+            egui::Grid::new("idle_form").num_columns(2).show(ui, |ui| {
+                ui.label("CPU below");
+                ui.add(
+                    DragValue::new(&mut rule.max_cpu)
+                        .speed(0.5)
+                        .clamp_range(0.0..=400.0)
+                        .suffix(" %"),
+                );
+                ui.end_row();
+                ui.label("Network below");
+                ui.add(
+                    DragValue::new(&mut rule.max_network)
+                        .speed(1.0)
+                        .clamp_range(0.0..=f64::MAX)
+                        .suffix(" kB/s"),
+                );
+                ui.end_row();
+                ui.label("For");
+                ui.add(
+                    DragValue::new(&mut rule.minutes)
+                        .clamp_range(5..=1440)
+                        .suffix(" min"),
+                );
+                ui.end_row();
+            });
+            ui.add(Checkbox::new(&mut rule.automatic, "Crunch without asking"));
+
+            let metrics = match self.metrics.get(id) {
+                Some(metrics) => metrics,
+                None => {
+                    ui.label(RichText::new("No metrics yet").italics());
+                    return;
+                }
+            };
+            for server in metrics {
+                if let Some(sample) = server.latest() {
+                    ui.label(format!(
+                        "Server {}: CPU {:.1} %, network {:.1} kB/s",
+                        server.server,
+                        sample.cpu,
+                        sample.network / 1000.0
+                    ));
+                }
+            }
+            match rule.idle_for(metrics) {
+                Some(idle) => ui.label(format!(
+                    "Idle for {} of {} min",
+                    idle.num_minutes(),
+                    rule.minutes
+                )),
+                None => ui.label(RichText::new("No metrics yet").italics()),
+            };
+        });
+        if rule != known {
+            match rule {
+                Some(rule) => self.idle_rules.insert(id.to_string(), rule),
+                None => self.idle_rules.remove(id),
+            };
+        }
+    }
+
+    /// How to ask the game servers who is online, along with the latest answers
+    fn players_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id();
+        let known = self.game_queries.get(id).copied();
+        let mut query = known;
+        ui.collapsing("👥 Players", |ui| {
+            let mut enabled = query.is_some();
+            if ui
+                .add(Checkbox::new(
+                    &mut enabled,
+                    "Ask the game servers who is online",
+                ))
+                .changed()
+            {
+                query = enabled.then(GameQuery::default);
+            }
+            let query = match &mut query {
+                Some(query) => query,
+                None => return,
+            };
+            egui::Grid::new("players_form")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Game");
+                    ComboBox::from_id_source(("query_protocol", id))
+                        .selected_text(query.protocol.label())
+                        .show_ui(ui, |ui| {
+                            for protocol in QueryProtocol::ALL {
+                                let selected = ui.selectable_value(
+                                    &mut query.protocol,
+                                    protocol,
+                                    protocol.label(),
+                                );
+                                if selected.changed() {
+                                    query.port = protocol.default_port();
+                                }
+                            }
+                        });
+                    ui.end_row();
+                    ui.label("Query port");
+                    ui.add(DragValue::new(&mut query.port).clamp_range(1..=u16::MAX));
+                    ui.end_row();
+                });
+            let mut crunch_when_empty = query.empty_minutes.is_some();
+            if ui
+                .add(Checkbox::new(&mut crunch_when_empty, "Crunch when empty"))
+                .changed()
+            {
+                query.empty_minutes = crunch_when_empty.then(|| 30);
+            }
+            if let Some(minutes) = &mut query.empty_minutes {
+                ui.horizontal(|ui| {
+                    ui.label("For");
+                    ui.add(DragValue::new(minutes).clamp_range(1..=1440).suffix(" min"));
+                });
+                ui.add(Checkbox::new(&mut query.automatic, "Crunch without asking"));
+            }
+
+            let occupancy = match self.occupancy.get(id) {
+                Some(occupancy) => occupancy,
+                None => {
+                    ui.label(RichText::new("Not queried yet").italics());
+                    return;
+                }
+            };
+            for (server, status) in &occupancy.statuses {
+                match status {
+                    Ok(status) => ui.label(format!(
+                        "Server {}: {}/{} players, {}",
+                        server, status.players, status.max_players, status.motd
+                    )),
+                    Err(error) => ui.colored_label(Color32::YELLOW, error),
+                };
+            }
+            if let Some(empty) = occupancy.empty_for(chrono::Utc::now()) {
+                ui.label(format!("Empty for {} min", empty.num_minutes()));
+            }
+        });
+        if query != known {
+            match query {
+                Some(query) => self.game_queries.insert(id.to_string(), query),
+                None => self.game_queries.remove(id),
+            };
+        }
+    }
+
+    /// Proxy restoring the application when a client connects, applied on saving so the
+    /// port isn't bound again on every keystroke
+    fn wake_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id().to_string();
+        let known = self.wake_proxies.get(&id).cloned();
+        ui.collapsing("🔁 Wake on connect", |ui| {
+            let form = self
+                .wake_forms
+                .entry(id.clone())
+                .or_insert_with(|| known.clone().unwrap_or_default());
+            egui::Grid::new("wake_form").num_columns(2).show(ui, |ui| {
+                ui.label("Listen on");
+                ui.add(TextEdit::singleline(&mut form.listen).hint_text("0.0.0.0:25565"));
+                ui.end_row();
+                ui.label("Forward to port");
+                ui.add(DragValue::new(&mut form.target_port).clamp_range(1..=u16::MAX));
+                ui.end_row();
+                ui.label("While starting");
+                ComboBox::from_id_source(("wake_mode", &id))
+                    .selected_text(form.mode.label())
+                    .show_ui(ui, |ui| {
+                        for mode in WakeMode::ALL {
+                            ui.selectable_value(&mut form.mode, mode, mode.label());
+                        }
+                    });
+                ui.end_row();
+                if form.mode == WakeMode::Hold {
+                    ui.label("Hold for");
+                    ui.add(
+                        DragValue::new(&mut form.hold_secs)
+                            .clamp_range(1..=3600)
+                            .suffix(" s"),
+                    );
+                    ui.end_row();
+                }
+                ui.label("Message");
+                ui.text_edit_singleline(&mut form.message);
+                ui.end_row();
+            });
+
+            ui.horizontal(|ui| {
+                let changed = known.as_ref() != Some(form);
+                if ui.add_enabled(changed, Button::new("Save")).clicked() {
+                    self.wake_proxies.insert(id.clone(), form.clone());
+                }
+                if ui
+                    .add_enabled(known.is_some(), Button::new("Remove"))
+                    .clicked()
+                {
+                    self.wake_proxies.remove(&id);
+                    *form = WakeProxy::default();
+                }
+            });
+            if let Some(known) = &known {
+                match proxy::target(application, known.target_port) {
+                    Some(target) => ui.label(format!("Forwarding {} to {}", known.listen, target)),
+                    None => ui.label(format!("Restoring on connections to {}", known.listen)),
+                };
+            }
+        });
+    }
+
+    /// Commands to run over SSH on every server before it is shut down
+    fn hooks_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id();
+        let known = self.shutdown_hooks.get(id).cloned();
+        let mut hooks = known.clone();
+        ui.collapsing("🔌 Shutdown hooks", |ui| {
+            let mut enabled = hooks.is_some();
+            if ui
+                .add(Checkbox::new(
+                    &mut enabled,
+                    "Run commands before shutting down",
+                ))
+                .changed()
+            {
+                hooks = enabled.then(ShutdownHooks::default);
+            }
+            let hooks = match &mut hooks {
+                Some(hooks) => hooks,
+                None => return,
+            };
+            egui::Grid::new("hooks_form").num_columns(2).show(ui, |ui| {
+                ui.label("User");
+                ui.text_edit_singleline(&mut hooks.user);
+                ui.end_row();
+                ui.label("Port");
+                ui.add(DragValue::new(&mut hooks.port).clamp_range(1..=u16::MAX));
+                ui.end_row();
+                ui.label("Private key");
+                ui.add(TextEdit::singleline(&mut hooks.key_path).hint_text("~/.ssh/id_ed25519"));
+                ui.end_row();
+                ui.label("Timeout");
+                ui.add(
+                    DragValue::new(&mut hooks.timeout_secs)
+                        .clamp_range(1..=3600)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+            });
+            let text = self
+                .hook_commands
+                .entry(id.to_string())
+                .or_insert_with(|| hooks.commands.join("\n"));
+            ui.label("Commands, one per line");
+            if ui
+                .add(TextEdit::multiline(text).hint_text("systemctl stop game"))
+                .changed()
+            {
+                hooks.commands = text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|command| !command.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+            if application
+                .servers
+                .iter()
+                .flatten()
+                .any(|server| server.public_net.ipv4.is_none())
+            {
+                ui.colored_label(Color32::YELLOW, "Servers without IPv4 can't be crunched");
+            }
+        });
+        if hooks != known {
+            match hooks {
+                Some(hooks) => self.shutdown_hooks.insert(id.to_string(), hooks),
+                None => self.shutdown_hooks.remove(id),
+            };
+        }
+    }
+
+    /// Checks a restored server must pass before its application counts as ready
+    fn health_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id();
+        let known = self.health_checks.get(id).cloned();
+        let mut checks = known.clone().unwrap_or_default();
+        ui.collapsing("🩺 Health checks", |ui| {
+            let mut removed = None;
+            for (i, check) in checks.checks.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    match check {
+                        HealthCheck::Tcp { port } => {
+                            ui.label("TCP port");
+                            ui.add(DragValue::new(port).clamp_range(1..=u16::MAX));
+                        }
+                        HealthCheck::Http {
+                            port,
+                            path,
+                            status,
+                            body,
+                        } => {
+                            ui.label("HTTP port");
+                            ui.add(DragValue::new(port).clamp_range(1..=u16::MAX));
+                            ui.add(TextEdit::singleline(path).desired_width(80.0));
+                            ui.label("answers");
+                            ui.add(DragValue::new(status).clamp_range(100..=599));
+                            ui.add(
+                                TextEdit::singleline(body)
+                                    .hint_text("with body containing")
+                                    .desired_width(120.0),
+                            );
+                        }
+                        HealthCheck::Ssh {
+                            user,
+                            port,
+                            key_path,
+                            command,
+                        } => {
+                            ui.label("SSH");
+                            ui.add(TextEdit::singleline(user).desired_width(50.0));
+                            ui.add(DragValue::new(port).clamp_range(1..=u16::MAX));
+                            ui.add(
+                                TextEdit::singleline(key_path)
+                                    .hint_text("~/.ssh/id_ed25519")
+                                    .desired_width(100.0),
+                            );
+                            ui.add(TextEdit::singleline(command).desired_width(140.0));
+                        }
+                    }
+                    if ui.small_button("🗑").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = removed {
+                checks.checks.remove(i);
+            }
+            ui.horizontal(|ui| {
+                ui.label("Add");
+                if ui.button("TCP").clicked() {
+                    checks.checks.push(HealthCheck::tcp());
+                }
+                if ui.button("HTTP").clicked() {
+                    checks.checks.push(HealthCheck::http());
+                }
+                if ui.button("SSH").clicked() {
+                    checks.checks.push(HealthCheck::ssh());
+                }
+            });
+            egui::Grid::new("health_form")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Retry every");
+                    ui.add(
+                        DragValue::new(&mut checks.retry_secs)
+                            .clamp_range(1..=300)
+                            .suffix(" s"),
+                    );
+                    ui.end_row();
+                    ui.label("Give up after");
+                    ui.add(
+                        DragValue::new(&mut checks.deadline_secs)
+                            .clamp_range(10..=3600)
+                            .suffix(" s"),
+                    );
+                    ui.end_row();
+                });
+        });
+        let checks = (!checks.checks.is_empty()).then(|| checks);
+        if checks != known {
+            match checks {
+                Some(checks) => self.health_checks.insert(id.to_string(), checks),
+                None => self.health_checks.remove(id),
+            };
+            // Earlier outcomes were of other checks, run the new ones if it's running
+            self.healthy.remove(id);
+            self.health_due = true;
+        }
+    }
+
+    /// Form to provision a server from the latest snapshot, prefilled with the original server
+    fn restore_form(
+        &mut self,
+        application: &Application,
+        ui: &mut Ui,
+        loading: bool,
+    ) -> Option<RestoreSpec> {
+        let name = application.name.clone()?;
+        let latest = application.latest_snapshot()?;
+        let spec = self.restore_forms.entry(name.clone()).or_insert_with(|| {
+            application
+                .restore_spec(self.known_servers.get(application.id()))
+                .unwrap_or(RestoreSpec {
+                    image_id: latest.id,
+                    server: ServerSpec {
+                        name: name.clone(),
+                        server_type: String::new(),
+                        location: String::new(),
+                    },
+                })
+        });
+
+        let mut clicked = false;
+        ui.collapsing("🔄 Restore", |ui| {
+            ui.label(format!(
+                "From snapshot {} ({})",
+                spec.image_id, latest.created
+            ));
+            egui::Grid::new("restore_form")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Name");
+                    ui.add(TextEdit::singleline(&mut spec.server.name));
+                    ui.end_row();
+                    ui.label("Server type");
+                    ui.add(TextEdit::singleline(&mut spec.server.server_type).hint_text("cx11"));
+                    ui.end_row();
+                    ui.label("Location");
+                    ui.add(TextEdit::singleline(&mut spec.server.location).hint_text("nbg1"));
+                    ui.end_row();
+                });
+            let complete = !spec.server.name.is_empty()
+                && !spec.server.server_type.is_empty()
+                && !spec.server.location.is_empty();
+            clicked = ui
+                .add_enabled(!loading && complete, Button::new("Restore"))
+                .clicked();
+        });
+
+        clicked.then(|| spec.clone())
+    }
 }
@@ -0,0 +1,161 @@
+use super::App;
+use crate::utils::{
+    cost::{self, CostBreakdown, Rate},
+    BudgetStatus, Data, OverBudget, RemoteData,
+};
+use egui::{
+    Checkbox, Color32, ComboBox, Context, DragValue, Grid, ProgressBar, RichText, ScrollArea,
+    SidePanel, Ui,
+};
+
+impl App {
+    /// Project-wide cost summary, broken down by application and location
+    pub fn draw_cost_panel(&mut self, ctx: &Context) {
+        if !self.show_cost_panel {
+            return;
+        }
+        let vat_rate = self.vat_rate();
+        let applications: Vec<(String, CostBreakdown)> = match &self.application_list {
+            Some(RemoteData {
+                data: Data::Application(applications),
+                ..
+            }) => applications
+                .iter()
+                .map(|app| {
+                    let name = app.name.clone().unwrap_or_default();
+                    (name, CostBreakdown::of(app, vat_rate))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut project = CostBreakdown::default();
+        for (_, breakdown) in &applications {
+            project += breakdown;
+        }
+
+        SidePanel::right("cost_panel").show(ctx, |ui| {
+            ui.heading("💶 Costs");
+            Self::gross_toggle(ui, &mut self.show_gross);
+            ui.separator();
+
+            let gross = self.show_gross;
+            ScrollArea::vertical().show(ui, |ui| {
+                Grid::new("cost_applications")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Application");
+                        ui.strong("Hourly");
+                        ui.strong("Monthly");
+                        ui.end_row();
+                        for (name, breakdown) in &applications {
+                            Self::rate_row(ui, name, breakdown.total(), gross);
+                        }
+                    });
+                ui.separator();
+                Self::cost_grid(ui, "cost_project", &project, gross);
+                ui.separator();
+                self.project_budget_form(ui);
+            });
+        });
+    }
+
+    fn project_budget_form(&mut self, ui: &mut Ui) {
+        ui.strong("💰 Project budget");
+        Self::budget_editor(ui, &mut self.budgets.project);
+        if let Some(budget) = self.budgets.project {
+            let status = self.spend.project_status(budget, chrono::Utc::now());
+            Self::budget_status(ui, &status);
+        }
+        ComboBox::from_label("When an application is about to exceed its budget")
+            .selected_text(self.budgets.over_budget.label())
+            .show_ui(ui, |ui| {
+                for action in OverBudget::ALL {
+                    ui.selectable_value(&mut self.budgets.over_budget, action, action.label());
+                }
+            });
+    }
+
+    /// Monthly net budget, `None` if there is none
+    pub(super) fn budget_editor(ui: &mut Ui, budget: &mut Option<f64>) {
+        ui.horizontal(|ui| {
+            let mut enabled = budget.is_some();
+            if ui
+                .add(Checkbox::new(&mut enabled, "Monthly budget"))
+                .changed()
+            {
+                *budget = enabled.then(|| 10.0);
+            }
+            if let Some(budget) = budget {
+                ui.add(
+                    DragValue::new(budget)
+                        .speed(0.5)
+                        .clamp_range(0.0..=f64::MAX)
+                        .suffix(" €"),
+                );
+            }
+        });
+    }
+
+    /// Month-to-date spend and where it's heading, amounts exclude VAT
+    pub(super) fn budget_status(ui: &mut Ui, status: &BudgetStatus) {
+        let text = format!(
+            "{:.2} of {:.2} € spent ({:.0}%)",
+            status.spent,
+            status.budget,
+            status.ratio() * 100.0
+        );
+        ui.add(ProgressBar::new(status.ratio().min(1.0) as f32).text(text));
+        let projected = format!(
+            "Projected by the end of the month: {:.2} €",
+            status.projected
+        );
+        match status.projected > status.budget {
+            true => ui.colored_label(Color32::RED, projected),
+            false => ui.label(projected),
+        };
+    }
+
+    pub(super) fn vat_rate(&self) -> f64 {
+        cost::vat_rate(self.known_prices.values().flatten())
+    }
+
+    pub(super) fn gross_toggle(ui: &mut Ui, gross: &mut bool) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(gross, false, "Net");
+            ui.selectable_value(gross, true, "Gross");
+        });
+    }
+
+    /// Costs by location, snapshots and traffic, along with their total
+    pub(super) fn cost_grid(ui: &mut Ui, id: &str, breakdown: &CostBreakdown, gross: bool) {
+        Grid::new(id).num_columns(3).striped(true).show(ui, |ui| {
+            for (location, rate) in &breakdown.servers {
+                Self::rate_row(ui, &format!("Servers in {}", location), *rate, gross);
+            }
+            Self::rate_row(ui, "Snapshots", breakdown.snapshots, gross);
+            Self::rate_row(ui, "Traffic overage", breakdown.traffic, gross);
+            let total = breakdown.total();
+            ui.strong("Total");
+            ui.strong(format!("{:.4} €/h", total.hourly.get(gross)));
+            ui.strong(format!("{:.2} €/mo", total.monthly.get(gross)));
+            ui.end_row();
+        });
+        if breakdown.unpriced > 0 {
+            ui.label(
+                RichText::new(format!(
+                    "{} server(s) or snapshot(s) without known price",
+                    breakdown.unpriced
+                ))
+                .italics(),
+            );
+        }
+    }
+
+    fn rate_row(ui: &mut Ui, label: &str, rate: Rate, gross: bool) {
+        ui.label(label);
+        ui.label(format!("{:.4} €/h", rate.hourly.get(gross)));
+        ui.label(format!("{:.2} €/mo", rate.monthly.get(gross)));
+        ui.end_row();
+    }
+}
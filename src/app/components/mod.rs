@@ -8,3 +8,16 @@ pub use api_prefs_window::ApiPerfsData;
 mod error_window;
 
 mod application_window;
+
+mod plan_window;
+
+mod cost_panel;
+
+mod savings_window;
+pub use savings_window::SavingsWindowData;
+
+mod schedule_form;
+pub use schedule_form::ScheduleForm;
+
+mod rcon_form;
+pub use rcon_form::RconForm;
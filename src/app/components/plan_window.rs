@@ -0,0 +1,149 @@
+use super::App;
+use crate::utils::{Application, Error, Plan, PlanKind, Transition};
+use egui::{Color32, Context, Grid, RichText, Window};
+
+impl App {
+    pub fn draw_plan_window(&mut self, ctx: &Context) {
+        let plan = match &self.plan {
+            Some(plan) => plan,
+            None => return,
+        };
+        let title = match plan.kind {
+            PlanKind::Crunch(_) => format!("Plan: crunch {}", plan.origin),
+            PlanKind::Restore(_) => format!("Plan: restore {}", plan.origin),
+            PlanKind::Prune(_) => format!("Plan: prune {}", plan.origin),
+        };
+
+        let mut open = true;
+        let mut confirmed = false;
+        let mut cancelled = false;
+        Window::new(title)
+            .id(egui::Id::new("plan_window"))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("The following requests will be sent:");
+                Grid::new("plan_calls").striped(true).show(ui, |ui| {
+                    for call in &plan.calls {
+                        ui.monospace(call.method);
+                        ui.monospace(&call.path);
+                        ui.label(&call.description);
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                match plan.monthly_cost_change {
+                    Some(change) => {
+                        let color = match change > 0.0 {
+                            true => Color32::RED,
+                            false => Color32::GREEN,
+                        };
+                        ui.label(
+                            RichText::new(format!("Monthly cost change: {:+.2} € (net)", change))
+                                .color(color),
+                        );
+                    }
+                    None => {
+                        ui.label(RichText::new("Monthly cost change: unknown").italics());
+                    }
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("✔ Confirm").clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+
+        if confirmed {
+            if let Some(plan) = self.plan.take() {
+                self.execute_plan(plan, ctx);
+            }
+        } else if cancelled || !open {
+            self.plan = None;
+        }
+    }
+
+    pub fn execute_plan(&mut self, plan: Plan, ctx: &Context) {
+        let Plan { origin, kind, .. } = plan;
+        // A missing half shows running applications as crunched and the other way around
+        let action = match kind {
+            PlanKind::Crunch(_) => Some("crunch"),
+            PlanKind::Restore(_) => Some("restore"),
+            PlanKind::Prune(_) => None,
+        };
+        if let Some(action) = action.filter(|_| self.list_incomplete) {
+            let error = format!(
+                "Refused to {} {}, the application list is incomplete",
+                action, origin
+            );
+            self.error_log.push(Error::new(&error));
+            return;
+        }
+        self.set_loading(&origin);
+        let now = chrono::Utc::now();
+        let transition = match kind {
+            PlanKind::Crunch(application) => {
+                self.req_crunch(&application, &origin, ctx);
+                Some(Transition::crunched(&application, now))
+            }
+            PlanKind::Restore(spec) => {
+                self.restore_forms.remove(&origin);
+                self.req_restore(spec, &origin, ctx);
+                self.find_application(&origin)
+                    .map(|application| Transition::restored(application, now))
+            }
+            PlanKind::Prune(image_ids) => {
+                self.req_prune(image_ids, &origin, ctx);
+                None
+            }
+        };
+
+        // Recorded once the workflow is done, unless it failed right away
+        if let Some(transition) = transition {
+            if self.remote_loading.contains(&origin) {
+                self.pending_transitions.insert(origin, transition);
+            }
+        }
+    }
+
+    pub(crate) fn find_application(&self, name: &str) -> Option<&Application> {
+        self.applications()
+            .iter()
+            .find(|app| app.name.as_deref() == Some(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        app::App,
+        utils::{empty_snapshot, generate_application_list},
+    };
+    use egui::Context;
+    use hcloud::models::{ListImagesResponse, ListServersResponse};
+
+    #[test]
+    fn execute_plan_unconfigured() {
+        const ORIGIN: &str = "planned";
+        let mut app = App::default();
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![],
+        };
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![empty_snapshot()],
+        };
+        let application = generate_application_list(&servers, &images, &app.app_label)
+            .pop()
+            .unwrap();
+
+        let plan = app.plan_crunch(ORIGIN, &application);
+        app.execute_plan(plan, &Context::default());
+        assert!(!app.remote_loading.contains(ORIGIN));
+        assert!(!app.error_log.is_empty());
+        assert!(app.pending_transitions.is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use super::App;
+use crate::utils::{Application, Data, Key, RconShutdown, RemoteData, Secret};
+use egui::{Button, Color32, DragValue, RichText, ScrollArea, TextEdit, Ui};
+
+/// Edited RCON settings, kept as text until they are saved
+#[derive(Clone)]
+pub struct RconForm {
+    port: u16,
+    password: String,
+    countdown: String,
+    broadcast: String,
+    commands: String,
+    timeout_secs: u64,
+    console: String,
+    error: Option<String>,
+}
+impl RconForm {
+    fn new(rcon: &RconShutdown) -> Self {
+        let countdown: Vec<String> = rcon.countdown.iter().map(u32::to_string).collect();
+        Self {
+            port: rcon.port,
+            password: Key::from(rcon.password.clone()).0,
+            countdown: countdown.join(", "),
+            broadcast: rcon.broadcast.clone(),
+            commands: rcon.commands.join("\n"),
+            timeout_secs: rcon.timeout_secs,
+            console: String::new(),
+            error: None,
+        }
+    }
+
+    fn to_rcon(&self) -> Result<RconShutdown, String> {
+        let countdown = self
+            .countdown
+            .split(',')
+            .map(str::trim)
+            .filter(|seconds| !seconds.is_empty())
+            .map(|seconds| {
+                seconds
+                    .parse()
+                    .map_err(|_| format!("Invalid countdown \"{}\", use seconds", seconds))
+            })
+            .collect::<Result<_, _>>()?;
+        let commands: Vec<String> = self
+            .commands
+            .lines()
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+            .map(str::to_string)
+            .collect();
+        if commands.is_empty() {
+            return Err("At least one command is needed, e.g. \"stop\"".to_string());
+        }
+        Ok(RconShutdown {
+            port: self.port,
+            password: Secret::Unencrypted(Key(self.password.clone())),
+            countdown,
+            broadcast: self.broadcast.clone(),
+            commands,
+            timeout_secs: self.timeout_secs,
+        })
+    }
+}
+
+impl App {
+    /// Game server commands to save and stop before crunching, along with a console
+    pub(super) fn rcon_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id().to_string();
+        let name = application.name.clone().unwrap_or_default();
+        let known = self.rcon.get(&id).cloned();
+        let hosts: Vec<String> = application
+            .servers
+            .iter()
+            .flatten()
+            .filter_map(|server| Some(server.public_net.ipv4.as_ref()?.ip.clone()))
+            .collect();
+
+        ui.collapsing("🎮 Game server", |ui| {
+            let form = self
+                .rcon_forms
+                .entry(id.clone())
+                .or_insert_with(|| RconForm::new(known.as_ref().unwrap_or(&Default::default())));
+            egui::Grid::new("rcon_form").num_columns(2).show(ui, |ui| {
+                ui.label("RCON port");
+                ui.add(DragValue::new(&mut form.port).clamp_range(1..=u16::MAX));
+                ui.end_row();
+                ui.label("Password");
+                ui.add(TextEdit::singleline(&mut form.password).password(true));
+                ui.end_row();
+                ui.label("Warn at");
+                ui.add(TextEdit::singleline(&mut form.countdown).hint_text("60, 30, 10"));
+                ui.end_row();
+                ui.label("Warning");
+                ui.add(TextEdit::singleline(&mut form.broadcast).hint_text("say Stopping in {}s"));
+                ui.end_row();
+                ui.label("Then run");
+                ui.add(
+                    TextEdit::multiline(&mut form.commands)
+                        .desired_rows(2)
+                        .hint_text("save-all\nstop"),
+                );
+                ui.end_row();
+                ui.label("Timeout");
+                ui.add(
+                    DragValue::new(&mut form.timeout_secs)
+                        .clamp_range(1..=600)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    match form.to_rcon() {
+                        Ok(rcon) => {
+                            form.error = None;
+                            self.rcon.insert(id.clone(), rcon);
+                        }
+                        Err(error) => form.error = Some(error),
+                    }
+                }
+                if ui
+                    .add_enabled(known.is_some(), Button::new("Remove"))
+                    .clicked()
+                {
+                    self.rcon.remove(&id);
+                }
+            });
+            if let Some(error) = &form.error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            let rcon = match &known {
+                Some(rcon) => rcon,
+                None => return,
+            };
+            ui.separator();
+            ui.horizontal(|ui| {
+                let console = ui.add(TextEdit::singleline(&mut form.console).hint_text("list"));
+                let entered = console.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                let sendable = !form.console.trim().is_empty() && !hosts.is_empty();
+                if ui.add_enabled(sendable, Button::new("Send")).clicked() || entered && sendable {
+                    let command = std::mem::take(&mut form.console);
+                    for host in &hosts {
+                        let (rcon, host, command) = (rcon.clone(), host.clone(), command.clone());
+                        let (tx, ctx, origin) = (self.tx.clone(), ui.ctx().clone(), name.clone());
+                        tokio::spawn(async move {
+                            let output = rcon.send(&host, command.trim()).await;
+                            let _ = tx.send(RemoteData::new(Data::Rcon(output), &origin));
+                            ctx.request_repaint();
+                        });
+                    }
+                }
+            });
+            if let Some(lines) = self.rcon_output.get_mut(&name) {
+                ScrollArea::vertical()
+                    .max_height(150.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in lines.iter() {
+                            ui.label(RichText::new(line).monospace());
+                        }
+                    });
+                if ui.button("Clear").clicked() {
+                    lines.clear();
+                }
+            }
+        });
+    }
+}
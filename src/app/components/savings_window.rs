@@ -0,0 +1,113 @@
+use super::App;
+use egui::{
+    plot::{Bar, BarChart, Plot},
+    Context, Grid, RichText, Window,
+};
+
+#[derive(Clone)]
+pub struct SavingsWindowData {
+    open: bool,
+    /// Where the CSV export is written to
+    path: String,
+    /// Outcome of the last export
+    status: Option<String>,
+}
+impl Default for SavingsWindowData {
+    fn default() -> Self {
+        Self {
+            open: false,
+            path: "savings.csv".to_string(),
+            status: None,
+        }
+    }
+}
+
+impl App {
+    pub fn open_savings_window(&mut self) {
+        self.savings_window.open = true;
+    }
+
+    /// What crunching has saved so far, by month and application
+    pub fn draw_savings_window(&mut self, ctx: &Context) {
+        let mut open = self.savings_window.open;
+        let now = chrono::Utc::now();
+        Window::new("📉 Savings").open(&mut open).show(ctx, |ui| {
+            if self.savings.is_empty() {
+                ui.label(RichText::new("Nothing crunched yet").italics());
+                return;
+            }
+            let months = self.savings.monthly(now);
+            let labels: Vec<String> = months
+                .keys()
+                .map(|(year, month)| format!("{}-{:02}", year, month))
+                .collect();
+            let bars = months
+                .values()
+                .enumerate()
+                .map(|(index, applications)| {
+                    let net = applications.values().map(|savings| savings.net()).sum();
+                    Bar::new(index as f64, net).name(&labels[index])
+                })
+                .collect();
+
+            let axis_labels = labels.clone();
+            Plot::new("savings_plot")
+                .height(160.0)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .x_axis_formatter(move |x, _| match x.fract() == 0.0 && x >= 0.0 {
+                    true => axis_labels.get(x as usize).cloned().unwrap_or_default(),
+                    false => String::new(),
+                })
+                .show(ui, |plot| {
+                    plot.bar_chart(BarChart::new(bars).name("Net savings (€)").width(0.6))
+                });
+            ui.separator();
+
+            Grid::new("savings_applications")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Application");
+                    ui.strong("Crunched");
+                    ui.strong("Saved");
+                    ui.strong("Storage");
+                    ui.strong("Net");
+                    ui.end_row();
+                    for (name, savings) in self.savings.per_application(now) {
+                        ui.label(name);
+                        ui.label(format!("{:.1} h", savings.hours));
+                        ui.label(format!("{:.2} €", savings.saved));
+                        ui.label(format!("{:.2} €", savings.storage));
+                        ui.label(format!("{:.2} €", savings.net()));
+                        ui.end_row();
+                    }
+                });
+            ui.label(RichText::new("Amounts exclude VAT").italics());
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("📋 Copy CSV").clicked() {
+                    ui.output().copied_text = self.savings.to_csv(now);
+                    self.savings_window.status = Some("Copied to clipboard".to_string());
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    ui.text_edit_singleline(&mut self.savings_window.path);
+                    if ui.button("💾 Export CSV").clicked() {
+                        let path = &self.savings_window.path;
+                        let status = match std::fs::write(path, self.savings.to_csv(now)) {
+                            Ok(()) => format!("Saved to {}", path),
+                            Err(e) => format!("Failed to save {}: {}", path, e),
+                        };
+                        self.savings_window.status = Some(status);
+                    }
+                }
+            });
+            if let Some(status) = &self.savings_window.status {
+                ui.label(status);
+            }
+        });
+        self.savings_window.open = open;
+    }
+}
@@ -0,0 +1,254 @@
+use super::App;
+use crate::utils::{Application, CalendarSource, Schedule, Trigger, Zone};
+use chrono::{Local, NaiveTime};
+use egui::{Button, Checkbox, Color32, DragValue, RichText, TextEdit, Ui};
+
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+/// Upcoming transitions shown below the form
+const PREVIEW_RUNS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Window,
+    Cron,
+    Calendar,
+}
+
+/// Edited schedule, kept as text until it is saved
+#[derive(Clone)]
+pub struct ScheduleForm {
+    mode: Mode,
+    days: [bool; 7],
+    start: String,
+    end: String,
+    restore: String,
+    crunch: String,
+    path: String,
+    lead_minutes: u32,
+    trail_minutes: u32,
+    zone: String,
+    error: Option<String>,
+}
+impl ScheduleForm {
+    fn new(schedule: Option<&Schedule>) -> Self {
+        let mut form = Self {
+            mode: Mode::Window,
+            days: [true, true, true, true, true, false, false],
+            start: "18:00".to_string(),
+            end: "23:00".to_string(),
+            restore: "0 18 * * 1-5".to_string(),
+            crunch: "0 23 * * 1-5".to_string(),
+            path: String::new(),
+            lead_minutes: 15,
+            trail_minutes: 30,
+            zone: Zone::Local.label(),
+            error: None,
+        };
+        if let Some(schedule) = schedule {
+            form.zone = schedule.zone.label();
+            match &schedule.trigger {
+                Trigger::Window { days, start, end } => {
+                    form.days = *days;
+                    form.start = start.format("%H:%M").to_string();
+                    form.end = end.format("%H:%M").to_string();
+                }
+                Trigger::Cron { restore, crunch } => {
+                    form.mode = Mode::Cron;
+                    form.restore = restore.clone();
+                    form.crunch = crunch.clone();
+                }
+                Trigger::Calendar(source) => {
+                    form.mode = Mode::Calendar;
+                    form.path = source.path.clone();
+                    form.lead_minutes = source.lead_minutes;
+                    form.trail_minutes = source.trail_minutes;
+                }
+            }
+        }
+        form
+    }
+
+    fn to_schedule(&self) -> Result<Schedule, String> {
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("Invalid time \"{}\", use HH:MM", time))
+        };
+        let trigger = match self.mode {
+            Mode::Cron => Trigger::Cron {
+                restore: self.restore.trim().to_string(),
+                crunch: self.crunch.trim().to_string(),
+            },
+            Mode::Window => Trigger::Window {
+                days: self.days,
+                start: time(&self.start)?,
+                end: time(&self.end)?,
+            },
+            Mode::Calendar => Trigger::Calendar(CalendarSource::new(
+                self.path.trim(),
+                self.lead_minutes,
+                self.trail_minutes,
+            )),
+        };
+        let mut schedule = Schedule {
+            trigger,
+            zone: Zone::parse(&self.zone)?,
+        };
+        match &mut schedule.trigger {
+            Trigger::Calendar(source) => {
+                source.reload();
+                source.calendar()?;
+            }
+            _ => {
+                schedule.crons()?;
+            }
+        }
+        Ok(schedule)
+    }
+}
+
+impl App {
+    /// When to restore and crunch the application, along with the upcoming and missed runs
+    pub(super) fn schedule_form(&mut self, application: &Application, ui: &mut Ui) {
+        let id = application.id().to_string();
+        let known = self.schedules.schedules.get(&id).cloned();
+        let mut changed = false;
+
+        ui.collapsing("🕑 Schedule", |ui| {
+            let form = self
+                .schedule_forms
+                .entry(id.clone())
+                .or_insert_with(|| ScheduleForm::new(known.as_ref()));
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut form.mode, Mode::Window, "Weekly window");
+                ui.selectable_value(&mut form.mode, Mode::Cron, "Cron");
+                ui.selectable_value(&mut form.mode, Mode::Calendar, "Calendar");
+            });
+            egui::Grid::new("schedule_form")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    match form.mode {
+                        Mode::Cron => {
+                            ui.label("Restore at");
+                            ui.add(
+                                TextEdit::singleline(&mut form.restore).hint_text("0 18 * * 1-5"),
+                            );
+                            ui.end_row();
+                            ui.label("Crunch at");
+                            ui.add(
+                                TextEdit::singleline(&mut form.crunch).hint_text("0 23 * * 1-5"),
+                            );
+                            ui.end_row();
+                        }
+                        Mode::Calendar => {
+                            ui.label("Calendar file");
+                            ui.add(TextEdit::singleline(&mut form.path).hint_text("events.ics"));
+                            ui.end_row();
+                            ui.label("Restore before events");
+                            ui.add(
+                                DragValue::new(&mut form.lead_minutes)
+                                    .clamp_range(0..=1440)
+                                    .suffix(" min"),
+                            );
+                            ui.end_row();
+                            ui.label("Crunch after events");
+                            ui.add(
+                                DragValue::new(&mut form.trail_minutes)
+                                    .clamp_range(0..=1440)
+                                    .suffix(" min"),
+                            );
+                            ui.end_row();
+                        }
+                        Mode::Window => {
+                            ui.label("Days");
+                            ui.horizontal(|ui| {
+                                for (day, label) in form.days.iter_mut().zip(WEEKDAYS) {
+                                    ui.add(Checkbox::new(day, label));
+                                }
+                            });
+                            ui.end_row();
+                            ui.label("Running from");
+                            ui.horizontal(|ui| {
+                                ui.add(TextEdit::singleline(&mut form.start).desired_width(50.0));
+                                ui.label("to");
+                                ui.add(TextEdit::singleline(&mut form.end).desired_width(50.0));
+                            });
+                            ui.end_row();
+                        }
+                    }
+                    // Calendars bring their own time zones, except for floating times
+                    ui.label("Time zone");
+                    ui.add(
+                        TextEdit::singleline(&mut form.zone)
+                            .hint_text("UTC, Local, +HH:MM or Europe/Berlin"),
+                    );
+                    ui.end_row();
+                });
+
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    match form.to_schedule() {
+                        Ok(schedule) => {
+                            form.error = None;
+                            self.schedules.schedules.insert(id.clone(), schedule);
+                            changed = true;
+                        }
+                        Err(error) => form.error = Some(error),
+                    }
+                }
+                if ui
+                    .add_enabled(known.is_some(), Button::new("Remove"))
+                    .clicked()
+                {
+                    self.schedules.schedules.remove(&id);
+                    changed = true;
+                }
+            });
+            if let Some(error) = &form.error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            // Calendar files may break after they were saved
+            if let Some(Trigger::Calendar(source)) = known.as_ref().map(|known| &known.trigger) {
+                if let Err(error) = source.calendar() {
+                    ui.colored_label(Color32::RED, error);
+                }
+            }
+            if let Some(schedule) = &known {
+                let mut after = chrono::Utc::now();
+                for _ in 0..PREVIEW_RUNS {
+                    let (at, action) = match schedule.next_after(after) {
+                        Some(run) => run,
+                        None => break,
+                    };
+                    let local = at.with_timezone(&Local).format("%a %Y-%m-%d %H:%M");
+                    ui.label(format!("{} on {}", action.label(), local));
+                    after = at;
+                }
+            }
+
+            let missed: Vec<String> = self
+                .schedules
+                .missed
+                .iter()
+                .filter(|run| run.application == id)
+                .map(|run| {
+                    let local = run.at.with_timezone(&Local).format("%a %Y-%m-%d %H:%M");
+                    format!("Missed {} on {}", run.action.label().to_lowercase(), local)
+                })
+                .collect();
+            if !missed.is_empty() {
+                ui.separator();
+                for missed in missed {
+                    ui.label(RichText::new(missed).color(Color32::YELLOW));
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.schedules.missed.retain(|run| run.application != id);
+                }
+            }
+        });
+
+        if changed {
+            self.publish_schedules();
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use super::App;
-use crate::utils::Secret;
-use egui::{Context, CursorIcon, TopBottomPanel, Ui};
+use crate::utils::{BudgetStatus, Data, RemoteData, Secret};
+use egui::{Color32, Context, CursorIcon, ProgressBar, TopBottomPanel, Ui};
 
 const API_ORIGIN: &str = "status_bar";
 
@@ -15,10 +15,114 @@ impl App {
             ui.horizontal(|ui| {
                 self.button(ctx, ui);
                 ui.label(format!("Last updated: {}", last_updated));
+                if self.list_incomplete {
+                    ui.colored_label(Color32::YELLOW, "⚠ Incomplete")
+                        .on_hover_text("Servers or images failed to load, see the error log");
+                }
+                self.budget_warning(ui);
+                self.next_scheduled(ui);
+                if let Some(listing) = &self.list_progress {
+                    ui.separator();
+                    let text = match listing.total {
+                        Some(total) => {
+                            format!("Fetching {}: {}/{}", listing.what, listing.fetched, total)
+                        }
+                        None => format!("Fetching {}: {}", listing.what, listing.fetched),
+                    };
+                    match listing.total.filter(|total| *total > 0) {
+                        Some(total) => ui.add(
+                            ProgressBar::new(listing.fetched as f32 / total as f32)
+                                .desired_width(150.0)
+                                .text(text),
+                        ),
+                        None => ui.label(text),
+                    };
+                }
+                if let Some(progress) = self.actions.progress() {
+                    ui.separator();
+                    ui.add(
+                        ProgressBar::new(progress)
+                            .desired_width(150.0)
+                            .text(format!("{} running action(s)", self.actions.len())),
+                    );
+                }
             });
         });
     }
 
+    /// Applications, and the project as a whole, past the warning share of their budget
+    fn budget_warnings(&self) -> Vec<(String, BudgetStatus)> {
+        let now = chrono::Utc::now();
+        let mut warnings = Vec::new();
+        if let Some(RemoteData {
+            data: Data::Application(applications),
+            ..
+        }) = &self.application_list
+        {
+            for application in applications {
+                let id = application.id();
+                if let Some(budget) = self.budgets.get(id) {
+                    let name = application.name.clone().unwrap_or_default();
+                    warnings.push((name, self.spend.status(id, budget, now)));
+                }
+            }
+        }
+        if let Some(budget) = self.budgets.project {
+            warnings.push((
+                "Project".to_string(),
+                self.spend.project_status(budget, now),
+            ));
+        }
+        warnings.retain(|(_, status)| status.is_warning());
+        warnings
+    }
+
+    fn budget_warning(&self, ui: &mut Ui) {
+        let warnings = self.budget_warnings();
+        if warnings.is_empty() {
+            return;
+        }
+        let color = match warnings.iter().any(|(_, status)| status.is_exceeded()) {
+            true => Color32::RED,
+            false => Color32::YELLOW,
+        };
+        let names: Vec<&str> = warnings.iter().map(|(name, _)| name.as_str()).collect();
+        let details: Vec<String> = warnings
+            .iter()
+            .map(|(name, status)| {
+                format!(
+                    "{}: {:.2} of {:.2} € spent ({:.0}%)",
+                    name,
+                    status.spent,
+                    status.budget,
+                    status.ratio() * 100.0
+                )
+            })
+            .collect();
+        ui.colored_label(color, format!("⚠ Budget: {}", names.join(", ")))
+            .on_hover_text(details.join("\n"));
+    }
+
+    fn next_scheduled(&self, ui: &mut Ui) {
+        if let Some(run) = self.schedules.next_after(chrono::Utc::now()) {
+            ui.separator();
+            ui.label(format!(
+                "🕑 {} {} at {}",
+                run.action.label(),
+                self.application_name(&run.application),
+                run.at.with_timezone(&chrono::Local).format("%a %H:%M")
+            ));
+        }
+    }
+
+    pub fn refresh_application_list(&mut self, ctx: &Context) {
+        if !self.remote_loading.contains(API_ORIGIN) {
+            self.set_loading(API_ORIGIN);
+            self.refreshed_at = Some(chrono::Utc::now());
+            self.req_application_list(API_ORIGIN, ctx);
+        }
+    }
+
     fn button(&mut self, ctx: &Context, ui: &mut Ui) {
         let Self {
             hcloud_api_secret, ..
@@ -34,8 +138,7 @@ impl App {
                     .on_hover_text("Refresh Server List")
                     .clicked()
                 {
-                    self.remote_loading.insert(API_ORIGIN.to_string());
-                    self.req_application_list(API_ORIGIN, ctx);
+                    self.refresh_application_list(ctx);
                 }
             }
             _ => {
@@ -1,20 +1,40 @@
 mod api;
 mod components;
+mod proxy;
+mod scheduler;
 
-use crate::utils::{Data, Error, RemoteData, Secret};
+use crate::utils::{
+    ActionTracker, Application, Budgets, Data, Error, GameQuery, HealthChecks, Identities,
+    IdleRule, Journal, JournalEntry, Lifecycle, ListProgress, Occupancy, OverBudget, Overrides,
+    Plan, PreShutdown, RconShutdown, RemoteData, RestoreSpec, RetentionPolicy, SavingsLedger,
+    ScheduledAction, ScheduledRun, Schedules, Secret, ServerMetrics, ServerSpec, ShutdownHooks,
+    Spend, Timestamp, Transition, WakeProxy, WorkflowUpdate, DEFAULT_APP_LABEL, HEALTH_ORIGIN,
+    JOURNAL_KEY, QUERY_ORIGIN,
+};
 use api::{Endpoint, Hetzner, Unconfigured};
 use components::*;
+use hcloud::models::{action, PricePerTime};
 use serde::{Deserialize, Serialize};
 use serde_encrypt::{shared_key::SharedKey, AsSharedKey};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     rc::Rc,
     sync::mpsc::{Receiver, Sender},
     time::Duration,
 };
+use tokio::sync::watch;
 
 pub(crate) type App = ServerCruncherApp;
 
+/// How often metrics of applications with an idle rule are fetched
+const METRICS_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How often the application list is refreshed, so spend keeps accruing while the app is open
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How often game servers are asked who is online
+const QUERY_INTERVAL: Duration = Duration::from_secs(60);
+/// Lines of RCON output kept per application
+const RCON_OUTPUT_LINES: usize = 200;
+
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(Deserialize, Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
@@ -30,9 +50,120 @@ pub struct ServerCruncherApp {
     endpoint: Rc<dyn Endpoint>,
     hcloud_api_secret: Option<Secret>,
     application_list: Option<RemoteData>,
+    /// Last seen server of each application by application ID, used as default when restoring
+    known_servers: HashMap<String, ServerSpec>,
+    /// Last seen prices of each server type
+    known_prices: HashMap<String, Vec<PricePerTime>>,
+    /// Label key grouping servers and snapshots into applications
+    app_label: String,
+    /// Manual grouping applied on top of the label key
+    overrides: Overrides,
+    /// Application IDs of every known server and image
+    identities: Identities,
+    /// Snapshot retention of each application by application ID
+    retention: HashMap<String, RetentionPolicy>,
+    /// Show costs including VAT
+    show_gross: bool,
+    show_cost_panel: bool,
+    /// Every crunch and restore, to tell what crunching has saved
+    savings: SavingsLedger,
+    budgets: Budgets,
+    /// Month-to-date spend, compared against the budgets
+    spend: Spend,
+    /// When to crunch and restore applications
+    schedules: Schedules,
+    /// Hands changed schedules to the running scheduler
+    #[serde(skip)]
+    scheduler: Option<watch::Sender<Schedules>>,
+    /// When applications count as unused, by application ID
+    idle_rules: HashMap<String, IdleRule>,
+    /// Commands run on the servers before crunching, by application ID
+    shutdown_hooks: HashMap<String, ShutdownHooks>,
+    /// Game server commands sent before crunching, by application ID
+    rcon: HashMap<String, RconShutdown>,
+    /// Game server console output by origin
+    #[serde(skip)]
+    rcon_output: HashMap<String, Vec<String>>,
+    /// How to ask game servers who is online, by application ID
+    game_queries: HashMap<String, GameQuery>,
+    /// Proxies restoring crunched applications on demand, by application ID
+    wake_proxies: HashMap<String, WakeProxy>,
+    #[serde(skip)]
+    proxies: HashMap<String, proxy::ProxyHandle>,
+    /// What restored servers must pass before their application is ready, by application ID
+    health_checks: HashMap<String, HealthChecks>,
+    /// Whether the latest health checks passed, by application ID
+    #[serde(skip)]
+    healthy: HashMap<String, bool>,
+    /// Whether running applications should be checked for health on the next frame
+    #[serde(skip)]
+    health_due: bool,
+    /// Applications whose health checks are running, by ID
+    #[serde(skip)]
+    health_pending: HashSet<String>,
 
     #[serde(skip)] // Always skip UI Indicators
     remote_loading: HashSet<String>,
+    #[serde(skip)]
+    refresh_requested: bool,
+    /// When the application list was last requested
+    #[serde(skip)]
+    refreshed_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip)]
+    list_progress: Option<ListProgress>,
+    /// Whether the application list is missing servers or images after a failed request
+    #[serde(skip)]
+    list_incomplete: bool,
+    #[serde(skip)]
+    workflows: HashMap<String, WorkflowUpdate>,
+    #[serde(skip)]
+    actions: ActionTracker,
+    #[serde(skip)] // Stored on its own under `JOURNAL_KEY`
+    journal: Journal,
+    #[serde(skip)]
+    restore_forms: HashMap<String, RestoreSpec>,
+    #[serde(skip)]
+    plan: Option<Plan>,
+    /// Plans to execute without confirmation on the next frame
+    #[serde(skip)]
+    pending_plans: Vec<Plan>,
+    /// Applications a crunch was proposed or scheduled for, so it happens only once
+    #[serde(skip)]
+    over_budget: HashSet<String>,
+    /// Applications a crunch was proposed or scheduled for after being idle
+    #[serde(skip)]
+    idle: HashSet<String>,
+    /// Latest metrics by application ID
+    #[serde(skip)]
+    metrics: HashMap<String, Vec<ServerMetrics>>,
+    #[serde(skip)]
+    metrics_requested: HashMap<String, chrono::DateTime<chrono::Utc>>,
+    /// Applications a crunch was proposed or scheduled for after being empty
+    #[serde(skip)]
+    empty: HashSet<String>,
+    /// Latest player counts by application ID
+    #[serde(skip)]
+    occupancy: HashMap<String, Occupancy>,
+    #[serde(skip)]
+    queries_requested: HashMap<String, chrono::DateTime<chrono::Utc>>,
+    /// Applications to crunch even though players are online
+    #[serde(skip)]
+    forced_crunch: HashSet<String>,
+    /// Crunches and restores to record in the savings ledger once their workflow is done
+    #[serde(skip)]
+    pending_transitions: HashMap<String, Transition>,
+    /// Pending application names typed into the rename fields
+    #[serde(skip)]
+    rename_forms: HashMap<String, String>,
+    #[serde(skip)]
+    schedule_forms: HashMap<String, ScheduleForm>,
+    #[serde(skip)]
+    rcon_forms: HashMap<String, RconForm>,
+    #[serde(skip)]
+    wake_forms: HashMap<String, WakeProxy>,
+    /// Shutdown hook commands as typed, one per line
+    #[serde(skip)]
+    hook_commands: HashMap<String, String>,
 
     #[serde(skip)] // Skip error log
     error_log: Vec<Error>,
@@ -41,6 +172,8 @@ pub struct ServerCruncherApp {
     show_error_log: bool,
     #[serde(skip)]
     api_perfs: ApiPerfsData,
+    #[serde(skip)]
+    savings_window: SavingsWindowData,
 }
 
 impl Default for ServerCruncherApp {
@@ -54,15 +187,78 @@ impl Default for ServerCruncherApp {
             endpoint: Rc::new(Unconfigured),
             hcloud_api_secret: None,
             application_list: None,
+            known_servers: HashMap::new(),
+            known_prices: HashMap::new(),
+            app_label: DEFAULT_APP_LABEL.to_string(),
+            overrides: Overrides::default(),
+            identities: Identities::default(),
+            retention: HashMap::new(),
+            show_gross: false,
+            show_cost_panel: false,
+            savings: SavingsLedger::default(),
+            budgets: Budgets::default(),
+            spend: Spend::default(),
+            schedules: Schedules::default(),
+            scheduler: None,
+            idle_rules: HashMap::new(),
+            shutdown_hooks: HashMap::new(),
+            rcon: HashMap::new(),
+            rcon_output: HashMap::new(),
+            game_queries: HashMap::new(),
+            wake_proxies: HashMap::new(),
+            proxies: HashMap::new(),
+            health_checks: HashMap::new(),
+            healthy: HashMap::new(),
+            health_due: false,
+            health_pending: HashSet::new(),
             remote_loading: HashSet::new(),
+            refresh_requested: false,
+            refreshed_at: None,
+            list_progress: None,
+            list_incomplete: false,
+            workflows: HashMap::new(),
+            actions: ActionTracker::default(),
+            journal: Journal::default(),
+            restore_forms: HashMap::new(),
+            plan: None,
+            pending_plans: Vec::new(),
+            over_budget: HashSet::new(),
+            idle: HashSet::new(),
+            metrics: HashMap::new(),
+            metrics_requested: HashMap::new(),
+            empty: HashSet::new(),
+            occupancy: HashMap::new(),
+            queries_requested: HashMap::new(),
+            forced_crunch: HashSet::new(),
+            pending_transitions: HashMap::new(),
+            rename_forms: HashMap::new(),
+            schedule_forms: HashMap::new(),
+            rcon_forms: HashMap::new(),
+            wake_forms: HashMap::new(),
+            hook_commands: HashMap::new(),
             error_log: Vec::new(),
             show_error_log: false,
             api_perfs: Default::default(),
+            savings_window: Default::default(),
         }
     }
 }
 
 impl ServerCruncherApp {
+    /// Writes the app with its API key and RCON passwords encrypted, while keeping them
+    /// usable in memory
+    fn set_encrypted(&mut self, storage: &mut dyn eframe::Storage) {
+        let secret = self.hcloud_api_secret.clone();
+        let rcon = self.rcon.clone();
+        self.hcloud_api_secret = secret.clone().map(|secret| secret.encrypt(&self.local_key));
+        for rcon in self.rcon.values_mut() {
+            rcon.password = rcon.password.clone().encrypt(&self.local_key);
+        }
+        eframe::set_value(storage, eframe::APP_KEY, self);
+        self.hcloud_api_secret = secret;
+        self.rcon = rcon;
+    }
+
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         // This is also where you can customized the look at feel of egui using
@@ -79,21 +275,41 @@ impl ServerCruncherApp {
                 loaded_app.hcloud_api_secret = Some(secret.decrypt(&loaded_app.local_key));
                 loaded_app.endpoint = Rc::new(Hetzner);
             }
+            for rcon in loaded_app.rcon.values_mut() {
+                rcon.password = rcon.password.clone().decrypt(&loaded_app.local_key);
+            }
+
+            // Pick up workflows interrupted by the app closing
+            loaded_app.journal = eframe::get_value(storage, JOURNAL_KEY).unwrap_or_default();
+            loaded_app.resume_workflows(&cc.egui_ctx);
+            loaded_app.start_scheduler(&cc.egui_ctx);
+            // Applications may have been running all along, they aren't known to be ready yet
+            loaded_app.health_due = true;
             return loaded_app;
         }
 
-        Default::default()
+        let mut app = Self::default();
+        app.start_scheduler(&cc.egui_ctx);
+        app
     }
 }
 
 impl eframe::App for ServerCruncherApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        // Encrypt API keys before writing to disk
-        if let Some(secret) = self.hcloud_api_secret.clone() {
-            self.hcloud_api_secret = Some(secret.encrypt(&self.local_key));
+        if self.scheduler.is_some() {
+            self.schedules.checked_until = Some(chrono::Utc::now());
+        }
+        self.set_encrypted(storage);
+        eframe::set_value(storage, JOURNAL_KEY, &self.journal);
+    }
+
+    /// Save often while workflows are running, so the journal survives a crash
+    fn auto_save_interval(&self) -> Duration {
+        match self.journal.is_empty() {
+            true => Duration::from_secs(30),
+            false => Duration::from_secs(1),
         }
-        eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
@@ -116,16 +332,34 @@ impl eframe::App for ServerCruncherApp {
                         self.open_api_perfs_window();
                     }
                 });
+                ui.toggle_value(&mut self.show_cost_panel, "💶 Costs");
+                if ui.button("📉 Savings").clicked() {
+                    self.open_savings_window();
+                }
             });
         });
 
         if let Ok(remote) = self.rx.try_recv() {
             self.handle_incoming_remote(remote);
         }
+        if self.refresh_requested || self.refresh_due() {
+            self.refresh_requested = false;
+            self.refresh_application_list(ctx);
+        }
+        self.request_metrics(ctx);
+        self.request_queries(ctx);
+        self.request_health_checks(ctx);
+        self.sync_proxies(ctx);
+        for plan in std::mem::take(&mut self.pending_plans) {
+            self.execute_plan(plan, ctx);
+        }
 
         self.draw_status_bar(ctx);
         self.draw_error_window(ctx);
         self.draw_api_perfs_window(ctx);
+        self.draw_plan_window(ctx);
+        self.draw_cost_panel(ctx);
+        self.draw_savings_window(ctx);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             // The central panel the region left after adding TopPanel's and SidePanel's
@@ -144,17 +378,665 @@ impl App {
     pub fn unset_loading(&mut self, origin: &str) {
         self.remote_loading.remove(origin);
     }
+    /// Resumes or rolls back every workflow left in the journal, grouped by origin
+    fn resume_workflows(&mut self, ctx: &egui::Context) {
+        let mut by_origin: HashMap<String, Vec<JournalEntry>> = HashMap::new();
+        for entry in self.journal.entries() {
+            by_origin
+                .entry(entry.origin.clone())
+                .or_default()
+                .push(entry.clone());
+        }
+
+        for (origin, entries) in by_origin {
+            let last = entries[entries.len() - 1].clone();
+            self.set_loading(&origin);
+            self.req_resume(entries, &origin, ctx);
+
+            // Entries stay in the journal until the endpoint reports back
+            if self.remote_loading.contains(&origin) {
+                self.workflows
+                    .insert(origin, WorkflowUpdate::new(last.step, last.server_id));
+            }
+        }
+    }
+
+    /// Records the runs missed while the app was closed and starts triggering the schedules
+    fn start_scheduler(&mut self, ctx: &egui::Context) {
+        self.schedules.reload_calendars();
+        for run in self.schedules.record_missed(chrono::Utc::now()) {
+            let error = format!(
+                "Missed scheduled {} of {} at {}",
+                run.action.label().to_lowercase(),
+                self.application_name(&run.application),
+                run.at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            );
+            self.error_log.push(Error::new(&error));
+        }
+        let (sender, receiver) = watch::channel(self.schedules.clone());
+        self.scheduler = Some(sender);
+        tokio::spawn(scheduler::run(receiver, self.tx.clone(), ctx.clone()));
+    }
+
+    /// Hands changed schedules to the scheduler
+    fn publish_schedules(&self) {
+        if let Some(scheduler) = &self.scheduler {
+            let _ = scheduler.send(self.schedules.clone());
+        }
+    }
+
+    fn applications(&self) -> &[Application] {
+        match &self.application_list {
+            Some(RemoteData {
+                data: Data::Application(applications),
+                ..
+            }) => applications,
+            _ => &[],
+        }
+    }
+
+    fn application_by_id(&self, id: &str) -> Option<&Application> {
+        self.applications()
+            .iter()
+            .find(|application| application.id() == id)
+    }
+
+    /// Name of the application of the given ID, or the ID if it's not around anymore
+    fn application_name(&self, id: &str) -> String {
+        self.application_by_id(id)
+            .and_then(|application| application.name.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// Plans a scheduled crunch or restore, unless the application is busy or already there
+    fn run_scheduled(&mut self, run: ScheduledRun) {
+        let application = match self.application_by_id(&run.application).cloned() {
+            Some(application) => application,
+            None => return,
+        };
+        let name = application.name.clone().unwrap_or_default();
+        let skipped = match (self.list_incomplete, self.remote_loading.contains(&name)) {
+            (true, _) => Some("the application list is incomplete"),
+            (false, true) => Some("it is busy"),
+            (false, false) => None,
+        };
+        if let Some(reason) = skipped {
+            let error = format!(
+                "Skipped scheduled {} of {}, {}",
+                run.action.label().to_lowercase(),
+                name,
+                reason
+            );
+            self.error_log.push(Error::new(&error));
+            return;
+        }
+
+        let workflow = self.workflows.get(&name);
+        let lifecycle = Lifecycle::of(&application, workflow, self.actions.pending(&name));
+        let plan = match run.action {
+            ScheduledAction::Crunch if lifecycle.can_crunch() => {
+                Some(self.plan_crunch(&name, &application))
+            }
+            ScheduledAction::Restore if lifecycle.can_restore() => {
+                self.restore_plan(&name, &application)
+            }
+            // Already crunched or running
+            _ => None,
+        };
+        if let Some(plan) = plan {
+            self.pending_plans.push(plan);
+        }
+    }
+
+    /// Everything to do on the servers of the application before they are shut down
+    fn pre_shutdown(&self, application: &Application) -> PreShutdown {
+        PreShutdown {
+            rcon: self.rcon.get(application.id()).cloned(),
+            hooks: self
+                .shutdown_hooks
+                .get(application.id())
+                .filter(|hooks| !hooks.commands.is_empty())
+                .cloned(),
+            hosts: application
+                .servers
+                .iter()
+                .flatten()
+                .map(|server| {
+                    let ip = server.public_net.ipv4.as_ref().map(|ipv4| ipv4.ip.clone());
+                    (server.id, ip)
+                })
+                .collect(),
+        }
+    }
+
+    fn plan_crunch(&self, name: &str, application: &Application) -> Plan {
+        let prepare = self.pre_shutdown(application);
+        Plan::crunch(name, application, &self.app_label, &prepare)
+    }
+
+    fn plan_restore(&self, name: &str, spec: &RestoreSpec) -> Plan {
+        let prices = self.known_prices.get(&spec.server.server_type);
+        let snapshot = self
+            .applications()
+            .iter()
+            .flat_map(|application| application.images.iter().flatten())
+            .find(|image| image.id == spec.image_id);
+        Plan::restore(
+            name,
+            spec,
+            snapshot,
+            &self.app_label,
+            prices.map(Vec::as_slice),
+        )
+    }
+
+    /// Restore of the newest snapshot, logging an error if the server to create is unknown
+    fn restore_plan(&mut self, name: &str, application: &Application) -> Option<Plan> {
+        match application.restore_spec(self.known_servers.get(application.id())) {
+            Some(spec) => Some(self.plan_restore(name, &spec)),
+            None => {
+                let error = format!("Can't restore {}, its server is unknown", name);
+                self.error_log.push(Error::new(&error));
+                None
+            }
+        }
+    }
+
+    /// Restores a crunched application a client connected to, unless it is on its way up
+    fn wake(&mut self, id: &str) {
+        let application = match self.application_by_id(id).cloned() {
+            Some(application) => application,
+            None => return,
+        };
+        let name = application.name.clone().unwrap_or_default();
+        let workflow = self.workflows.get(&name);
+        let lifecycle = Lifecycle::of(&application, workflow, self.actions.pending(&name));
+        let unknown = self.list_incomplete || self.remote_loading.contains(&name);
+        if unknown || !lifecycle.can_restore() {
+            return;
+        }
+        if let Some(plan) = self.restore_plan(&name, &application) {
+            self.pending_plans.push(plan);
+        }
+    }
+
+    /// Whether clients may be sent to an application, i.e. its health checks passed if it has any
+    fn is_ready(&self, application: &Application) -> bool {
+        let checked = self
+            .health_checks
+            .get(application.id())
+            .map_or(false, |checks| !checks.checks.is_empty());
+        !checked || self.healthy.get(application.id()) == Some(&true)
+    }
+
+    /// Runs the health checks of running applications that didn't pass them yet, e.g. after
+    /// the app started or their checks were changed
+    fn request_health_checks(&mut self, ctx: &egui::Context) {
+        if !std::mem::take(&mut self.health_due) {
+            return;
+        }
+        let due: Vec<_> = self
+            .applications()
+            .iter()
+            .filter(|application| self.healthy.get(application.id()) != Some(&true))
+            .filter(|application| !self.health_pending.contains(application.id()))
+            .filter_map(|application| {
+                let checks = self.health_checks.get(application.id())?;
+                let name = application.name.clone().unwrap_or_default();
+                let workflow = self.workflows.get(&name);
+                let lifecycle = Lifecycle::of(application, workflow, self.actions.pending(&name));
+                let running =
+                    lifecycle == Lifecycle::Running && !self.remote_loading.contains(&name);
+                let hosts: Vec<(i32, Option<String>)> = application
+                    .servers
+                    .iter()
+                    .flatten()
+                    .map(|server| {
+                        let ip = server.public_net.ipv4.as_ref().map(|ipv4| ipv4.ip.clone());
+                        (server.id, ip)
+                    })
+                    .collect();
+                let id = application.id().to_string();
+                (running && !checks.checks.is_empty()).then(|| (id, checks.clone(), hosts))
+            })
+            .collect();
+        for (id, checks, hosts) in due {
+            self.health_pending.insert(id.clone());
+            let (tx, ctx) = (self.tx.clone(), ctx.clone());
+            tokio::spawn(async move {
+                let mut health = Ok(());
+                for (server, host) in hosts {
+                    health = match host {
+                        Some(host) => checks.run(&host).await,
+                        None => Err(format!(
+                            "Server {} has no public IPv4 address to run its health checks",
+                            server
+                        )),
+                    };
+                    if health.is_err() {
+                        break;
+                    }
+                }
+                let data = Data::Health {
+                    application: id,
+                    health,
+                };
+                let _ = tx.send(RemoteData::new(data, HEALTH_ORIGIN));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Starts and stops proxies to match their configuration, and points them at the
+    /// running servers once they are ready
+    fn sync_proxies(&mut self, ctx: &egui::Context) {
+        // Targets stay as they were until the servers are known again
+        if self.list_incomplete {
+            return;
+        }
+        let configs = &self.wake_proxies;
+        self.proxies
+            .retain(|id, proxy| configs.get(id) == Some(&proxy.config));
+        let targets: Vec<(String, WakeProxy, Option<String>)> = self
+            .wake_proxies
+            .iter()
+            .map(|(id, config)| {
+                let application = self.application_by_id(id).filter(|app| self.is_ready(app));
+                let target = application.and_then(|app| proxy::target(app, config.target_port));
+                (id.clone(), config.clone(), target)
+            })
+            .collect();
+        for (id, config, target) in targets {
+            match self.proxies.get(&id) {
+                Some(proxy) => proxy.set_target(target),
+                None => {
+                    let (tx, ctx) = (self.tx.clone(), ctx.clone());
+                    let proxy = proxy::ProxyHandle::start(id.clone(), config, target, tx, ctx);
+                    self.proxies.insert(id, proxy);
+                }
+            }
+        }
+    }
+
+    /// Regroups a fresh application list and remembers what it can tell about each application
+    fn set_application_list(
+        &mut self,
+        applications: Vec<Application>,
+        origin: String,
+        updated_at: Timestamp,
+        complete: bool,
+    ) {
+        let mut applications = self.overrides.apply(applications);
+        self.identities.apply(&mut applications, complete);
+        for application in &applications {
+            if let Some(server) = application.servers.as_ref().and_then(|s| s.first()) {
+                self.known_servers
+                    .insert(application.id().to_string(), ServerSpec::from(server));
+            }
+            for server in application.servers.iter().flatten() {
+                self.known_prices.insert(
+                    server.server_type.name.clone(),
+                    server.server_type.prices.clone(),
+                );
+            }
+        }
+        // Only prune against complete lists, a missing image could be the one to keep
+        if complete {
+            self.queue_automatic_prunes(&applications);
+            self.spend.observe(&applications, chrono::Utc::now());
+            self.check_budgets(&applications);
+            self.health_due = true;
+        }
+        self.list_progress = None;
+        self.list_incomplete = !complete;
+        self.unset_loading(&origin);
+        self.application_list = Some(RemoteData {
+            data: Data::Application(applications),
+            updated_at,
+            origin,
+        });
+    }
+
+    /// Queues the snapshots automatic retention policies would prune, for idle applications
+    fn queue_automatic_prunes(&mut self, applications: &[Application]) {
+        let now = chrono::Utc::now();
+        for application in applications {
+            let policy = match self.retention.get(application.id()) {
+                Some(policy) if policy.automatic => policy,
+                _ => continue,
+            };
+            let name = application.name.clone().unwrap_or_default();
+            if self.remote_loading.contains(&name) || self.workflows.contains_key(&name) {
+                continue;
+            }
+            let images = application.images.clone().unwrap_or_default();
+            let preview = policy.preview(&images, now);
+            if !preview.is_empty() {
+                self.pending_plans.push(Plan::prune(&name, &preview));
+            }
+        }
+    }
+
+    /// Proposes or schedules a crunch of idle applications about to exceed their budget
+    fn check_budgets(&mut self, applications: &[Application]) {
+        let now = chrono::Utc::now();
+        for application in applications {
+            let id = application.id();
+            let budget = match self.budgets.get(id) {
+                Some(budget) => budget,
+                None => continue,
+            };
+            if !self.spend.status(id, budget, now).is_imminent() {
+                self.over_budget.remove(id);
+                continue;
+            }
+
+            let name = application.name.clone().unwrap_or_default();
+            let workflow = self.workflows.get(&name);
+            let lifecycle = Lifecycle::of(application, workflow, self.actions.pending(&name));
+            if self.remote_loading.contains(&name) || !lifecycle.can_crunch() {
+                continue;
+            }
+            let plan = self.plan_crunch(&name, application);
+            match self.budgets.over_budget {
+                OverBudget::Warn => (),
+                OverBudget::Propose
+                    if self.plan.is_none() && self.over_budget.insert(id.into()) =>
+                {
+                    self.plan = Some(plan);
+                }
+                OverBudget::Propose => (),
+                OverBudget::Crunch if self.over_budget.insert(id.into()) => {
+                    self.pending_plans.push(plan);
+                }
+                OverBudget::Crunch => (),
+            }
+        }
+    }
+
+    /// Whether the application list is due for its periodic refresh
+    fn refresh_due(&self) -> bool {
+        let unlocked = matches!(self.hcloud_api_secret, Some(Secret::Unencrypted(_)));
+        let due = self.refreshed_at.map_or(true, |refreshed| {
+            (chrono::Utc::now() - refreshed)
+                .to_std()
+                .map_or(false, |age| age >= REFRESH_INTERVAL)
+        });
+        unlocked && due
+    }
+
+    /// Fetches the metrics of applications with servers and an idle rule every few minutes
+    fn request_metrics(&mut self, ctx: &egui::Context) {
+        let now = chrono::Utc::now();
+        let due: Vec<(Application, u32)> = self
+            .applications()
+            .iter()
+            .filter(|application| application.servers.iter().flatten().next().is_some())
+            .filter_map(|application| {
+                let rule = self.idle_rules.get(application.id())?;
+                let requested = self.metrics_requested.get(application.id());
+                let due = requested.map_or(true, |requested| {
+                    (now - *requested)
+                        .to_std()
+                        .map_or(false, |age| age >= METRICS_INTERVAL)
+                });
+                due.then(|| (application.clone(), rule.minutes))
+            })
+            .collect();
+        for (application, minutes) in due {
+            self.metrics_requested
+                .insert(application.id().to_string(), now);
+            self.req_metrics(&application, minutes, ctx);
+        }
+    }
+
+    /// Asks the game servers of applications with a query who is online, every minute
+    fn request_queries(&mut self, ctx: &egui::Context) {
+        let now = chrono::Utc::now();
+        let due: Vec<(Application, GameQuery)> = self
+            .applications()
+            .iter()
+            .filter(|application| application.servers.iter().flatten().next().is_some())
+            .filter_map(|application| {
+                let query = self.game_queries.get(application.id())?;
+                let requested = self.queries_requested.get(application.id());
+                let due = requested.map_or(true, |requested| {
+                    (now - *requested)
+                        .to_std()
+                        .map_or(false, |age| age >= QUERY_INTERVAL)
+                });
+                due.then(|| (application.clone(), *query))
+            })
+            .collect();
+        for (application, query) in due {
+            let id = application.id().to_string();
+            self.queries_requested.insert(id.clone(), now);
+            let hosts: Vec<(i32, Option<String>)> = application
+                .servers
+                .iter()
+                .flatten()
+                .map(|server| {
+                    let ip = server.public_net.ipv4.as_ref().map(|ipv4| ipv4.ip.clone());
+                    (server.id, ip)
+                })
+                .collect();
+            let (tx, ctx) = (self.tx.clone(), ctx.clone());
+            tokio::spawn(async move {
+                let mut statuses = Vec::new();
+                for (server, host) in hosts {
+                    let status = match host {
+                        Some(host) => query.probe(&host).await,
+                        None => Err(format!("Server {} has no public IPv4 address", server)),
+                    };
+                    statuses.push((server, status));
+                }
+                let data = Data::Players {
+                    application: id,
+                    statuses,
+                };
+                let _ = tx.send(RemoteData::new(data, QUERY_ORIGIN));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Proposes or schedules a crunch once nobody has been online long enough
+    fn check_empty(&mut self, id: &str) {
+        let query = match self.game_queries.get(id) {
+            Some(query) => *query,
+            None => return,
+        };
+        let empty = match (query.empty_minutes, self.occupancy.get(id)) {
+            (Some(minutes), Some(occupancy)) => occupancy
+                .empty_for(chrono::Utc::now())
+                .map_or(false, |empty| {
+                    empty >= chrono::Duration::minutes(minutes as i64)
+                }),
+            _ => false,
+        };
+        if !empty {
+            self.empty.remove(id);
+            return;
+        }
+        let application = match self.application_by_id(id) {
+            Some(application) => application.clone(),
+            None => return,
+        };
+
+        let name = application.name.clone().unwrap_or_default();
+        let workflow = self.workflows.get(&name);
+        let lifecycle = Lifecycle::of(&application, workflow, self.actions.pending(&name));
+        if self.remote_loading.contains(&name) || !lifecycle.can_crunch() {
+            return;
+        }
+        let plan = self.plan_crunch(&name, &application);
+        match query.automatic {
+            true if self.empty.insert(id.into()) => self.pending_plans.push(plan),
+            false if self.plan.is_none() && self.empty.insert(id.into()) => self.plan = Some(plan),
+            _ => (),
+        }
+    }
+
+    /// Proposes or schedules a crunch once an application has been idle long enough
+    fn check_idle(&mut self, id: &str) {
+        let rule = match self.idle_rules.get(id) {
+            Some(rule) => *rule,
+            None => return,
+        };
+        let idle = self
+            .metrics
+            .get(id)
+            .map_or(false, |metrics| rule.is_idle(metrics));
+        if !idle {
+            self.idle.remove(id);
+            return;
+        }
+        let application = match self.application_by_id(id) {
+            Some(application) => application.clone(),
+            None => return,
+        };
+
+        let name = application.name.clone().unwrap_or_default();
+        let workflow = self.workflows.get(&name);
+        let lifecycle = Lifecycle::of(&application, workflow, self.actions.pending(&name));
+        if self.remote_loading.contains(&name) || !lifecycle.can_crunch() {
+            return;
+        }
+        let plan = self.plan_crunch(&name, &application);
+        match rule.automatic {
+            true if self.idle.insert(id.into()) => self.pending_plans.push(plan),
+            false if self.plan.is_none() && self.idle.insert(id.into()) => self.plan = Some(plan),
+            _ => (),
+        }
+    }
+
     fn handle_incoming_remote(&mut self, remote: RemoteData) {
         match remote.data {
-            Data::Application(_) => {
+            Data::Application(applications) => {
+                self.set_application_list(applications, remote.origin, remote.updated_at, true);
+            }
+            Data::Partial {
+                applications,
+                error,
+            } => {
+                self.error_log.push(Error {
+                    error,
+                    ts: remote.updated_at.clone(),
+                });
+                self.set_application_list(applications, remote.origin, remote.updated_at, false);
+            }
+            Data::Action(action) => {
+                let finished = action.status != action::Status::Running;
+                let failed = self.actions.update(&remote.origin, action);
+
+                // Running workflows handle their own actions and failures
+                if self.workflows.contains_key(&remote.origin) {
+                    return;
+                }
+                if let Some(action) = failed {
+                    let error = match action.error {
+                        Some(error) => format!("{}: {}", action.command, error.message),
+                        None => format!("{} failed", action.command),
+                    };
+                    self.error_log.push(Error {
+                        error,
+                        ts: remote.updated_at,
+                    });
+                    self.show_error_log = true;
+                }
+                if finished {
+                    self.unset_loading(&remote.origin);
+                    // Server state has changed, fetch it on the next frame
+                    self.refresh_requested = true;
+                }
+            }
+            Data::Listing(progress) => self.list_progress = Some(progress),
+            Data::Scheduled(run) => self.run_scheduled(run),
+            Data::Calendars(calendars) => self.schedules.adopt_calendars(calendars),
+            Data::Wake(id) => self.wake(&id),
+            Data::Health {
+                application,
+                health,
+            } => {
+                if let Err(error) = &health {
+                    self.error_log.push(Error {
+                        error: error.clone(),
+                        ts: remote.updated_at,
+                    });
+                }
+                self.health_pending.remove(&application);
+                self.healthy.insert(application, health.is_ok());
+            }
+            Data::Metrics {
+                application,
+                metrics,
+            } => match metrics {
+                Ok(metrics) => {
+                    self.metrics.insert(application.clone(), metrics);
+                    self.check_idle(&application);
+                }
+                Err(error) => self.error_log.push(Error {
+                    error,
+                    ts: remote.updated_at,
+                }),
+            },
+            Data::Players {
+                application,
+                statuses,
+            } => {
+                self.occupancy
+                    .entry(application.clone())
+                    .or_default()
+                    .observe(statuses, chrono::Utc::now());
+                self.check_empty(&application);
+            }
+            Data::Rcon(output) => {
+                let lines = self.rcon_output.entry(remote.origin).or_default();
+                lines.push(output);
+                let excess = lines.len().saturating_sub(RCON_OUTPUT_LINES);
+                lines.drain(..excess);
+            }
+            Data::Image(_) | Data::Pruned(_) => {
+                self.unset_loading(&remote.origin);
+                self.refresh_requested = true;
+            }
+            Data::Workflow(update) if update.is_done() => {
+                if let Some(mut transition) = self.pending_transitions.remove(&remote.origin) {
+                    transition.at = chrono::Utc::now();
+                    self.savings.record(transition);
+                }
+                self.journal.finish(&remote.origin);
+                self.workflows.remove(&remote.origin);
                 self.unset_loading(&remote.origin);
-                self.application_list = Some(remote);
+                self.refresh_requested = true;
+            }
+            Data::Workflow(update) => {
+                // Servers are replaced or changed, earlier checks don't tell about them anymore
+                if let Some(application) = self.find_application(&remote.origin) {
+                    let id = application.id().to_string();
+                    self.healthy.remove(&id);
+                }
+                self.journal.record(&remote.origin, &update);
+                if let Some(action) = update.action.clone() {
+                    self.actions.update(&remote.origin, action);
+                }
+                self.workflows.insert(remote.origin, update);
             }
             Data::Error(e) => {
                 self.error_log.push(Error {
                     error: e,
                     ts: remote.updated_at,
                 });
+                self.actions.clear(&remote.origin);
+                self.journal.finish(&remote.origin);
+                self.pending_transitions.remove(&remote.origin);
+                self.list_progress = None;
+                if self.workflows.remove(&remote.origin).is_some() {
+                    // A failed workflow may have left servers in a different state
+                    self.refresh_requested = true;
+                }
                 self.unset_loading(&remote.origin);
                 self.show_error_log = true;
             }
@@ -164,8 +1046,120 @@ impl App {
 
 #[cfg(test)]
 mod test {
-    use super::App;
-    use crate::utils::{Data, RemoteData};
+    use super::{
+        api::{Endpoint, ServerAction},
+        App,
+    };
+    use crate::utils::{
+        empty_server, empty_snapshot, generate_application_list, prices, Application, Data,
+        GameQuery, HealthCheck, HealthChecks, IdleRule, JournalEntry, Key, ListProgress, Occupancy,
+        OverBudget, PlanKind, RconShutdown, RemoteData, Resource, RestoreSpec, RetentionPolicy,
+        Sample, ScheduledAction, ScheduledRun, Secret, ServerMetrics, ServerSpec, ServerStatus,
+        Step, Transition, WorkflowUpdate, HEALTH_ORIGIN, QUERY_ORIGIN, RCON_PASSWORD,
+    };
+    use egui::Context;
+    use hcloud::models::{
+        action, Action, CreatedFrom, Image, ListImagesResponse, ListServersResponse,
+    };
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    /// Endpoint remembering the API key every request was sent with
+    #[derive(Default)]
+    struct KeyRecorder(RefCell<Vec<String>>);
+    impl KeyRecorder {
+        fn record(&self, app: &mut App, origin: &str) {
+            let key: Key = app.hcloud_api_secret.clone().unwrap().into();
+            self.0.borrow_mut().push(key.0);
+            app.unset_loading(origin);
+        }
+    }
+    impl Endpoint for KeyRecorder {
+        fn req_application_list(&self, app: &mut App, origin: &'static str, _ctx: &Context) {
+            self.record(app, origin);
+        }
+        fn req_server_action(
+            &self,
+            app: &mut App,
+            _server_id: i32,
+            _action: ServerAction,
+            origin: &str,
+            _ctx: &Context,
+        ) {
+            self.record(app, origin);
+        }
+        fn req_crunch(
+            &self,
+            app: &mut App,
+            _application: &Application,
+            origin: &str,
+            _ctx: &Context,
+        ) {
+            self.record(app, origin);
+        }
+        fn req_restore(&self, app: &mut App, _spec: RestoreSpec, origin: &str, _ctx: &Context) {
+            self.record(app, origin);
+        }
+        fn req_resume(
+            &self,
+            app: &mut App,
+            _entries: Vec<JournalEntry>,
+            origin: &str,
+            _ctx: &Context,
+        ) {
+            self.record(app, origin);
+        }
+        fn req_convert_backup(&self, app: &mut App, _image_id: i32, origin: &str, _ctx: &Context) {
+            self.record(app, origin);
+        }
+        fn req_prune(&self, app: &mut App, _image_ids: Vec<i32>, origin: &str, _ctx: &Context) {
+            self.record(app, origin);
+        }
+        fn req_metrics(
+            &self,
+            app: &mut App,
+            _application: &Application,
+            _minutes: u32,
+            _ctx: &Context,
+        ) {
+            self.record(app, "");
+        }
+    }
+
+    /// Keeps saved values in memory
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn save() {
+        const TOKEN: &str = "hcloud-token";
+        let rcon = RconShutdown {
+            password: Secret::Unencrypted(Key(RCON_PASSWORD.to_string())),
+            ..Default::default()
+        };
+        let mut app = App {
+            hcloud_api_secret: Some(Secret::Unencrypted(Key(TOKEN.to_string()))),
+            rcon: HashMap::from([("game".to_string(), rcon)]),
+            ..Default::default()
+        };
+        let mut storage = MemoryStorage::default();
+        eframe::App::save(&mut app, &mut storage);
+
+        // Written encrypted, but still usable for the next requests
+        assert!(!storage.0[eframe::APP_KEY].contains(TOKEN));
+        let key: Key = app.hcloud_api_secret.clone().unwrap().into();
+        assert_eq!(key.0, TOKEN);
+        let password: Key = app.rcon["game"].password.clone().into();
+        assert_eq!(password.0, RCON_PASSWORD);
+    }
 
     #[test]
     fn set_loading() {
@@ -198,10 +1192,40 @@ mod test {
         app.set_loading(ORIGIN);
         assert!(app.application_list.is_none());
 
+        let progress = ListProgress {
+            what: "servers".to_string(),
+            fetched: 50,
+            total: Some(60),
+        };
+        app.handle_incoming_remote(RemoteData::new(Data::Listing(progress.clone()), ORIGIN));
+        assert_eq!(app.list_progress, Some(progress));
+        assert!(app.remote_loading.contains(ORIGIN));
+
         let remote_application = RemoteData::new(Data::Application(vec![]), ORIGIN);
         app.handle_incoming_remote(remote_application);
         assert!(app.application_list.is_some());
+        assert!(app.list_progress.is_none());
+        assert!(!app.remote_loading.contains(ORIGIN));
+
+        app.set_loading(ORIGIN);
+        let running = Action {
+            status: action::Status::Running,
+            ..Default::default()
+        };
+        let remote_action = RemoteData::new(Data::Action(running.clone()), ORIGIN);
+        app.handle_incoming_remote(remote_action);
+        assert!(app.remote_loading.contains(ORIGIN));
+        assert_eq!(app.actions.pending(ORIGIN).len(), 1);
+
+        let finished = Action {
+            status: action::Status::Success,
+            ..running
+        };
+        let remote_action = RemoteData::new(Data::Action(finished), ORIGIN);
+        app.handle_incoming_remote(remote_action);
         assert!(!app.remote_loading.contains(ORIGIN));
+        assert!(app.actions.is_empty());
+        assert!(app.refresh_requested);
 
         let remote_error = RemoteData::new(Data::Error(ERROR.to_string()), ORIGIN);
         assert!(app.error_log.is_empty());
@@ -209,4 +1233,642 @@ mod test {
         assert!(!app.error_log.is_empty());
         assert_eq!(app.error_log[0].error, ERROR);
     }
+
+    #[test]
+    fn handle_incoming_partial() {
+        const ORIGIN: &str = "partial";
+        let mut app = App::default();
+        app.set_loading(ORIGIN);
+
+        let partial = Data::Partial {
+            applications: vec![],
+            error: "images failed".to_string(),
+        };
+        app.handle_incoming_remote(RemoteData::new(partial, ORIGIN));
+        assert!(matches!(
+            app.application_list,
+            Some(RemoteData {
+                data: Data::Application(_),
+                ..
+            })
+        ));
+        assert!(app.list_incomplete);
+        assert_eq!(app.error_log.len(), 1);
+        assert!(!app.remote_loading.contains(ORIGIN));
+
+        let complete = RemoteData::new(Data::Application(vec![]), ORIGIN);
+        app.handle_incoming_remote(complete);
+        assert!(!app.list_incomplete);
+    }
+
+    /// Servers failing to load make running applications look crunched
+    #[test]
+    fn partial_refuses_lifecycle() {
+        const ORIGIN: &str = "game";
+        let mut app = App::default();
+        let snapshot = Image {
+            created_from: Some(Box::new(CreatedFrom {
+                id: 1,
+                name: ORIGIN.to_string(),
+            })),
+            ..empty_snapshot()
+        };
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![snapshot],
+        };
+        let applications = generate_application_list(&Default::default(), &images, &app.app_label);
+        let id = applications[0].id().to_string();
+        let spec = ServerSpec {
+            name: ORIGIN.to_string(),
+            server_type: "cx11".to_string(),
+            location: "nbg1".to_string(),
+        };
+        app.known_servers.insert(id.clone(), spec);
+        let partial = Data::Partial {
+            applications,
+            error: "servers failed".to_string(),
+        };
+        app.handle_incoming_remote(RemoteData::new(partial, "refresh"));
+        app.error_log.clear();
+
+        let run = ScheduledRun {
+            application: id.clone(),
+            action: ScheduledAction::Restore,
+            at: chrono::Utc::now(),
+        };
+        app.handle_incoming_remote(RemoteData::new(Data::Scheduled(run), "scheduler"));
+        app.wake(&id);
+        assert!(app.pending_plans.is_empty());
+        assert!(app.error_log[0]
+            .error
+            .ends_with("the application list is incomplete"));
+
+        let application = app.application_by_id(&id).cloned().unwrap();
+        let plan = app.restore_plan(ORIGIN, &application).unwrap();
+        app.execute_plan(plan, &Context::default());
+        let crunch = app.plan_crunch(ORIGIN, &application);
+        app.execute_plan(crunch, &Context::default());
+        assert!(!app.remote_loading.contains(ORIGIN));
+        assert_eq!(app.error_log.len(), 3);
+    }
+
+    #[test]
+    fn handle_incoming_overrides() {
+        const ORIGIN: &str = "grouped";
+        let mut app = App::default();
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![
+                Image {
+                    id: 1,
+                    ..empty_snapshot()
+                },
+                Image {
+                    id: 2,
+                    ..empty_snapshot()
+                },
+            ],
+        };
+        let applications = generate_application_list(&Default::default(), &images, &app.app_label);
+        assert_eq!(applications.len(), 2);
+
+        app.overrides.assign(Resource::Image(1), "merged");
+        app.overrides.assign(Resource::Image(2), "merged");
+        for _ in 0..2 {
+            let remote = RemoteData::new(Data::Application(applications.clone()), ORIGIN);
+            app.handle_incoming_remote(remote);
+            match &app.application_list {
+                Some(RemoteData {
+                    data: Data::Application(applications),
+                    ..
+                }) => {
+                    assert_eq!(applications.len(), 1);
+                    assert_eq!(applications[0].name.clone().unwrap(), "merged");
+                    assert_eq!(applications[0].id(), "name:merged");
+                }
+                _ => panic!("application list missing"),
+            }
+        }
+    }
+
+    #[test]
+    fn handle_incoming_retention() {
+        const ORIGIN: &str = "game";
+        let mut app = App::default();
+        let snapshot = |id, created: &str| Image {
+            id,
+            created: created.to_string(),
+            created_from: Some(Box::new(CreatedFrom {
+                id: 1,
+                name: ORIGIN.to_string(),
+            })),
+            ..empty_snapshot()
+        };
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![
+                snapshot(2, "2022-10-01T00:00:00+00:00"),
+                snapshot(3, "2022-11-01T00:00:00+00:00"),
+            ],
+        };
+        let applications = generate_application_list(&Default::default(), &images, &app.app_label);
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            keep_weekly: 0,
+            automatic: true,
+        };
+        app.retention
+            .insert(applications[0].id().to_string(), policy);
+
+        // Partial lists are never pruned
+        let partial = Data::Partial {
+            applications: applications.clone(),
+            error: "servers failed".to_string(),
+        };
+        app.handle_incoming_remote(RemoteData::new(partial, ORIGIN));
+        assert!(app.pending_plans.is_empty());
+
+        let remote = RemoteData::new(Data::Application(applications), ORIGIN);
+        app.handle_incoming_remote(remote);
+        assert_eq!(app.pending_plans.len(), 1);
+        assert_eq!(app.pending_plans[0].origin, ORIGIN);
+        assert!(matches!(&app.pending_plans[0].kind, PlanKind::Prune(ids) if ids == &[2]));
+
+        app.set_loading(ORIGIN);
+        app.handle_incoming_remote(RemoteData::new(Data::Pruned(vec![2]), ORIGIN));
+        assert!(!app.remote_loading.contains(ORIGIN));
+        assert!(app.refresh_requested);
+    }
+
+    #[test]
+    fn handle_incoming_budget() {
+        const ORIGIN: &str = "expensive";
+        let mut app = App::default();
+        let mut server = empty_server();
+        server.name = ORIGIN.to_string();
+        server.datacenter.location.name = "fsn1".to_string();
+        server.server_type.prices = prices("fsn1", "7.30");
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let applications = generate_application_list(&servers, &Default::default(), &app.app_label);
+        app.budgets.set(applications[0].id(), Some(0.005));
+        let remote = || RemoteData::new(Data::Application(applications.clone()), ORIGIN);
+
+        app.handle_incoming_remote(remote());
+        assert!(app.plan.is_none());
+        assert!(app.pending_plans.is_empty());
+
+        app.budgets.over_budget = OverBudget::Propose;
+        app.handle_incoming_remote(remote());
+        assert!(matches!(app.plan.take().unwrap().kind, PlanKind::Crunch(_)));
+        // Only proposed once
+        app.handle_incoming_remote(remote());
+        assert!(app.plan.is_none());
+
+        app.over_budget.clear();
+        app.budgets.over_budget = OverBudget::Crunch;
+        app.handle_incoming_remote(remote());
+        assert_eq!(app.pending_plans.len(), 1);
+        assert_eq!(app.pending_plans[0].origin, ORIGIN);
+    }
+
+    #[test]
+    fn handle_incoming_scheduled() {
+        const ORIGIN: &str = "nightly";
+        let mut app = App::default();
+        let mut server = empty_server();
+        server.name = ORIGIN.to_string();
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let applications = generate_application_list(&servers, &Default::default(), &app.app_label);
+        let id = applications[0].id().to_string();
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), ORIGIN));
+        let run = |action| ScheduledRun {
+            application: id.clone(),
+            action,
+            at: chrono::Utc::now(),
+        };
+        let scheduled = |action| RemoteData::new(Data::Scheduled(run(action)), "scheduler");
+
+        // Already running
+        app.handle_incoming_remote(scheduled(ScheduledAction::Restore));
+        assert!(app.pending_plans.is_empty());
+
+        app.set_loading(ORIGIN);
+        app.handle_incoming_remote(scheduled(ScheduledAction::Crunch));
+        assert!(app.pending_plans.is_empty());
+        assert_eq!(app.error_log.len(), 1);
+
+        app.unset_loading(ORIGIN);
+        app.handle_incoming_remote(scheduled(ScheduledAction::Crunch));
+        assert_eq!(app.pending_plans.len(), 1);
+        assert_eq!(app.pending_plans[0].origin, ORIGIN);
+        assert!(matches!(app.pending_plans[0].kind, PlanKind::Crunch(_)));
+    }
+
+    #[test]
+    fn refresh_due() {
+        const TOKEN: &str = "hcloud-token";
+        let recorder = Rc::new(KeyRecorder::default());
+        let mut app = App {
+            endpoint: recorder.clone(),
+            ..Default::default()
+        };
+        assert!(!app.refresh_due());
+
+        app.hcloud_api_secret = Some(Secret::Unencrypted(Key(TOKEN.to_string())));
+        assert!(app.refresh_due());
+        app.refresh_application_list(&Context::default());
+        assert_eq!(recorder.0.borrow().len(), 1);
+        assert!(!app.refresh_due());
+
+        app.refreshed_at = Some(chrono::Utc::now() - chrono::Duration::minutes(10));
+        assert!(app.refresh_due());
+    }
+
+    /// Scheduled runs happen long after the first autosave, which must not lose the API key
+    #[test]
+    fn scheduled_after_save() {
+        const ORIGIN: &str = "nightly";
+        const TOKEN: &str = "hcloud-token";
+        let recorder = Rc::new(KeyRecorder::default());
+        let mut app = App {
+            hcloud_api_secret: Some(Secret::Unencrypted(Key(TOKEN.to_string()))),
+            endpoint: recorder.clone(),
+            ..Default::default()
+        };
+        let mut server = empty_server();
+        server.name = ORIGIN.to_string();
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let applications = generate_application_list(&servers, &Default::default(), &app.app_label);
+        let id = applications[0].id().to_string();
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), ORIGIN));
+        eframe::App::save(&mut app, &mut MemoryStorage::default());
+
+        let run = ScheduledRun {
+            application: id,
+            action: ScheduledAction::Crunch,
+            at: chrono::Utc::now(),
+        };
+        app.handle_incoming_remote(RemoteData::new(Data::Scheduled(run), "scheduler"));
+        let ctx = Context::default();
+        for plan in std::mem::take(&mut app.pending_plans) {
+            app.execute_plan(plan, &ctx);
+        }
+        assert_eq!(*recorder.0.borrow(), vec![TOKEN.to_string()]);
+    }
+
+    #[test]
+    fn handle_incoming_wake() {
+        let mut app = App::default();
+        let mut snapshot = empty_snapshot();
+        snapshot
+            .labels
+            .insert(app.app_label.clone(), "woken".to_string());
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![snapshot],
+        };
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![],
+        };
+        let applications = generate_application_list(&servers, &images, &app.app_label);
+        let id = applications[0].id().to_string();
+        let name = applications[0].name.clone().unwrap();
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), &name));
+        let wake = || RemoteData::new(Data::Wake(id.clone()), "proxy");
+
+        // The server to create is unknown
+        app.handle_incoming_remote(wake());
+        assert!(app.pending_plans.is_empty());
+        assert_eq!(app.error_log.len(), 1);
+
+        let spec = ServerSpec {
+            name: "woken".to_string(),
+            server_type: "cx11".to_string(),
+            location: "nbg1".to_string(),
+        };
+        app.known_servers.insert(id.clone(), spec);
+        app.handle_incoming_remote(wake());
+        assert_eq!(app.pending_plans.len(), 1);
+        assert!(matches!(app.pending_plans[0].kind, PlanKind::Restore(_)));
+
+        // Further connections while restoring don't restore again
+        app.set_loading(&name);
+        app.handle_incoming_remote(wake());
+        assert_eq!(app.pending_plans.len(), 1);
+    }
+
+    #[test]
+    fn handle_incoming_health() {
+        const ORIGIN: &str = "checked";
+        let mut app = App::default();
+        let mut server = empty_server();
+        server.name = ORIGIN.to_string();
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let applications = generate_application_list(&servers, &Default::default(), &app.app_label);
+        let application = applications[0].clone();
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), ORIGIN));
+        assert!(app.is_ready(&application));
+
+        let checks = HealthChecks {
+            checks: vec![HealthCheck::tcp()],
+            ..Default::default()
+        };
+        app.health_checks
+            .insert(application.id().to_string(), checks);
+        assert!(!app.is_ready(&application));
+
+        let health = |health| Data::Health {
+            application: application.id().to_string(),
+            health,
+        };
+        let failed = health(Err("Health check failed".to_string()));
+        app.handle_incoming_remote(RemoteData::new(failed, HEALTH_ORIGIN));
+        assert!(!app.is_ready(&application));
+        assert_eq!(app.error_log.len(), 1);
+
+        app.handle_incoming_remote(RemoteData::new(health(Ok(())), HEALTH_ORIGIN));
+        assert!(app.is_ready(&application));
+
+        // A new workflow forgets about the checks of the previous servers
+        let crunch = WorkflowUpdate::new(Step::Shutdown, 0);
+        app.handle_incoming_remote(RemoteData::new(Data::Workflow(crunch), ORIGIN));
+        assert!(!app.is_ready(&application));
+    }
+
+    /// Applications that were running before their checks were known are checked as well
+    #[tokio::test]
+    async fn health_checks_running() {
+        const ORIGIN: &str = "running";
+        let mut app = App::default();
+        let mut server = empty_server();
+        server.name = ORIGIN.to_string();
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let applications = generate_application_list(&servers, &Default::default(), &app.app_label);
+        let id = applications[0].id().to_string();
+        let checks = HealthChecks {
+            checks: vec![HealthCheck::tcp()],
+            ..Default::default()
+        };
+        app.health_checks.insert(id.clone(), checks);
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), ORIGIN));
+        assert!(app.health_due);
+
+        let ctx = Context::default();
+        app.request_health_checks(&ctx);
+        assert!(!app.health_due);
+        assert!(app.health_pending.contains(&id));
+
+        // Checks still running aren't started again
+        app.health_due = true;
+        app.request_health_checks(&ctx);
+        assert_eq!(app.health_pending.len(), 1);
+
+        let passed = Data::Health {
+            application: id.clone(),
+            health: Ok(()),
+        };
+        app.handle_incoming_remote(RemoteData::new(passed, HEALTH_ORIGIN));
+        assert!(app.health_pending.is_empty());
+        assert_eq!(app.healthy.get(&id), Some(&true));
+
+        // Passed checks aren't repeated
+        app.health_due = true;
+        app.request_health_checks(&ctx);
+        assert!(app.health_pending.is_empty());
+    }
+
+    /// Clients may connect long after the first autosave, which must not lose the API key
+    #[test]
+    fn wake_after_save() {
+        const TOKEN: &str = "hcloud-token";
+        let recorder = Rc::new(KeyRecorder::default());
+        let mut app = App {
+            hcloud_api_secret: Some(Secret::Unencrypted(Key(TOKEN.to_string()))),
+            endpoint: recorder.clone(),
+            ..Default::default()
+        };
+        let mut snapshot = empty_snapshot();
+        snapshot
+            .labels
+            .insert(app.app_label.clone(), "woken".to_string());
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![snapshot],
+        };
+        let applications = generate_application_list(&Default::default(), &images, &app.app_label);
+        let id = applications[0].id().to_string();
+        let name = applications[0].name.clone().unwrap();
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), &name));
+        let spec = ServerSpec {
+            name: "woken".to_string(),
+            server_type: "cx11".to_string(),
+            location: "nbg1".to_string(),
+        };
+        app.known_servers.insert(id.clone(), spec);
+        eframe::App::save(&mut app, &mut MemoryStorage::default());
+
+        app.handle_incoming_remote(RemoteData::new(Data::Wake(id), "proxy"));
+        let ctx = Context::default();
+        for plan in std::mem::take(&mut app.pending_plans) {
+            app.execute_plan(plan, &ctx);
+        }
+        assert_eq!(*recorder.0.borrow(), vec![TOKEN.to_string()]);
+    }
+
+    #[test]
+    fn handle_incoming_players() {
+        const ORIGIN: &str = "empty";
+        let mut app = App::default();
+        let mut server = empty_server();
+        server.name = ORIGIN.to_string();
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let applications = generate_application_list(&servers, &Default::default(), &app.app_label);
+        let id = applications[0].id().to_string();
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), ORIGIN));
+        let query = GameQuery {
+            empty_minutes: Some(30),
+            ..Default::default()
+        };
+        app.game_queries.insert(id.clone(), query);
+
+        let statuses = |players| {
+            let status = ServerStatus {
+                players,
+                max_players: 10,
+                motd: String::new(),
+            };
+            vec![(0, Ok(status))]
+        };
+        let remote = |players| {
+            let data = Data::Players {
+                application: id.clone(),
+                statuses: statuses(players),
+            };
+            RemoteData::new(data, QUERY_ORIGIN)
+        };
+
+        app.handle_incoming_remote(remote(0));
+        assert!(app.plan.is_none());
+        // Empty since long enough
+        let long_ago = chrono::Utc::now() - chrono::Duration::minutes(31);
+        let mut occupancy = Occupancy::default();
+        occupancy.observe(statuses(0), long_ago);
+        app.occupancy.insert(id.clone(), occupancy);
+        app.handle_incoming_remote(remote(0));
+        assert!(matches!(app.plan.take().unwrap().kind, PlanKind::Crunch(_)));
+        app.handle_incoming_remote(remote(0));
+        assert!(app.plan.is_none());
+
+        // Players reset the count
+        app.handle_incoming_remote(remote(2));
+        assert_eq!(app.occupancy[&id].players(), 2);
+        assert_eq!(app.occupancy[&id].empty_for(chrono::Utc::now()), None);
+        assert!(app.plan.is_none());
+    }
+
+    #[test]
+    fn handle_incoming_rcon() {
+        const ORIGIN: &str = "game";
+        let mut app = App::default();
+        for i in 0..=super::RCON_OUTPUT_LINES {
+            let output = Data::Rcon(format!("> say {}", i));
+            app.handle_incoming_remote(RemoteData::new(output, ORIGIN));
+        }
+        let lines = &app.rcon_output[ORIGIN];
+        assert_eq!(lines.len(), super::RCON_OUTPUT_LINES);
+        assert_eq!(lines[0], "> say 1");
+        // Console output is no error and leaves the running workflow alone
+        assert!(app.error_log.is_empty());
+    }
+
+    #[test]
+    fn handle_incoming_metrics() {
+        const ORIGIN: &str = "unused";
+        let mut app = App::default();
+        let mut server = empty_server();
+        server.name = ORIGIN.to_string();
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let applications = generate_application_list(&servers, &Default::default(), &app.app_label);
+        let id = applications[0].id().to_string();
+        app.handle_incoming_remote(RemoteData::new(Data::Application(applications), ORIGIN));
+        app.idle_rules.insert(id.clone(), IdleRule::default());
+
+        let now = chrono::Utc::now();
+        let metrics = |cpu: f64| ServerMetrics {
+            server: 0,
+            step: 60,
+            samples: (0..=60)
+                .map(|minute| Sample {
+                    at: now - chrono::Duration::minutes(60 - minute),
+                    cpu,
+                    network: 100.0,
+                })
+                .collect(),
+        };
+        let remote = |cpu| {
+            let data = Data::Metrics {
+                application: id.clone(),
+                metrics: Ok(vec![metrics(cpu)]),
+            };
+            RemoteData::new(data, "metrics")
+        };
+
+        app.handle_incoming_remote(remote(50.0));
+        assert!(app.plan.is_none());
+        app.handle_incoming_remote(remote(1.0));
+        assert!(matches!(app.plan.take().unwrap().kind, PlanKind::Crunch(_)));
+        // Only proposed once
+        app.handle_incoming_remote(remote(1.0));
+        assert!(app.plan.is_none());
+
+        app.handle_incoming_remote(remote(50.0));
+        app.idle_rules.get_mut(&id).unwrap().automatic = true;
+        app.handle_incoming_remote(remote(1.0));
+        assert_eq!(app.pending_plans.len(), 1);
+        assert_eq!(app.pending_plans[0].origin, ORIGIN);
+    }
+
+    #[test]
+    fn handle_incoming_workflow() {
+        const ORIGIN: &str = "crunching";
+        let mut app = App::default();
+        app.set_loading(ORIGIN);
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![empty_snapshot()],
+        };
+        let applications = generate_application_list(&Default::default(), &images, &app.app_label);
+        let transition = Transition::crunched(&applications[0], chrono::Utc::now());
+        app.pending_transitions
+            .insert(ORIGIN.to_string(), transition);
+
+        let step = WorkflowUpdate::new(Step::AwaitOff, 1);
+        app.handle_incoming_remote(RemoteData::new(Data::Workflow(step), ORIGIN));
+        assert_eq!(app.workflows[ORIGIN].step, Step::AwaitOff);
+        assert!(app.remote_loading.contains(ORIGIN));
+
+        assert_eq!(app.journal.entries().len(), 1);
+
+        let done = WorkflowUpdate::new(Step::Done, 1);
+        app.handle_incoming_remote(RemoteData::new(Data::Workflow(done), ORIGIN));
+        assert!(app.workflows.is_empty());
+        assert!(app.journal.is_empty());
+        assert!(!app.remote_loading.contains(ORIGIN));
+        assert!(app.refresh_requested);
+        assert!(app.pending_transitions.is_empty());
+        assert!(!app.savings.is_empty());
+    }
+
+    #[test]
+    fn handle_incoming_failed_action() {
+        const ORIGIN: &str = "failing";
+        let mut app = App::default();
+        app.set_loading(ORIGIN);
+
+        let failed = Action {
+            command: "shutdown_server".to_string(),
+            status: action::Status::Error,
+            ..Default::default()
+        };
+        app.handle_incoming_remote(RemoteData::new(Data::Action(failed), ORIGIN));
+        assert_eq!(app.error_log[0].error, "shutdown_server failed");
+        assert!(!app.remote_loading.contains(ORIGIN));
+    }
+
+    #[test]
+    fn resume_workflows_unconfigured() {
+        const ORIGIN: &str = "interrupted";
+        let mut app = App::default();
+        app.journal
+            .record(ORIGIN, &WorkflowUpdate::new(Step::AwaitSnapshot, 1));
+
+        app.resume_workflows(&egui::Context::default());
+        assert!(app.workflows.is_empty());
+        assert!(!app.remote_loading.contains(ORIGIN));
+        assert!(!app.error_log.is_empty());
+        assert!(!app.journal.is_empty());
+    }
 }
@@ -0,0 +1,228 @@
+use crate::utils::{Application, Data, RemoteData, WakeMode, WakeProxy};
+use hcloud::models::server;
+use std::{sync::mpsc::Sender, time::Duration};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    sync::watch,
+    task::JoinHandle,
+    time::Instant,
+};
+
+/// Origin of wake-ups and errors sent by the proxies
+pub const PROXY_ORIGIN: &str = "proxy";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Delay between two attempts to reach a server that runs, but doesn't accept connections yet
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Running proxy of an application, stopped when dropped
+pub struct ProxyHandle {
+    pub config: WakeProxy,
+    target: watch::Sender<Option<String>>,
+    task: JoinHandle<()>,
+}
+impl ProxyHandle {
+    pub fn start(
+        application: String,
+        config: WakeProxy,
+        target: Option<String>,
+        tx: Sender<RemoteData>,
+        ctx: egui::Context,
+    ) -> Self {
+        let (sender, receiver) = watch::channel(target);
+        let task = tokio::spawn(listen(application, config.clone(), receiver, tx, ctx));
+        Self {
+            config,
+            target: sender,
+            task,
+        }
+    }
+
+    /// Address of the running server to forward to, `None` while it is crunched
+    pub fn set_target(&self, target: Option<String>) {
+        if *self.target.borrow() != target {
+            self.target.send_replace(target);
+        }
+    }
+}
+impl Drop for ProxyHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Address of the first running server of an application, where clients are forwarded to
+pub fn target(application: &Application, port: u16) -> Option<String> {
+    application
+        .servers
+        .iter()
+        .flatten()
+        .filter(|server| server.status == server::Status::Running)
+        .find_map(|server| Some(format!("{}:{}", server.public_net.ipv4.as_ref()?.ip, port)))
+}
+
+async fn listen(
+    application: String,
+    config: WakeProxy,
+    target: watch::Receiver<Option<String>>,
+    tx: Sender<RemoteData>,
+    ctx: egui::Context,
+) {
+    let listener = match TcpListener::bind(&config.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            let error = format!("Failed to listen on {}: {}", config.listen, e);
+            let _ = tx.send(RemoteData::new(Data::Error(error), PROXY_ORIGIN));
+            ctx.request_repaint();
+            return;
+        }
+    };
+    while let Ok((client, _)) = listener.accept().await {
+        let (application, config) = (application.clone(), config.clone());
+        let (target, tx, ctx) = (target.clone(), tx.clone(), ctx.clone());
+        tokio::spawn(serve(client, application, config, target, tx, ctx));
+    }
+}
+
+/// Forwards a client to the server, asking the app to restore it first if necessary
+async fn serve(
+    mut client: TcpStream,
+    application: String,
+    config: WakeProxy,
+    mut target: watch::Receiver<Option<String>>,
+    tx: Sender<RemoteData>,
+    ctx: egui::Context,
+) {
+    let deadline = Instant::now() + Duration::from_secs(config.hold_secs);
+    let mut woken = false;
+    loop {
+        let address = target.borrow().clone();
+        if let Some(address) = address {
+            let connected = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address));
+            if let Ok(Ok(mut server)) = connected.await {
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                return;
+            }
+        }
+        if !woken {
+            woken = true;
+            let wake = RemoteData::new(Data::Wake(application.clone()), PROXY_ORIGIN);
+            if tx.send(wake).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+        }
+        if config.mode == WakeMode::Reject || Instant::now() >= deadline {
+            break;
+        }
+        // Retried once the target changes, and every interval in case the server was starting
+        let wait = deadline.saturating_duration_since(Instant::now());
+        let changed = tokio::time::timeout(wait.min(RETRY_INTERVAL), target.changed());
+        // The proxy was stopped
+        if let Ok(Err(_)) = changed.await {
+            return;
+        }
+    }
+    let message = format!("{}\n", config.message);
+    let _ = client.write_all(message.as_bytes()).await;
+    let _ = client.shutdown().await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ProxyHandle, PROXY_ORIGIN};
+    use crate::utils::{Data, WakeMode, WakeProxy};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// Free local port for the proxy to listen on
+    async fn free_address() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    /// Server answering every read with its uppercase version
+    async fn upper_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = [0; 64];
+                    while let Ok(read) = stream.read(&mut buffer).await {
+                        if read == 0 {
+                            break;
+                        }
+                        let upper = buffer[..read].to_ascii_uppercase();
+                        stream.write_all(&upper).await.unwrap();
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    /// Retries connecting while the proxy binds its port
+    async fn connect(address: &str) -> TcpStream {
+        for _ in 0..50 {
+            if let Ok(stream) = TcpStream::connect(address).await {
+                return stream;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("proxy isn't listening on {}", address);
+    }
+
+    #[tokio::test]
+    async fn hold() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let config = WakeProxy {
+            listen: free_address().await,
+            ..Default::default()
+        };
+        let proxy = ProxyHandle::start("app".into(), config.clone(), None, tx, Default::default());
+
+        let mut client = connect(&config.listen).await;
+        client.write_all(b"hello").await.unwrap();
+        for _ in 0..50 {
+            if rx.try_recv().is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        // The wake-up went out, and the server comes up
+        proxy.set_target(Some(upper_server().await));
+        let mut response = [0; 5];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"HELLO");
+
+        // Later clients are forwarded right away
+        let mut client = connect(&config.listen).await;
+        client.write_all(b"again").await.unwrap();
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"AGAIN");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn reject() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let config = WakeProxy {
+            listen: free_address().await,
+            mode: WakeMode::Reject,
+            message: "Starting".to_string(),
+            ..Default::default()
+        };
+        let _proxy = ProxyHandle::start("app".into(), config.clone(), None, tx, Default::default());
+
+        let mut client = connect(&config.listen).await;
+        let mut message = String::new();
+        client.read_to_string(&mut message).await.unwrap();
+        assert_eq!(message, "Starting\n");
+        let wake = rx.try_recv().unwrap();
+        assert_eq!(wake.origin, PROXY_ORIGIN);
+        assert!(matches!(wake.data, Data::Wake(application) if application == "app"));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::utils::{Data, RemoteData, Schedules};
+use chrono::Utc;
+use std::{sync::mpsc::Sender, time::Duration};
+use tokio::sync::watch;
+
+/// Origin of the runs sent by the scheduler
+pub const SCHEDULER_ORIGIN: &str = "scheduler";
+/// Longest sleep between two checks, so clock changes and suspends are picked up
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Sends every scheduled run as it becomes due. Schedules are watched for changes and their
+/// calendar files read again when they change, the task ends once the app drops the sender.
+pub async fn run(
+    mut schedules: watch::Receiver<Schedules>,
+    tx: Sender<RemoteData>,
+    ctx: egui::Context,
+) {
+    let mut current = schedules.borrow().clone();
+    let mut checked = Utc::now();
+    loop {
+        current = match reload(current, &tx, &ctx).await {
+            Some(reloaded) => reloaded,
+            None => return,
+        };
+        let next = current.next_after(checked);
+        let wait = next
+            .and_then(|run| (run.at - Utc::now()).to_std().ok())
+            .map_or(MAX_WAIT, |wait| wait.min(MAX_WAIT));
+        // Woken early by changed schedules
+        match tokio::time::timeout(wait, schedules.changed()).await {
+            Ok(Ok(())) => current = schedules.borrow().clone(),
+            Ok(Err(_)) => return,
+            Err(_) => (),
+        }
+
+        current = match reload(current, &tx, &ctx).await {
+            Some(reloaded) => reloaded,
+            None => return,
+        };
+        let now = Utc::now();
+        let runs = current.runs_between(checked, now);
+        for run in runs {
+            let data = RemoteData::new(Data::Scheduled(run), SCHEDULER_ORIGIN);
+            if tx.send(data).is_err() {
+                return;
+            }
+        }
+        ctx.request_repaint();
+        checked = now;
+    }
+}
+
+/// Reads the calendar files that changed off the async threads, and hands them to the app.
+/// `None` once the app is gone.
+async fn reload(
+    mut schedules: Schedules,
+    tx: &Sender<RemoteData>,
+    ctx: &egui::Context,
+) -> Option<Schedules> {
+    let reloaded = tokio::task::spawn_blocking(move || {
+        let changed = schedules.reload_calendars();
+        (schedules, changed)
+    });
+    let (schedules, changed) = reloaded.await.ok()?;
+    if changed {
+        let data = RemoteData::new(Data::Calendars(schedules.calendars()), SCHEDULER_ORIGIN);
+        tx.send(data).ok()?;
+        ctx.request_repaint();
+    }
+    Some(schedules)
+}
@@ -0,0 +1,115 @@
+use hcloud::models::{action::Status, Action};
+use std::collections::HashMap;
+
+/// Keeps track of in-flight hcloud actions, grouped by the origin that started them
+#[derive(Debug, Default, Clone)]
+pub struct ActionTracker {
+    pending: HashMap<String, Vec<Action>>,
+}
+impl ActionTracker {
+    /// Records the latest state of an action. Finished actions are no longer tracked,
+    /// failed ones are handed back to the caller.
+    pub fn update(&mut self, origin: &str, action: Action) -> Option<Action> {
+        let actions = self.pending.entry(origin.to_string()).or_default();
+        actions.retain(|pending| pending.id != action.id);
+
+        let failed = match action.status {
+            Status::Running => {
+                actions.push(action);
+                None
+            }
+            Status::Success => None,
+            Status::Error => Some(action),
+        };
+
+        if actions.is_empty() {
+            self.pending.remove(origin);
+        }
+        failed
+    }
+
+    /// Stops tracking all actions of an origin, e.g. when a workflow was aborted
+    pub fn clear(&mut self, origin: &str) {
+        self.pending.remove(origin);
+    }
+
+    pub fn pending(&self, origin: &str) -> &[Action] {
+        self.pending
+            .get(origin)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Average progress of all pending actions, between 0 and 1
+    pub fn progress(&self) -> Option<f32> {
+        if self.is_empty() {
+            return None;
+        }
+        let total: f32 = self.pending.values().flatten().map(|a| a.progress).sum();
+        Some(total / self.len() as f32 / 100.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ActionTracker;
+    use hcloud::models::{action::Status, Action};
+
+    const ORIGIN: &str = "origin";
+
+    fn action(id: i32, progress: f32, status: Status) -> Action {
+        Action {
+            id,
+            progress,
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update() {
+        let mut tracker = ActionTracker::default();
+        assert!(tracker
+            .update(ORIGIN, action(1, 0.0, Status::Running))
+            .is_none());
+        assert!(tracker
+            .update(ORIGIN, action(1, 50.0, Status::Running))
+            .is_none());
+        assert_eq!(tracker.pending(ORIGIN).len(), 1);
+        assert_eq!(tracker.pending(ORIGIN)[0].progress, 50.0);
+
+        assert!(tracker
+            .update(ORIGIN, action(1, 100.0, Status::Success))
+            .is_none());
+        assert!(tracker.is_empty());
+        assert!(tracker.pending(ORIGIN).is_empty());
+    }
+
+    #[test]
+    fn update_failed() {
+        let mut tracker = ActionTracker::default();
+        tracker.update(ORIGIN, action(1, 0.0, Status::Running));
+        let failed = tracker.update(ORIGIN, action(1, 10.0, Status::Error));
+        assert_eq!(failed.map(|a| a.id), Some(1));
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn progress() {
+        let mut tracker = ActionTracker::default();
+        assert_eq!(tracker.progress(), None);
+
+        tracker.update(ORIGIN, action(1, 20.0, Status::Running));
+        tracker.update("other", action(2, 80.0, Status::Running));
+        assert_eq!(tracker.len(), 2);
+        assert_eq!(tracker.progress(), Some(0.5));
+    }
+}
@@ -1,43 +1,92 @@
+use chrono::{DateTime, FixedOffset};
 use hcloud::models::{image::Type, Image, ListImagesResponse, ListServersResponse, Server};
-use std::sync::atomic;
-
-static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Label key grouping servers and snapshots into applications, unless configured otherwise
+pub const DEFAULT_APP_LABEL: &str = "cruncher.app";
+
+/// Labels attached to snapshots taken by a crunch, used to restore the server later on
+pub const LABEL_NAME: &str = "cruncher.name";
+pub const LABEL_SERVER_TYPE: &str = "cruncher.server_type";
+pub const LABEL_LOCATION: &str = "cruncher.location";
+/// Longest label value accepted by hcloud
+const MAX_LABEL_VALUE: usize = 63;
+
+/// Turns a name into a valid label value: at most 63 alphanumerics, `-`, `_` and `.`, starting
+/// and ending with an alphanumeric. Anything else, e.g. spaces, becomes `-`.
+pub fn label_value(name: &str) -> String {
+    let value: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "-_.".contains(c) {
+            true => c,
+            false => '-',
+        })
+        .collect();
+    let value = value.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    // Only ASCII is left, so every index is a char boundary
+    let value = &value[..value.len().min(MAX_LABEL_VALUE)];
+    value
+        .trim_end_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_string()
+}
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Application {
-    id: usize,
+    /// Derived from labels or hcloud IDs, so it stays the same across refreshes
+    id: String,
     pub name: Option<String>,
-    pub status: Option<String>,
     pub images: Option<Vec<Image>>,
     pub servers: Option<Vec<Server>>,
+    /// Automatic backups of the servers, kept apart from snapshots as they are deleted along
+    /// with their server
+    #[serde(default)]
+    pub backups: Option<Vec<Image>>,
 }
 impl Application {
-    fn new() -> Self {
+    pub(super) fn new() -> Self {
         Self {
-            id: get_id(),
+            id: String::new(),
             name: None,
-            status: None,
             images: None,
             servers: None,
+            backups: None,
         }
     }
 
     fn with_server(mut self, server: &Server) -> Self {
+        self.id = format!("server:{}", server.id);
         self.name = Some(server.name.clone());
-        self.status = Some(format!("{:?}", server.status));
         let servers = vec![server.clone()];
         self.servers = Some(servers);
         self
     }
 
-    fn with_image(mut self, image: &Image) -> Self {
-        let Image { name, status, .. } = image;
+    pub(super) fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
 
-        match name {
-            None => self.name = Some(format!("unnamed {}", self.id)),
-            Some(name) => self.name = Some(name.clone()),
-        }
-        self.status = Some(format!("{:?}", status));
+    pub(super) fn with_id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub(super) fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    fn with_image(mut self, image: &Image) -> Self {
+        // Snapshots of crunched servers keep the identity of the server they were taken from
+        self.id = match &image.created_from {
+            Some(source) => format!("server:{}", source.id),
+            None => format!("image:{}", image.id),
+        };
+        self.name = Some(image_name(image));
 
         let images = vec![image.clone()];
         self.images = Some(images);
@@ -45,12 +94,90 @@ impl Application {
         self
     }
 
+    /// Whether crunches and restores of this application label their resources with the given
+    /// value, which is the name made into a valid label value
+    fn is_labeled(&self, label: &str) -> bool {
+        self.name.as_deref().map(label_value) == Some(label_value(label))
+    }
+
+    /// Fallback for unlabeled snapshots: related if taken from one of the servers, from a
+    /// server named like this application, or restored into this application
     fn is_image_related(&self, image: &Image) -> bool {
-        let Image { created_from, .. } = image;
-        matches!((created_from.clone(), self.name.clone()), (Some(source), Some(name)) if source.name == name)
+        // Restores label their servers after the application the snapshot made on its own
+        let restored = self.is_labeled(&image_name(image));
+        let source = match &image.created_from {
+            Some(source) => source,
+            None => return restored,
+        };
+        let from_server = self
+            .servers
+            .iter()
+            .flatten()
+            .any(|server| server.id == source.id);
+        from_server || restored || self.name.as_ref() == Some(&source.name)
+    }
+
+    /// Names a labeled application like the snapshot its label was made from, e.g.
+    /// "unnamed 42" rather than "unnamed-42"
+    fn adopt_name(&mut self, image: &Image) {
+        let name = image_name(image);
+        if self.name.as_deref() == Some(label_value(&name).as_str()) {
+            self.name = Some(name);
+        }
     }
 
-    fn add_image(&mut self, image: &Image) {
+    /// The most recently created snapshot of this application
+    pub fn latest_snapshot(&self) -> Option<&Image> {
+        self.images
+            .iter()
+            .flatten()
+            .filter(|image| image.r#type == Type::Snapshot)
+            .max_by_key(|image| parse_created(&image.created))
+    }
+
+    /// The most recent automatic backup of any of the servers
+    pub fn latest_backup(&self) -> Option<&Image> {
+        self.backups
+            .iter()
+            .flatten()
+            .max_by_key(|image| parse_created(&image.created))
+    }
+
+    /// Builds a restore from the newest snapshot, taking the server specification from the
+    /// snapshot labels, or from the last known server of this application as fallback.
+    pub fn restore_spec(&self, last_known: Option<&ServerSpec>) -> Option<RestoreSpec> {
+        let image = self.latest_snapshot()?;
+        let server = ServerSpec::from_labels(&image.labels).or_else(|| last_known.cloned())?;
+        Some(RestoreSpec {
+            image_id: image.id,
+            server,
+        })
+    }
+
+    /// Server and image IDs making up this application
+    pub fn resources(&self) -> Vec<Resource> {
+        let servers = self
+            .servers
+            .iter()
+            .flatten()
+            .map(|s| Resource::Server(s.id));
+        let images = self.images.iter().flatten().map(|i| Resource::Image(i.id));
+        servers.chain(images).collect()
+    }
+
+    pub(super) fn add_server(&mut self, server: &Server) {
+        self.servers
+            .get_or_insert_with(Vec::new)
+            .push(server.clone());
+    }
+
+    pub(super) fn add_backup(&mut self, image: &Image) {
+        self.backups
+            .get_or_insert_with(Vec::new)
+            .push(image.clone());
+    }
+
+    pub(super) fn add_image(&mut self, image: &Image) {
         match &self.images {
             None => {
                 let vec = vec![image.clone()];
@@ -63,52 +190,131 @@ impl Application {
     }
 }
 
-fn bump_counter() {
-    COUNTER.fetch_add(1, atomic::Ordering::SeqCst);
+/// A single hcloud resource belonging to an application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Resource {
+    Server(i32),
+    Image(i32),
+}
+
+/// The parts of a server needed to provision it again
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServerSpec {
+    pub name: String,
+    pub server_type: String,
+    pub location: String,
+}
+impl From<&Server> for ServerSpec {
+    fn from(server: &Server) -> Self {
+        Self {
+            name: server.name.clone(),
+            server_type: server.server_type.name.clone(),
+            location: server.datacenter.location.name.clone(),
+        }
+    }
 }
+impl ServerSpec {
+    /// Labels to attach to a snapshot of this server, taken for the given application
+    pub fn labels(&self, app_label: &str, application: &str) -> HashMap<String, String> {
+        HashMap::from([
+            (app_label.to_string(), label_value(application)),
+            (LABEL_NAME.to_string(), label_value(&self.name)),
+            (LABEL_SERVER_TYPE.to_string(), self.server_type.clone()),
+            (LABEL_LOCATION.to_string(), self.location.clone()),
+        ])
+    }
 
-fn get_counter() -> usize {
-    COUNTER.load(atomic::Ordering::SeqCst)
+    fn from_labels(labels: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            name: labels.get(LABEL_NAME)?.clone(),
+            server_type: labels.get(LABEL_SERVER_TYPE)?.clone(),
+            location: labels.get(LABEL_LOCATION)?.clone(),
+        })
+    }
 }
 
-fn get_id() -> usize {
-    let id = get_counter();
-    bump_counter();
-    id
+/// Everything needed to create a server from a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RestoreSpec {
+    pub image_id: i32,
+    pub server: ServerSpec,
 }
 
+/// Name of an application made of this snapshot alone
+fn image_name(image: &Image) -> String {
+    match (&image.name, &image.created_from) {
+        (Some(name), _) => name.clone(),
+        (None, Some(source)) => source.name.clone(),
+        (None, None) => format!("unnamed {}", image.id),
+    }
+}
+
+pub(super) fn parse_created(created: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(created).ok()
+}
+
+/// Groups servers and snapshots into applications by the value of their `app_label`.
+/// Unlabeled servers form an application of their own name, unlabeled snapshots join the
+/// application of the server they were taken from. Backups join the server they are bound to.
 pub fn generate_application_list(
     servers: &ListServersResponse,
     images: &ListImagesResponse,
+    app_label: &str,
 ) -> Vec<Application> {
-    let mut vec = Vec::new();
+    let mut vec: Vec<Application> = Vec::new();
 
     for server in &servers.servers {
-        let app = Application::new().with_server(server);
-        vec.push(app);
+        let name = server.labels.get(app_label).unwrap_or(&server.name);
+        match vec.iter_mut().find(|app| app.is_labeled(name)) {
+            Some(app) => app.add_server(server),
+            None => {
+                let app = Application::new().with_server(server).named(name);
+                vec.push(match server.labels.get(app_label) {
+                    Some(label) => app.with_id(&format!("label:{}", label)),
+                    None => app,
+                });
+            }
+        }
     }
 
     for image in &images.images {
+        if image.r#type == Type::Backup {
+            let server = image.bound_to;
+            let bound = vec
+                .iter_mut()
+                .find(|app| app.servers.iter().flatten().any(|s| Some(s.id) == server));
+            // Backups are deleted along with their server, so unbound ones are on their way out
+            if let Some(app) = bound {
+                app.add_backup(image);
+            }
+            continue;
+        }
         // Skip if this is anything but a snapshot
         if image.r#type != Type::Snapshot {
             continue;
         }
 
-        let mut assinged = false;
-        for app in &mut vec {
-            if app.is_image_related(image) {
+        let label = image.labels.get(app_label);
+        let related = match label {
+            Some(name) => vec.iter_mut().find(|app| app.is_labeled(name)),
+            None => vec.iter_mut().find(|app| app.is_image_related(image)),
+        };
+        match (related, label) {
+            (Some(app), _) => {
                 app.add_image(image);
-                assinged = true;
-                break;
+                app.adopt_name(image);
             }
+            (None, Some(name)) => {
+                let app = Application::new().with_image(image);
+                // Keep the name the label was made from, e.g. "unnamed 42" for "unnamed-42"
+                let app = match app.is_labeled(name) {
+                    true => app,
+                    false => app.named(name),
+                };
+                vec.push(app.with_id(&format!("label:{}", name)))
+            }
+            (None, None) => vec.push(Application::new().with_image(image)),
         }
-        // Continue if we assinged in inner loop
-        if assinged {
-            continue;
-        }
-
-        let app = Application::new().with_image(image);
-        vec.push(app);
     }
 
     vec
@@ -116,21 +322,23 @@ pub fn generate_application_list(
 
 #[cfg(test)]
 mod tests {
-    use super::Application;
+    use super::{label_value, Application, DEFAULT_APP_LABEL};
     use crate::utils::{empty_server, empty_snapshot};
     use hcloud::models::{CreatedFrom, Image, ListImagesResponse, ListServersResponse, Server};
+    use std::collections::HashMap;
 
     mod application {
         use super::{empty_server, empty_snapshot, Application};
-        use hcloud::models::CreatedFrom;
+        use crate::utils::application::{RestoreSpec, ServerSpec, DEFAULT_APP_LABEL};
+        use hcloud::models::{CreatedFrom, Image};
 
         impl PartialEq for Application {
             fn eq(&self, other: &Self) -> bool {
                 self.id == other.id
                     && self.name == other.name
-                    && self.status == other.status
                     && self.images == other.images
                     && self.servers == other.servers
+                    && self.backups == other.backups
             }
         }
 
@@ -138,11 +346,11 @@ mod tests {
         fn is_image_related() {
             let unrelated_image = empty_snapshot();
             let application = Application {
-                id: 0,
+                id: String::new(),
                 name: Some("unique".to_string()),
-                status: None,
                 images: None,
                 servers: None,
+                backups: None,
             };
 
             let mut related_image = unrelated_image.clone();
@@ -162,11 +370,11 @@ mod tests {
 
             let application = Application::new().with_image(&image);
             let control_application = Application {
-                id: application.id,
+                id: "image:0".to_string(),
                 name: Some("unique".to_string()),
-                status: Some("Available".to_string()),
                 images: Some(vec![image]),
                 servers: None,
+                backups: None,
             };
 
             assert_eq!(application, control_application);
@@ -178,23 +386,45 @@ mod tests {
 
             let application = Application::new().with_image(&image);
             let control_application = Application {
-                id: application.id,
-                name: Some(format!("unnamed {}", application.id)),
-                status: Some("Available".to_string()),
+                id: "image:0".to_string(),
+                name: Some("unnamed 0".to_string()),
                 images: Some(vec![image.clone()]),
                 servers: None,
+                backups: None,
             };
 
             assert_eq!(application, control_application);
         }
 
+        #[test]
+        fn with_image_created_from() {
+            let mut image = empty_snapshot();
+            image.created_from = Some(Box::new(CreatedFrom {
+                id: 0,
+                name: "crunched".to_string(),
+            }));
+
+            let application = Application::new().with_image(&image);
+            assert_eq!(application.name, Some("crunched".to_string()));
+            assert_eq!(application.id(), "server:0");
+            assert!(application.is_image_related(&image));
+        }
+
         #[test]
         fn with_image_multiple_unamed() {
             let image = empty_snapshot();
+            let another_image = Image {
+                id: 1,
+                ..image.clone()
+            };
             let application = Application::new().with_image(&image);
-            let another_application = Application::new().with_image(&image);
+            let another_application = Application::new().with_image(&another_image);
             assert_ne!(application.id, another_application.id);
             assert_ne!(application.name, another_application.name);
+
+            // Refreshing yields the same identity again
+            let refreshed = Application::new().with_image(&image);
+            assert_eq!(application, refreshed);
         }
 
         #[test]
@@ -203,13 +433,71 @@ mod tests {
             server.name = "unique".to_string();
             let application = Application::new().with_server(&server);
             let mut control_application = Application::new();
-            control_application.id = application.id;
+            control_application.id = "server:0".to_string();
             control_application.name = Some("unique".to_string());
-            control_application.status = Some("Running".to_string());
             control_application.servers = Some(vec![server.clone()]);
             assert_eq!(application, control_application);
         }
 
+        #[test]
+        fn latest_snapshot() {
+            let older = Image {
+                id: 1,
+                created: "2022-10-01T10:00:00+00:00".to_string(),
+                ..empty_snapshot()
+            };
+            let newer = Image {
+                id: 2,
+                created: "2022-10-02T08:00:00+02:00".to_string(),
+                ..empty_snapshot()
+            };
+            let mut application = Application::new().with_image(&newer);
+            application.add_image(&older);
+
+            assert_eq!(application.latest_snapshot().unwrap().id, 2);
+        }
+
+        #[test]
+        fn restore_spec() {
+            let spec = ServerSpec {
+                name: "restored".to_string(),
+                server_type: "cx11".to_string(),
+                location: "nbg1".to_string(),
+            };
+            let unlabeled = Image {
+                id: 1,
+                ..empty_snapshot()
+            };
+            let labeled = Image {
+                id: 2,
+                labels: spec.labels(DEFAULT_APP_LABEL, "restored"),
+                ..empty_snapshot()
+            };
+            let last_known = ServerSpec {
+                name: "last known".to_string(),
+                ..spec.clone()
+            };
+
+            let application = Application::new().with_image(&unlabeled);
+            assert_eq!(application.restore_spec(None), None);
+            assert_eq!(
+                application.restore_spec(Some(&last_known)),
+                Some(RestoreSpec {
+                    image_id: 1,
+                    server: last_known.clone(),
+                })
+            );
+
+            let application = Application::new().with_image(&labeled);
+            assert_eq!(
+                application.restore_spec(Some(&last_known)),
+                Some(RestoreSpec {
+                    image_id: 2,
+                    server: spec,
+                })
+            );
+        }
+
         #[test]
         fn add_image() {
             let image = empty_snapshot();
@@ -262,12 +550,231 @@ mod tests {
             meta: None,
             images: vec![image1, image2, invalid_image],
         };
-        let applications = super::generate_application_list(&server_list, &image_list);
+        let applications =
+            super::generate_application_list(&server_list, &image_list, DEFAULT_APP_LABEL);
         assert_eq!(applications.len(), 4);
-        let first = applications.get(0).unwrap();
+        let first = applications.first().unwrap();
         let last = applications.get(3).unwrap();
         assert_eq!(first.name.clone().unwrap(), "Amogus".to_string());
         assert_eq!(first.images.clone().unwrap().len(), 1);
         assert_eq!(last.name.clone().unwrap(), "In Our Group".to_string());
     }
+
+    #[test]
+    fn generate_application_list_crunched() {
+        let base_image = empty_snapshot();
+        let snapshot = Image {
+            created_from: Some(Box::new(CreatedFrom {
+                id: 0,
+                name: "Crunched".to_string(),
+            })),
+            ..base_image
+        };
+        let server_list = ListServersResponse {
+            meta: None,
+            servers: vec![],
+        };
+        let image_list = ListImagesResponse {
+            meta: None,
+            images: vec![snapshot.clone(), snapshot],
+        };
+        let applications =
+            super::generate_application_list(&server_list, &image_list, DEFAULT_APP_LABEL);
+        assert_eq!(applications.len(), 1);
+        let crunched = applications.first().unwrap();
+        assert_eq!(crunched.name.clone().unwrap(), "Crunched".to_string());
+        assert_eq!(crunched.images.clone().unwrap().len(), 2);
+        assert!(crunched.servers.is_none());
+    }
+
+    #[test]
+    fn generate_application_list_labeled() {
+        let labels = HashMap::from([(DEFAULT_APP_LABEL.to_string(), "Game".to_string())]);
+        let web = Server {
+            id: 1,
+            name: "web".to_string(),
+            labels: labels.clone(),
+            ..empty_server()
+        };
+        let db = Server {
+            id: 2,
+            name: "db".to_string(),
+            labels: labels.clone(),
+            status: hcloud::models::server::Status::Off,
+            ..empty_server()
+        };
+        let labeled = Image {
+            id: 3,
+            labels,
+            ..empty_snapshot()
+        };
+        // Taken from the database before it was renamed, so only its ID still matches
+        let unlabeled = Image {
+            id: 4,
+            created_from: Some(Box::new(CreatedFrom {
+                id: 2,
+                name: "old db".to_string(),
+            })),
+            ..empty_snapshot()
+        };
+        let server_list = ListServersResponse {
+            meta: None,
+            servers: vec![web, db],
+        };
+        let image_list = ListImagesResponse {
+            meta: None,
+            images: vec![labeled, unlabeled],
+        };
+
+        let applications =
+            super::generate_application_list(&server_list, &image_list, DEFAULT_APP_LABEL);
+        assert_eq!(applications.len(), 1);
+        let game = applications.first().unwrap();
+        assert_eq!(game.name.clone().unwrap(), "Game".to_string());
+        assert_eq!(game.id(), "label:Game");
+        assert_eq!(game.servers.clone().unwrap().len(), 2);
+        assert_eq!(game.images.clone().unwrap().len(), 2);
+
+        // With a different label key, servers fall back to their names
+        let applications = super::generate_application_list(&server_list, &image_list, "other");
+        assert_eq!(applications.len(), 3);
+    }
+
+    #[test]
+    fn generate_application_list_labeled_crunched() {
+        let labels = HashMap::from([(DEFAULT_APP_LABEL.to_string(), "Game".to_string())]);
+        let snapshot = Image {
+            labels,
+            ..empty_snapshot()
+        };
+        let server_list = ListServersResponse {
+            meta: None,
+            servers: vec![],
+        };
+        let image_list = ListImagesResponse {
+            meta: None,
+            images: vec![snapshot.clone(), snapshot],
+        };
+
+        let applications =
+            super::generate_application_list(&server_list, &image_list, DEFAULT_APP_LABEL);
+        assert_eq!(applications.len(), 1);
+        assert_eq!(applications[0].name.clone().unwrap(), "Game".to_string());
+        assert_eq!(applications[0].images.clone().unwrap().len(), 2);
+    }
+
+    /// Crunches and restores label resources with the name made into a label value
+    #[test]
+    fn generate_application_list_label_values() {
+        let unnamed = Image {
+            id: 42,
+            ..empty_snapshot()
+        };
+        let restored = Server {
+            id: 7,
+            name: "unnamed-42".to_string(),
+            labels: HashMap::from([(DEFAULT_APP_LABEL.to_string(), label_value("unnamed 42"))]),
+            ..empty_server()
+        };
+        let server_list = ListServersResponse {
+            meta: None,
+            servers: vec![restored.clone()],
+        };
+        let image_list = ListImagesResponse {
+            meta: None,
+            images: vec![unnamed],
+        };
+        let applications =
+            super::generate_application_list(&server_list, &image_list, DEFAULT_APP_LABEL);
+        assert_eq!(applications.len(), 1);
+        assert_eq!(applications[0].name.as_deref(), Some("unnamed 42"));
+        assert_eq!(applications[0].servers.clone().unwrap().len(), 1);
+        assert_eq!(applications[0].images.clone().unwrap().len(), 1);
+
+        let crunched = Image {
+            id: 43,
+            name: Some("My Game".to_string()),
+            labels: HashMap::from([(DEFAULT_APP_LABEL.to_string(), label_value("My Game"))]),
+            ..empty_snapshot()
+        };
+        let image_list = ListImagesResponse {
+            meta: None,
+            images: vec![crunched],
+        };
+        let empty = ListServersResponse {
+            meta: None,
+            servers: vec![],
+        };
+        let applications = super::generate_application_list(&empty, &image_list, DEFAULT_APP_LABEL);
+        assert_eq!(applications.len(), 1);
+        assert_eq!(applications[0].name.as_deref(), Some("My Game"));
+        assert_eq!(applications[0].id(), "label:My-Game");
+
+        let restored = Server {
+            name: "My-Game".to_string(),
+            labels: HashMap::from([(DEFAULT_APP_LABEL.to_string(), "My-Game".to_string())]),
+            ..restored
+        };
+        let server_list = ListServersResponse {
+            meta: None,
+            servers: vec![restored],
+        };
+        let applications =
+            super::generate_application_list(&server_list, &image_list, DEFAULT_APP_LABEL);
+        assert_eq!(applications.len(), 1);
+        assert_eq!(applications[0].name.as_deref(), Some("My Game"));
+        assert_eq!(applications[0].id(), "label:My-Game");
+    }
+
+    #[test]
+    fn generate_application_list_backups() {
+        let server = Server {
+            id: 1,
+            name: "backed up".to_string(),
+            ..empty_server()
+        };
+        let older = Image {
+            id: 2,
+            r#type: hcloud::models::image::Type::Backup,
+            bound_to: Some(1),
+            created: "2022-10-01T10:00:00+00:00".to_string(),
+            ..empty_snapshot()
+        };
+        let newer = Image {
+            id: 3,
+            created: "2022-10-02T10:00:00+00:00".to_string(),
+            ..older.clone()
+        };
+        let unbound = Image {
+            id: 4,
+            bound_to: Some(5),
+            ..older.clone()
+        };
+        let server_list = ListServersResponse {
+            meta: None,
+            servers: vec![server],
+        };
+        let image_list = ListImagesResponse {
+            meta: None,
+            images: vec![older, newer, unbound],
+        };
+
+        let applications =
+            super::generate_application_list(&server_list, &image_list, DEFAULT_APP_LABEL);
+        assert_eq!(applications.len(), 1);
+        let application = applications.first().unwrap();
+        assert!(application.images.is_none());
+        assert_eq!(application.backups.clone().unwrap().len(), 2);
+        assert_eq!(application.latest_backup().unwrap().id, 3);
+    }
+
+    #[test]
+    fn label_values() {
+        assert_eq!(label_value("minecraft"), "minecraft");
+        assert_eq!(label_value("unnamed 42"), "unnamed-42");
+        assert_eq!(label_value(" (Jörg's server) "), "J-rg-s-server");
+        assert_eq!(label_value("__"), "");
+        let long = format!("{}-end", "a".repeat(62));
+        assert_eq!(label_value(&long), "a".repeat(62));
+    }
 }
@@ -0,0 +1,249 @@
+use super::{cost::CostBreakdown, savings::next_month, Application};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Share of a budget after which the status bar warns
+const WARN_RATIO: f64 = 0.8;
+/// How far ahead to look when deciding whether a budget is about to be exceeded
+const LOOKAHEAD_HOURS: f64 = 1.0;
+/// Longest time since the last observation that is still counted after the app was closed,
+/// its servers may have been crunched meanwhile
+const MAX_RESTART_GAP_SECS: i64 = 60 * 60;
+
+/// What to do when an application is about to exceed its budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OverBudget {
+    Warn,
+    /// Open a crunch plan for confirmation
+    Propose,
+    /// Crunch without asking
+    Crunch,
+}
+impl Default for OverBudget {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+impl OverBudget {
+    pub const ALL: [OverBudget; 3] = [OverBudget::Warn, OverBudget::Propose, OverBudget::Crunch];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OverBudget::Warn => "Only warn",
+            OverBudget::Propose => "Propose a crunch",
+            OverBudget::Crunch => "Crunch automatically",
+        }
+    }
+}
+
+/// Monthly net budgets in EUR
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Budgets {
+    /// By application ID
+    applications: HashMap<String, f64>,
+    pub project: Option<f64>,
+    pub over_budget: OverBudget,
+}
+impl Budgets {
+    pub fn get(&self, application: &str) -> Option<f64> {
+        self.applications.get(application).copied()
+    }
+
+    pub fn set(&mut self, application: &str, budget: Option<f64>) {
+        match budget {
+            Some(budget) => self.applications.insert(application.to_string(), budget),
+            None => self.applications.remove(application),
+        };
+    }
+}
+
+/// Month-to-date spend compared to a budget
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BudgetStatus {
+    pub budget: f64,
+    pub spent: f64,
+    /// Spend at the end of the month at the current rate
+    pub projected: f64,
+    hourly: f64,
+}
+impl BudgetStatus {
+    pub fn ratio(&self) -> f64 {
+        match self.budget > 0.0 {
+            true => self.spent / self.budget,
+            false => f64::INFINITY,
+        }
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.ratio() >= WARN_RATIO
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.spent >= self.budget
+    }
+
+    /// Whether the budget will be exceeded within the next hour
+    pub fn is_imminent(&self) -> bool {
+        self.spent + self.hourly * LOOKAHEAD_HOURS >= self.budget
+    }
+}
+
+/// Net spend of each application this month, accumulated from the resources seen at every
+/// refresh. Hetzner bills servers whether they are running or not, so every server counts.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Spend {
+    month: Option<(i32, u32)>,
+    /// Month-to-date spend by application ID
+    spent: HashMap<String, f64>,
+    /// Hourly rate at the last observation by application ID
+    hourly: HashMap<String, f64>,
+    last_observed: Option<DateTime<Utc>>,
+    /// Whether anything was observed since the app started
+    #[serde(skip)]
+    resumed: bool,
+}
+impl Spend {
+    /// Charges the time since the last observation at the rates seen back then, and takes the
+    /// rates of the given applications for the time to come
+    pub fn observe(&mut self, applications: &[Application], now: DateTime<Utc>) {
+        let month = (now.year(), now.month());
+        let month_start = Utc
+            .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+            .single();
+        if self.month != Some(month) {
+            self.month = Some(month);
+            self.spent.clear();
+        }
+
+        let since = self
+            .last_observed
+            .map(|last| month_start.map_or(last, |start| last.max(start)));
+        let max_gap = match self.resumed {
+            true => i64::MAX,
+            false => MAX_RESTART_GAP_SECS,
+        };
+        let seconds = since
+            .map(|since| (now - since).num_seconds().clamp(0, max_gap))
+            .unwrap_or_default();
+        let hours = seconds as f64 / 3600.0;
+        for (id, hourly) in &self.hourly {
+            *self.spent.entry(id.clone()).or_default() += hourly * hours;
+        }
+
+        self.hourly = applications
+            .iter()
+            .map(|application| (application.id().to_string(), hourly_rate(application)))
+            .collect();
+        self.last_observed = Some(now);
+        self.resumed = true;
+    }
+
+    pub fn status(&self, application: &str, budget: f64, now: DateTime<Utc>) -> BudgetStatus {
+        let spent = self.spent.get(application).copied().unwrap_or_default();
+        let hourly = self.hourly.get(application).copied().unwrap_or_default();
+        Self::project(budget, spent, hourly, now)
+    }
+
+    pub fn project_status(&self, budget: f64, now: DateTime<Utc>) -> BudgetStatus {
+        let spent = self.spent.values().sum();
+        let hourly = self.hourly.values().sum();
+        Self::project(budget, spent, hourly, now)
+    }
+
+    fn project(budget: f64, spent: f64, hourly: f64, now: DateTime<Utc>) -> BudgetStatus {
+        let remaining =
+            next_month(now).map_or(0, |next| (next - now).num_seconds()) as f64 / 3600.0;
+        BudgetStatus {
+            budget,
+            spent,
+            projected: spent + hourly * remaining,
+            hourly,
+        }
+    }
+}
+
+/// Net hourly cost of servers and snapshot storage
+fn hourly_rate(application: &Application) -> f64 {
+    let breakdown = CostBreakdown::of(application, 0.0);
+    let servers: f64 = breakdown.servers.values().map(|rate| rate.hourly.net).sum();
+    servers + breakdown.snapshots.hourly.net
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Budgets, Spend};
+    use crate::utils::{empty_server, prices, Application};
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn application() -> Application {
+        let mut server = empty_server();
+        server.datacenter.location.name = "fsn1".to_string();
+        // 7.30 € per month make 0.01 € per hour
+        server.server_type.prices = prices("fsn1", "7.30");
+        let mut application = Application::new().with_id("label:game");
+        application.add_server(&server);
+        application
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn observe() {
+        let mut spend = Spend::default();
+        let start = Utc.with_ymd_and_hms(2022, 10, 10, 0, 0, 0).unwrap();
+        spend.observe(&[application()], start);
+        spend.observe(&[application()], start + Duration::minutes(30));
+        spend.observe(&[application()], start + Duration::minutes(60));
+        let status = spend.status("label:game", 10.0, start + Duration::minutes(60));
+        assert!(close(status.spent, 0.01));
+
+        // Servers keep being billed between refreshes
+        spend.observe(&[application()], start + Duration::hours(5));
+        let status = spend.status("label:game", 10.0, start + Duration::hours(5));
+        assert!(close(status.spent, 0.05));
+
+        // Only the time across a restart is capped, the servers may have been crunched
+        let json = serde_json::to_string(&spend).unwrap();
+        let mut spend: Spend = serde_json::from_str(&json).unwrap();
+        spend.observe(&[], start + Duration::days(1));
+        let status = spend.status("label:game", 10.0, start + Duration::days(1));
+        assert!(close(status.spent, 0.06));
+        assert!(close(status.projected, 0.06));
+
+        // Spend starts over every month
+        spend.observe(&[], Utc.with_ymd_and_hms(2022, 11, 1, 0, 30, 0).unwrap());
+        assert!(close(spend.status("label:game", 1.0, start).spent, 0.0));
+    }
+
+    #[test]
+    fn status() {
+        let mut spend = Spend::default();
+        let start = Utc.with_ymd_and_hms(2022, 10, 31, 12, 0, 0).unwrap();
+        spend.observe(&[application()], start);
+        for minutes in 1..=60 {
+            spend.observe(&[application()], start + Duration::minutes(minutes));
+        }
+        let now = start + Duration::hours(1);
+
+        let status = spend.status("label:game", 0.012, now);
+        assert!(status.is_warning());
+        assert!(!status.is_exceeded());
+        assert!(status.is_imminent());
+        // 11 hours left this month
+        assert!(close(status.projected, 0.12));
+        assert!(!spend.status("label:game", 1.0, now).is_imminent());
+        assert!(spend.project_status(0.01, now).is_exceeded());
+    }
+
+    #[test]
+    fn budgets() {
+        let mut budgets = Budgets::default();
+        budgets.set("label:game", Some(5.0));
+        assert_eq!(budgets.get("label:game"), Some(5.0));
+        budgets.set("label:game", None);
+        assert_eq!(budgets.get("label:game"), None);
+    }
+}
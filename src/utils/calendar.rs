@@ -0,0 +1,1104 @@
+use super::schedule::Zone;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, time::SystemTime};
+
+/// Upper bound of recurrence periods expanded, e.g. days of a daily rule
+const MAX_PERIODS: u32 = 10_000;
+/// Upper bound of `INTERVAL`, larger ones don't repeat within centuries anyway
+const MAX_INTERVAL: i32 = 10_000;
+/// Time zone names understood as UTC when a calendar doesn't define them
+const UTC_NAMES: [&str; 4] = ["UTC", "Etc/UTC", "GMT", "Z"];
+
+/// Wall clock time as written in a calendar, along with the zone it's in
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DateValue {
+    local: NaiveDateTime,
+    zone: TimeRef,
+    date_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TimeRef {
+    Utc,
+    /// Evaluated in the zone of the schedule
+    Floating,
+    /// `TZID` of a `VTIMEZONE`
+    Named(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// `RRULE` of an event or a time zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<DateValue>,
+    /// Weekdays, optionally the n-th of the month or year, negative counting from the end
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+}
+impl Recurrence {
+    fn parse(value: &str) -> Result<Self, String> {
+        let mut recurrence = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+        };
+        let invalid = |part: &str| format!("Invalid recurrence rule part \"{}\"", part);
+        let mut frequency = None;
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=').ok_or_else(|| invalid(part))?;
+            let number = |value: &str| value.parse::<i32>().map_err(|_| invalid(part));
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => return Err(format!("Unsupported recurrence frequency {}", other)),
+                    })
+                }
+                "INTERVAL" => {
+                    recurrence.interval = number(value)?.clamp(1, MAX_INTERVAL) as u32;
+                }
+                "COUNT" => recurrence.count = Some(number(value)?.max(0) as u32),
+                "UNTIL" => recurrence.until = Some(parse_date(value, &HashMap::new())?),
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        // Weekdays are the last two characters, which need not be ASCII
+                        let split = day.char_indices().rev().nth(1).map_or(0, |(i, _)| i);
+                        let weekday = parse_weekday(&day[split..]).ok_or_else(|| invalid(part))?;
+                        let ordinal = match &day[..split] {
+                            "" => None,
+                            ordinal => Some(number(ordinal.trim_start_matches('+'))?),
+                        };
+                        recurrence.by_day.push((ordinal, weekday));
+                    }
+                }
+                "BYMONTHDAY" => {
+                    for day in value.split(',') {
+                        recurrence.by_month_day.push(number(day)?);
+                    }
+                }
+                "BYMONTH" => {
+                    for month in value.split(',') {
+                        recurrence.by_month.push(number(month)? as u32);
+                    }
+                }
+                // Week start only matters for rarely used combinations
+                "WKST" => (),
+                _ => return Err(format!("Unsupported recurrence rule part \"{}\"", part)),
+            }
+        }
+        recurrence.frequency = frequency.ok_or("Recurrence rule without FREQ")?;
+        Ok(recurrence)
+    }
+
+    /// Wall clock times from `start` up to and including `until`, stopping at `limit` or
+    /// past the dates chrono can represent
+    fn occurrences(
+        &self,
+        start: NaiveDateTime,
+        until: NaiveDateTime,
+        limit: Option<NaiveDateTime>,
+    ) -> Vec<NaiveDateTime> {
+        let mut found = Vec::new();
+        let mut count = 0;
+        for period in 0..MAX_PERIODS {
+            let step = period.checked_mul(self.interval);
+            let mut candidates = match step.and_then(|step| self.candidates(start, step)) {
+                Some(candidates) => candidates,
+                None => return found,
+            };
+            candidates.sort();
+            candidates.dedup();
+            for candidate in candidates.into_iter().filter(|at| *at >= start) {
+                let exhausted = self.count.map_or(false, |max| count >= max);
+                if exhausted || limit.map_or(false, |limit| candidate > limit) || candidate > until
+                {
+                    return found;
+                }
+                count += 1;
+                found.push(candidate);
+            }
+        }
+        found
+    }
+
+    /// Matching times within the period `step` periods after the one of `start`, `None` if
+    /// that period can't be represented
+    fn candidates(&self, start: NaiveDateTime, step: u32) -> Option<Vec<NaiveDateTime>> {
+        let date = start.date();
+        let days = match self.frequency {
+            Frequency::Daily => {
+                let day = date.checked_add_signed(Duration::days(step as i64))?;
+                let weekday_matches = self.by_day.is_empty()
+                    || self
+                        .by_day
+                        .iter()
+                        .any(|(_, weekday)| *weekday == day.weekday());
+                let month_day_matches = self.by_month_day.is_empty()
+                    || self.by_month_day.iter().any(|month_day| {
+                        resolve_month_day(*month_day, day.year(), day.month()) == Some(day.day())
+                    });
+                match weekday_matches && month_day_matches {
+                    true => vec![day],
+                    false => vec![],
+                }
+            }
+            Frequency::Weekly => {
+                let monday = date
+                    .checked_sub_signed(Duration::days(
+                        date.weekday().num_days_from_monday() as i64
+                    ))?
+                    .checked_add_signed(Duration::weeks(step as i64))?;
+                let weekdays = match self.by_day.is_empty() {
+                    true => vec![date.weekday()],
+                    false => self.by_day.iter().map(|(_, weekday)| *weekday).collect(),
+                };
+                weekdays
+                    .into_iter()
+                    .filter_map(|weekday| {
+                        monday.checked_add_signed(Duration::days(
+                            weekday.num_days_from_monday() as i64
+                        ))
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = (date.year() * 12 + date.month0() as i32)
+                    .checked_add(i32::try_from(step).ok()?)?;
+                self.month_days(months / 12, months as u32 % 12 + 1, date.day())
+            }
+            Frequency::Yearly => {
+                let year = date.year().checked_add(i32::try_from(step).ok()?)?;
+                if self.by_month.is_empty()
+                    && self.by_month_day.is_empty()
+                    && !self.by_day.is_empty()
+                {
+                    // Weekdays counted within the whole year
+                    let days: Vec<NaiveDate> = NaiveDate::from_ymd_opt(year, 1, 1)
+                        .into_iter()
+                        .flat_map(|first| first.iter_days())
+                        .take_while(|day| day.year() == year)
+                        .collect();
+                    select_weekdays(&days, &self.by_day)
+                } else {
+                    let months = match self.by_month.is_empty() {
+                        true => vec![date.month()],
+                        false => self.by_month.clone(),
+                    };
+                    months
+                        .into_iter()
+                        .filter(|month| (1..=12).contains(month))
+                        .flat_map(|month| self.month_days(year, month, date.day()))
+                        .collect()
+                }
+            }
+        };
+        let times = days
+            .into_iter()
+            .filter(|day| self.by_month.is_empty() || self.by_month.contains(&day.month()))
+            .map(|day| day.and_time(start.time()))
+            .collect();
+        Some(times)
+    }
+
+    /// Matching days of a month, `default_day` if neither days nor weekdays are restricted
+    fn month_days(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        if !self.by_month_day.is_empty() {
+            return self
+                .by_month_day
+                .iter()
+                .filter_map(|day| resolve_month_day(*day, year, month))
+                .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+                .filter(|day| {
+                    self.by_day.is_empty()
+                        || self
+                            .by_day
+                            .iter()
+                            .any(|(_, weekday)| *weekday == day.weekday())
+                })
+                .collect();
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<NaiveDate> = (1..=days_in_month(year, month))
+                .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+                .collect();
+            return select_weekdays(&days, &self.by_day);
+        }
+        NaiveDate::from_ymd_opt(year, month, default_day)
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Days of the given weekdays, or only their n-th occurrence if an ordinal is given
+fn select_weekdays(days: &[NaiveDate], by_day: &[(Option<i32>, Weekday)]) -> Vec<NaiveDate> {
+    let mut selected = Vec::new();
+    for (ordinal, weekday) in by_day {
+        let matching: Vec<NaiveDate> = days
+            .iter()
+            .copied()
+            .filter(|day| day.weekday() == *weekday)
+            .collect();
+        match ordinal {
+            None => selected.extend(matching),
+            Some(ordinal) => {
+                let index = match *ordinal > 0 {
+                    true => *ordinal - 1,
+                    false => matching.len() as i32 + ordinal,
+                };
+                if let Some(day) = usize::try_from(index).ok().and_then(|i| matching.get(i)) {
+                    selected.push(*day);
+                }
+            }
+        }
+    }
+    selected
+}
+
+/// Day of the month, negative days counting from its end
+fn resolve_month_day(day: i32, year: i32, month: u32) -> Option<u32> {
+    let days = days_in_month(year, month) as i32;
+    let day = match day < 0 {
+        true => days + 1 + day,
+        false => day,
+    };
+    (1..=days).contains(&day).then(|| day as u32)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = match month {
+        12 => (year + 1, 1),
+        month => (year, month + 1),
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|first| first.pred_opt())
+        .map_or(31, |last| last.day())
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day.to_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// Observance of a `VTIMEZONE`, i.e. its standard or daylight saving time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Observance {
+    /// First onset, wall clock time before the change, 1970-01-01 until `DTSTART` is read
+    onset: NaiveDateTime,
+    offset_from: i32,
+    offset_to: i32,
+    recurrence: Option<Recurrence>,
+    dates: Vec<NaiveDateTime>,
+}
+impl Observance {
+    /// Onsets up to the given UTC time, as UTC
+    fn onsets_until(&self, until: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let from = Duration::seconds(self.offset_from as i64);
+        let mut onsets = match &self.recurrence {
+            Some(recurrence) => {
+                let limit = recurrence.until.as_ref().map(|until| match until.zone {
+                    TimeRef::Utc => until.local + from,
+                    _ => until.local,
+                });
+                recurrence.occurrences(self.onset, until + from, limit)
+            }
+            None => vec![self.onset],
+        };
+        onsets.extend(self.dates.iter().copied());
+        onsets.into_iter().map(|onset| onset - from).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct VTimeZone {
+    observances: Vec<Observance>,
+}
+impl VTimeZone {
+    /// Offset from UTC in seconds at the given UTC time
+    fn offset_at(&self, at: NaiveDateTime) -> i32 {
+        let mut latest: Option<(NaiveDateTime, i32)> = None;
+        for observance in &self.observances {
+            for onset in observance.onsets_until(at) {
+                if onset <= at && latest.map_or(true, |(latest, _)| onset > latest) {
+                    latest = Some((onset, observance.offset_to));
+                }
+            }
+        }
+        match latest {
+            Some((_, offset)) => offset,
+            // Before the first change
+            None => self
+                .observances
+                .iter()
+                .min_by_key(|observance| observance.onset)
+                .map_or(0, |observance| observance.offset_from),
+        }
+    }
+
+    /// The earliest instant showing the given wall clock time. Times skipped by a change are
+    /// taken with the offset from before it.
+    fn to_utc(&self, local: NaiveDateTime) -> NaiveDateTime {
+        let mut offsets: Vec<i32> = self
+            .observances
+            .iter()
+            .flat_map(|observance| [observance.offset_from, observance.offset_to])
+            .collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+        offsets
+            .iter()
+            .rev()
+            .map(|offset| local - Duration::seconds(*offset as i64))
+            .find(|utc| Duration::seconds(self.offset_at(*utc) as i64) == local - *utc)
+            .unwrap_or_else(|| {
+                let before = self.offset_at(local - Duration::days(1));
+                local - Duration::seconds(before as i64)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Event {
+    uid: Option<String>,
+    start: Option<DateValue>,
+    end: Option<DateValue>,
+    duration: Option<Duration>,
+    recurrence: Option<Recurrence>,
+    exceptions: Vec<DateValue>,
+    /// Set on events replacing one occurrence of a recurring event
+    recurrence_id: Option<DateValue>,
+    cancelled: bool,
+}
+
+/// Events of an iCalendar file, each one an interval the application should be running
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Calendar {
+    events: Vec<Event>,
+    zones: HashMap<String, VTimeZone>,
+}
+impl Calendar {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut calendar = Calendar::default();
+        let mut components: Vec<String> = Vec::new();
+        let mut event: Option<Event> = None;
+        let mut zone: Option<(Option<String>, VTimeZone)> = None;
+        let mut observance: Option<Observance> = None;
+        let mut found = false;
+
+        for (number, line) in unfold(text) {
+            let error = |error: String| format!("Line {}: {}", number, error);
+            let (name, params, value) =
+                parse_line(&line).ok_or_else(|| error("Invalid content line".to_string()))?;
+            match name.as_str() {
+                "BEGIN" => {
+                    let component = value.to_uppercase();
+                    match component.as_str() {
+                        "VCALENDAR" => found = true,
+                        "VEVENT" => event = Some(Event::default()),
+                        "VTIMEZONE" => zone = Some((None, VTimeZone::default())),
+                        "STANDARD" | "DAYLIGHT" => observance = Some(Observance::default()),
+                        _ => (),
+                    }
+                    components.push(component);
+                }
+                "END" => {
+                    let component = value.to_uppercase();
+                    if components.pop().as_ref() != Some(&component) {
+                        return Err(error(format!("Unexpected END:{}", value)));
+                    }
+                    match component.as_str() {
+                        "VEVENT" => {
+                            let event = event.take().unwrap_or_default();
+                            if event.start.is_none() {
+                                return Err(error("Event without DTSTART".to_string()));
+                            }
+                            calendar.events.push(event);
+                        }
+                        "VTIMEZONE" => {
+                            if let Some((Some(id), zone)) = zone.take() {
+                                calendar.zones.insert(id, zone);
+                            }
+                        }
+                        "STANDARD" | "DAYLIGHT" => {
+                            if let (Some((_, zone)), Some(observance)) =
+                                (&mut zone, observance.take())
+                            {
+                                zone.observances.push(observance);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                _ => {
+                    let result = match components.last().map(String::as_str) {
+                        Some("VEVENT") => match &mut event {
+                            Some(event) => event.set(&name, &params, &value),
+                            None => Ok(()),
+                        },
+                        Some("VTIMEZONE") if name == "TZID" => {
+                            if let Some((id, _)) = &mut zone {
+                                *id = Some(value.clone());
+                            }
+                            Ok(())
+                        }
+                        Some("STANDARD") | Some("DAYLIGHT") => match &mut observance {
+                            Some(observance) => observance.set(&name, &params, &value),
+                            None => Ok(()),
+                        },
+                        // Alarms and unknown components are of no interest
+                        _ => Ok(()),
+                    };
+                    result.map_err(error)?;
+                }
+            }
+        }
+        if !found {
+            return Err("Not an iCalendar file".to_string());
+        }
+        if let Some(component) = components.last() {
+            return Err(format!("BEGIN:{} is never ended", component));
+        }
+
+        // Replaced occurrences are taken out of their recurring event
+        let replaced: Vec<(String, DateValue)> = calendar
+            .events
+            .iter()
+            .filter_map(|event| Some((event.uid.clone()?, event.recurrence_id.clone()?)))
+            .collect();
+        for (uid, occurrence) in replaced {
+            let master = calendar.events.iter_mut().find(|event| {
+                event.recurrence.is_some()
+                    && event.recurrence_id.is_none()
+                    && event.uid.as_ref() == Some(&uid)
+            });
+            if let Some(master) = master {
+                master.exceptions.push(occurrence);
+            }
+        }
+        calendar.events.retain(|event| !event.cancelled);
+        Ok(calendar)
+    }
+
+    /// Intervals of the events overlapping the given time span, ordered by their start.
+    /// Floating times and unknown time zones are evaluated in `floating`.
+    pub fn intervals(
+        &self,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        floating: Zone,
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let mut intervals = Vec::new();
+        for event in &self.events {
+            let start = match &event.start {
+                Some(start) => start,
+                None => continue,
+            };
+            let duration = self.duration(event, start, floating);
+            let starts = match &event.recurrence {
+                Some(recurrence) => {
+                    let limit = recurrence
+                        .until
+                        .as_ref()
+                        .map(|until| self.wall_clock(until, &start.zone, floating));
+                    // Wall clock bounds are padded, any offset is less than a day
+                    let until = until.naive_utc() + Duration::days(1);
+                    recurrence.occurrences(start.local, until, limit)
+                }
+                None => vec![start.local],
+            };
+            let exceptions: Vec<DateTime<Utc>> = event
+                .exceptions
+                .iter()
+                .filter_map(|exception| self.to_utc(exception.local, &exception.zone, floating))
+                .collect();
+            let earliest = from.naive_utc() - Duration::days(1) - duration;
+            for local in starts.into_iter().filter(|local| *local >= earliest) {
+                let start = match self.to_utc(local, &start.zone, floating) {
+                    Some(start) => start,
+                    None => continue,
+                };
+                let end = start + duration;
+                if end > from && start <= until && !exceptions.contains(&start) {
+                    intervals.push((start, end));
+                }
+            }
+        }
+        intervals.sort();
+        intervals
+    }
+
+    fn duration(&self, event: &Event, start: &DateValue, floating: Zone) -> Duration {
+        if let Some(duration) = event.duration {
+            return duration;
+        }
+        let duration = match &event.end {
+            Some(end) if end.zone == start.zone => end.local - start.local,
+            Some(end) => {
+                let start = self.to_utc(start.local, &start.zone, floating);
+                let end = self.to_utc(end.local, &end.zone, floating);
+                match (start, end) {
+                    (Some(start), Some(end)) => end - start,
+                    _ => Duration::zero(),
+                }
+            }
+            None if start.date_only => Duration::days(1),
+            None => Duration::zero(),
+        };
+        duration.max(Duration::zero())
+    }
+
+    fn to_utc(
+        &self,
+        local: NaiveDateTime,
+        zone: &TimeRef,
+        floating: Zone,
+    ) -> Option<DateTime<Utc>> {
+        match self.named_zone(zone) {
+            Ok(Some(zone)) => Some(Utc.from_utc_datetime(&zone.to_utc(local))),
+            Ok(None) => Some(Utc.from_utc_datetime(&local)),
+            Err(()) => floating.to_utc(local),
+        }
+    }
+
+    /// The given time as wall clock time of another zone
+    fn wall_clock(&self, at: &DateValue, zone: &TimeRef, floating: Zone) -> NaiveDateTime {
+        if at.zone == *zone || at.zone == TimeRef::Floating {
+            return at.local;
+        }
+        let utc = match self.to_utc(at.local, &at.zone, floating) {
+            Some(utc) => utc,
+            None => return at.local,
+        };
+        match self.named_zone(zone) {
+            Ok(Some(zone)) => {
+                let offset = zone.offset_at(utc.naive_utc());
+                utc.naive_utc() + Duration::seconds(offset as i64)
+            }
+            Ok(None) => utc.naive_utc(),
+            Err(()) => floating.to_local(utc),
+        }
+    }
+
+    /// `Ok(None)` for UTC, `Err` for floating times and zones the calendar doesn't define
+    fn named_zone(&self, zone: &TimeRef) -> Result<Option<&VTimeZone>, ()> {
+        match zone {
+            TimeRef::Utc => Ok(None),
+            TimeRef::Floating => Err(()),
+            TimeRef::Named(id) => match self.zones.get(id) {
+                Some(zone) => Ok(Some(zone)),
+                None if UTC_NAMES.iter().any(|name| name.eq_ignore_ascii_case(id)) => Ok(None),
+                None => Err(()),
+            },
+        }
+    }
+}
+
+impl Event {
+    fn set(
+        &mut self,
+        name: &str,
+        params: &HashMap<String, String>,
+        value: &str,
+    ) -> Result<(), String> {
+        match name {
+            "UID" => self.uid = Some(value.to_string()),
+            "DTSTART" => self.start = Some(parse_date(value, params)?),
+            "DTEND" => self.end = Some(parse_date(value, params)?),
+            "DURATION" => self.duration = Some(parse_duration(value)?),
+            "RRULE" => self.recurrence = Some(Recurrence::parse(value)?),
+            "EXDATE" => {
+                for date in value.split(',') {
+                    self.exceptions.push(parse_date(date, params)?);
+                }
+            }
+            "RECURRENCE-ID" => self.recurrence_id = Some(parse_date(value, params)?),
+            "STATUS" => self.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl Observance {
+    fn set(
+        &mut self,
+        name: &str,
+        params: &HashMap<String, String>,
+        value: &str,
+    ) -> Result<(), String> {
+        match name {
+            "DTSTART" => self.onset = parse_date(value, params)?.local,
+            "TZOFFSETFROM" => self.offset_from = parse_offset(value)?,
+            "TZOFFSETTO" => self.offset_to = parse_offset(value)?,
+            "RRULE" => self.recurrence = Some(Recurrence::parse(value)?),
+            "RDATE" => {
+                for date in value.split(',') {
+                    self.dates.push(parse_date(date, params)?.local);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// Joins folded lines, keeping the number of the line each one started on
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(folded), Some((_, last))) => last.push_str(folded),
+            _ if line.trim().is_empty() => (),
+            _ => lines.push((number + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Splits a content line into its upper case name, parameters and value
+fn parse_line(line: &str) -> Option<(String, HashMap<String, String>, String)> {
+    let mut quoted = false;
+    let colon = line.find(|c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ':' && !quoted
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.to_uppercase(), value.trim_matches('"').to_string()))
+        .collect();
+    Some((name, params, value.to_string()))
+}
+
+/// Parses `DATE` and `DATE-TIME` values, in UTC, floating or of the zone given by `TZID`
+fn parse_date(value: &str, params: &HashMap<String, String>) -> Result<DateValue, String> {
+    let value = value.trim();
+    let invalid = || format!("Invalid date \"{}\"", value);
+    let date_only = params.get("VALUE").map(String::as_str) == Some("DATE") || value.len() == 8;
+    if date_only {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        return Ok(DateValue {
+            local: date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?,
+            zone: TimeRef::Floating,
+            date_only,
+        });
+    }
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, TimeRef::Utc),
+        None => match params.get("TZID") {
+            Some(id) => (value, TimeRef::Named(id.clone())),
+            None => (value, TimeRef::Floating),
+        },
+    };
+    Ok(DateValue {
+        local: NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?,
+        zone,
+        date_only,
+    })
+}
+
+/// Parses durations like `PT1H30M` or `P1W`
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration \"{}\"", value);
+    let (negative, rest) = match value.trim().strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.trim().trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    let mut time = false;
+    for c in rest.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        if c == 'T' {
+            time = true;
+            continue;
+        }
+        let amount: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+        let part = match (c, time) {
+            ('W', false) => Duration::weeks(amount),
+            ('D', false) => Duration::days(amount),
+            ('H', true) => Duration::hours(amount),
+            ('M', true) => Duration::minutes(amount),
+            ('S', true) => Duration::seconds(amount),
+            _ => return Err(invalid()),
+        };
+        duration = duration.checked_add(&part).ok_or_else(invalid)?;
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(if negative { -duration } else { duration })
+}
+
+/// Parses UTC offsets like `+0200` or `-053000` into seconds
+fn parse_offset(value: &str) -> Result<i32, String> {
+    let invalid = || format!("Invalid offset \"{}\"", value);
+    let value = value.trim();
+    let (sign, digits) = match value.chars().next() {
+        Some('+') => (1, &value[1..]),
+        Some('-') => (-1, &value[1..]),
+        _ => return Err(invalid()),
+    };
+    if !(digits.len() == 4 || digits.len() == 6) || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let field = |range: std::ops::Range<usize>| digits[range].parse::<i32>().unwrap_or_default();
+    let seconds = if digits.len() == 6 { field(4..6) } else { 0 };
+    Ok(sign * (field(0..2) * 3600 + field(2..4) * 60 + seconds))
+}
+
+/// Calendar file of a schedule, read again whenever it changes on disk
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CalendarSource {
+    pub path: String,
+    /// Minutes to restore ahead of each event
+    pub lead_minutes: u32,
+    /// Minutes to keep running after each event
+    pub trail_minutes: u32,
+    #[serde(skip)]
+    loaded: Option<LoadedCalendar>,
+}
+
+#[derive(Debug, Clone)]
+struct LoadedCalendar {
+    /// Modification time and size the calendar was read at
+    version: Option<(SystemTime, u64)>,
+    calendar: Result<Calendar, String>,
+}
+
+impl PartialEq for CalendarSource {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+            && self.lead_minutes == other.lead_minutes
+            && self.trail_minutes == other.trail_minutes
+    }
+}
+impl Eq for CalendarSource {}
+
+impl CalendarSource {
+    pub fn new(path: &str, lead_minutes: u32, trail_minutes: u32) -> Self {
+        Self {
+            path: path.to_string(),
+            lead_minutes,
+            trail_minutes,
+            loaded: None,
+        }
+    }
+
+    /// Reads the file unless it's unchanged since the last read, returns whether it was read
+    pub fn reload(&mut self) -> bool {
+        let version = fs::metadata(&self.path)
+            .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+            .ok();
+        if let Some(loaded) = &self.loaded {
+            if version.is_some() && loaded.version == version {
+                return false;
+            }
+        }
+        let calendar = fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read {}: {}", self.path, e))
+            .and_then(|text| Calendar::parse(&text));
+        self.loaded = Some(LoadedCalendar { version, calendar });
+        true
+    }
+
+    pub fn calendar(&self) -> Result<&Calendar, String> {
+        match &self.loaded {
+            Some(loaded) => loaded.calendar.as_ref().map_err(Clone::clone),
+            None => Err(format!("{} wasn't read yet", self.path)),
+        }
+    }
+
+    /// Intervals the application should be running, including lead and trail time, with
+    /// overlapping ones merged
+    pub fn intervals(
+        &self,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        floating: Zone,
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let calendar = match self.calendar() {
+            Ok(calendar) => calendar,
+            Err(_) => return Vec::new(),
+        };
+        let lead = Duration::minutes(self.lead_minutes as i64);
+        let trail = Duration::minutes(self.trail_minutes as i64);
+        let mut merged: Vec<(DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+        for (start, end) in calendar.intervals(from - trail, until + lead, floating) {
+            let (start, end) = (start - lead, end + trail);
+            match merged.last_mut() {
+                Some((_, last_end)) if start <= *last_end => *last_end = end.max(*last_end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Calendar, CalendarSource, Recurrence};
+    use crate::utils::Zone;
+    use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at).unwrap().into()
+    }
+
+    const BERLIN: &str = "BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+DTSTART:19700329T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+DTSTART:19701025T030000\r
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+";
+
+    fn calendar(events: &str) -> Calendar {
+        let text = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}{}END:VCALENDAR\r\n",
+            BERLIN, events
+        );
+        Calendar::parse(&text).unwrap()
+    }
+
+    #[test]
+    fn single_event() {
+        let calendar = calendar(
+            "BEGIN:VEVENT\r
+UID:1\r
+SUMMARY:Game night with a very long description that is folded\r
+  onto the next line\r
+DTSTART:20221014T180000Z\r
+DURATION:PT3H30M\r
+BEGIN:VALARM\r
+TRIGGER:-PT15M\r
+END:VALARM\r
+END:VEVENT\r
+",
+        );
+        let intervals = calendar.intervals(
+            utc("2022-10-01T00:00:00Z"),
+            utc("2022-11-01T00:00:00Z"),
+            Zone::Utc,
+        );
+        assert_eq!(
+            intervals,
+            vec![(utc("2022-10-14T18:00:00Z"), utc("2022-10-14T21:30:00Z"))]
+        );
+        // Ended before
+        assert!(calendar
+            .intervals(
+                utc("2022-10-15T00:00:00Z"),
+                utc("2022-11-01T00:00:00Z"),
+                Zone::Utc
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn recurring_across_daylight_saving_time() {
+        let calendar = calendar(
+            "BEGIN:VEVENT\r
+UID:weekly\r
+DTSTART;TZID=Europe/Berlin:20221021T180000\r
+DTEND;TZID=Europe/Berlin:20221021T220000\r
+RRULE:FREQ=WEEKLY;BYDAY=FR,SA;COUNT=5\r
+EXDATE;TZID=Europe/Berlin:20221022T180000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:weekly\r
+RECURRENCE-ID;TZID=Europe/Berlin:20221028T180000\r
+DTSTART;TZID=Europe/Berlin:20221028T190000\r
+DTEND;TZID=Europe/Berlin:20221028T230000\r
+END:VEVENT\r
+",
+        );
+        let starts: Vec<DateTime<Utc>> = calendar
+            .intervals(
+                utc("2022-10-01T00:00:00Z"),
+                utc("2022-12-01T00:00:00Z"),
+                Zone::Utc,
+            )
+            .into_iter()
+            .map(|(start, end)| {
+                assert_eq!(end - start, Duration::hours(4));
+                start
+            })
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                utc("2022-10-21T18:00:00+02:00"),
+                // Moved by an hour
+                utc("2022-10-28T19:00:00+02:00"),
+                // Winter time from October 30th
+                utc("2022-10-29T18:00:00+02:00"),
+                utc("2022-11-04T18:00:00+01:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_and_floating() {
+        let calendar = calendar(
+            "BEGIN:VEVENT\r
+DTSTART:20221010T200000\r
+DTEND:20221010T210000\r
+RRULE:FREQ=MONTHLY;BYDAY=2MO;UNTIL=20230101T000000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20221224\r
+END:VEVENT\r
+",
+        );
+        let starts: Vec<DateTime<Utc>> = calendar
+            .intervals(
+                utc("2022-10-01T00:00:00Z"),
+                utc("2023-12-31T00:00:00Z"),
+                Zone::Fixed(3600),
+            )
+            .into_iter()
+            .map(|(start, _)| start)
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                utc("2022-10-10T20:00:00+01:00"),
+                utc("2022-11-14T20:00:00+01:00"),
+                utc("2022-12-12T20:00:00+01:00"),
+                utc("2022-12-24T00:00:00+01:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn huge_intervals() {
+        for frequency in ["DAILY", "WEEKLY", "MONTHLY", "YEARLY"] {
+            let calendar = calendar(&format!(
+                "BEGIN:VEVENT\r
+DTSTART:20221014T180000Z\r
+DURATION:PT1H\r
+RRULE:FREQ={};INTERVAL=2000000000\r
+END:VEVENT\r
+",
+                frequency
+            ));
+            let intervals = calendar.intervals(
+                utc("2022-10-01T00:00:00Z"),
+                utc("2023-10-01T00:00:00Z"),
+                Zone::Utc,
+            );
+            assert_eq!(intervals.len(), 1, "{}", frequency);
+
+            // Recurrences end where chrono's dates do
+            let mut recurrence = Recurrence::parse(&format!("FREQ={}", frequency)).unwrap();
+            recurrence.interval = u32::MAX;
+            let start = utc("2022-10-14T18:00:00Z").naive_utc();
+            let occurrences = recurrence.occurrences(start, NaiveDateTime::MAX, None);
+            assert_eq!(occurrences, vec![start], "{}", frequency);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Calendar::parse("BEGIN:VEVENT\r\nEND:VEVENT\r\n").is_err());
+        assert!(Calendar::parse("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n").is_err());
+        let error = Calendar::parse(
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART:20221014T180000Z\r\nRRULE:FREQ=HOURLY\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+        )
+        .unwrap_err();
+        assert_eq!(error, "Line 4: Unsupported recurrence frequency HOURLY");
+
+        // Malformed weekdays, including non-ASCII ones, are rejected instead of panicking
+        for by_day in ["Ö1", "1ÖM", "É", "X"] {
+            let calendar = format!(
+                "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART:20221014T180000Z\r\nRRULE:FREQ=WEEKLY;BYDAY={}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+                by_day
+            );
+            assert!(Calendar::parse(&calendar).is_err(), "{}", by_day);
+        }
+    }
+
+    #[test]
+    fn source() {
+        let path = std::env::temp_dir().join(format!("cruncher-{}.ics", std::process::id()));
+        let event = |start: &str| {
+            format!(
+                "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART:{}\r\nDURATION:PT1H\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+                start
+            )
+        };
+        std::fs::write(&path, event("20221014T180000Z")).unwrap();
+        let mut source = CalendarSource::new(path.to_str().unwrap(), 30, 15);
+        assert!(source.reload());
+        assert!(!source.reload());
+        let from = utc("2022-10-01T00:00:00Z");
+        let until = utc("2022-11-01T00:00:00Z");
+        assert_eq!(
+            source.intervals(from, until, Zone::Utc),
+            vec![(utc("2022-10-14T17:30:00Z"), utc("2022-10-14T19:15:00Z"))]
+        );
+
+        std::fs::write(&path, event("20221015T180000Z") + "\r\n").unwrap();
+        assert!(source.reload());
+        assert_eq!(
+            source.intervals(from, until, Zone::Utc)[0].0,
+            utc("2022-10-15T17:30:00Z")
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(source.reload());
+        assert!(source.calendar().is_err());
+    }
+}
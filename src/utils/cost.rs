@@ -0,0 +1,308 @@
+use super::Application;
+use hcloud::models::{image, Image, Price, PricePerTime, Server};
+use std::{
+    collections::BTreeMap,
+    iter::Sum,
+    ops::{Add, AddAssign},
+};
+
+/// Monthly price of snapshot storage in EUR per GB, excluding VAT
+pub const SNAPSHOT_PRICE_PER_GB: f64 = 0.0119;
+/// Price of outgoing traffic beyond the included amount in EUR per TB, excluding VAT
+pub const TRAFFIC_PRICE_PER_TB: f64 = 1.0;
+/// VAT rate assumed until a server price tells the actual one
+pub const DEFAULT_VAT_RATE: f64 = 0.19;
+/// Used to spread monthly prices over hours
+pub const HOURS_PER_MONTH: f64 = 730.0;
+const BYTES_PER_TB: f64 = 1e12;
+
+/// Monthly net price of a server type at the given location
+pub fn monthly_price(prices: &[PricePerTime], location: &str) -> Option<f64> {
+    prices
+        .iter()
+        .find(|price| price.location == location)
+        .and_then(|price| price.price_monthly.net.parse().ok())
+}
+
+pub fn server_monthly(server: &Server) -> Option<f64> {
+    monthly_price(&server.server_type.prices, &server.datacenter.location.name)
+}
+
+/// Hourly net price of a server
+pub fn server_hourly(server: &Server) -> Option<f64> {
+    Rate::of_server(server, DEFAULT_VAT_RATE).map(|rate| rate.hourly.net)
+}
+
+/// Monthly storage cost of a snapshot, if its size is known
+pub fn image_monthly(image: &Image) -> Option<f64> {
+    image
+        .image_size
+        .map(|size| size as f64 * SNAPSHOT_PRICE_PER_GB)
+}
+
+/// VAT rate charged on the given prices, taken from the first one with both net and gross set
+pub fn vat_rate<'a>(prices: impl IntoIterator<Item = &'a PricePerTime>) -> f64 {
+    prices
+        .into_iter()
+        .find_map(|price| {
+            let net: f64 = price.price_monthly.net.parse().ok()?;
+            let gross: f64 = price.price_monthly.gross.parse().ok()?;
+            (net > 0.0).then(|| gross / net - 1.0)
+        })
+        .unwrap_or(DEFAULT_VAT_RATE)
+}
+
+/// Amount in EUR, with and without VAT
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Amount {
+    pub net: f64,
+    pub gross: f64,
+}
+impl Amount {
+    pub fn from_net(net: f64, vat_rate: f64) -> Self {
+        Self {
+            net,
+            gross: net * (1.0 + vat_rate),
+        }
+    }
+
+    /// Parses an hcloud price, computing the gross amount if it's missing
+    fn parse(price: &Price, vat_rate: f64) -> Option<Self> {
+        let net = price.net.parse().ok()?;
+        Some(match price.gross.parse() {
+            Ok(gross) => Self { net, gross },
+            Err(_) => Self::from_net(net, vat_rate),
+        })
+    }
+
+    pub fn get(&self, gross: bool) -> f64 {
+        match gross {
+            true => self.gross,
+            false => self.net,
+        }
+    }
+}
+impl Add for Amount {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            net: self.net + other.net,
+            gross: self.gross + other.gross,
+        }
+    }
+}
+impl AddAssign for Amount {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+/// Hourly and monthly cost of something
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rate {
+    pub hourly: Amount,
+    pub monthly: Amount,
+}
+impl Rate {
+    /// Rate of something only priced per month
+    fn monthly(monthly: Amount) -> Self {
+        Self {
+            hourly: Amount {
+                net: monthly.net / HOURS_PER_MONTH,
+                gross: monthly.gross / HOURS_PER_MONTH,
+            },
+            monthly,
+        }
+    }
+
+    fn of_server(server: &Server, vat_rate: f64) -> Option<Self> {
+        let location = &server.datacenter.location.name;
+        let price = server
+            .server_type
+            .prices
+            .iter()
+            .find(|price| &price.location == location)?;
+        let monthly = Amount::parse(&price.price_monthly, vat_rate)?;
+        Some(match Amount::parse(&price.price_hourly, vat_rate) {
+            Some(hourly) => Self { hourly, monthly },
+            None => Self::monthly(monthly),
+        })
+    }
+}
+impl Add for Rate {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            hourly: self.hourly + other.hourly,
+            monthly: self.monthly + other.monthly,
+        }
+    }
+}
+impl AddAssign for Rate {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+impl Sum for Rate {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+/// What an application, or all of them together, costs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostBreakdown {
+    /// Servers by location
+    pub servers: BTreeMap<String, Rate>,
+    pub snapshots: Rate,
+    /// Outgoing traffic beyond the included amount, so far this billing period
+    pub traffic: Rate,
+    /// Servers and snapshots left out because their price or size is unknown
+    pub unpriced: usize,
+}
+impl CostBreakdown {
+    pub fn of(application: &Application, vat_rate: f64) -> Self {
+        let mut breakdown = Self::default();
+        for server in application.servers.iter().flatten() {
+            match Rate::of_server(server, vat_rate) {
+                Some(rate) => {
+                    let location = server.datacenter.location.name.clone();
+                    *breakdown.servers.entry(location).or_default() += rate;
+                }
+                None => breakdown.unpriced += 1,
+            }
+
+            let overage = server
+                .outgoing_traffic
+                .zip(server.included_traffic)
+                .map(|(outgoing, included)| (outgoing - included).max(0) as f64 / BYTES_PER_TB)
+                .unwrap_or_default();
+            let traffic = Amount::from_net(overage * TRAFFIC_PRICE_PER_TB, vat_rate);
+            breakdown.traffic += Rate::monthly(traffic);
+        }
+
+        let snapshots = application.images.iter().flatten();
+        for snapshot in snapshots.filter(|image| image.r#type == image::Type::Snapshot) {
+            match image_monthly(snapshot) {
+                Some(net) => breakdown.snapshots += Rate::monthly(Amount::from_net(net, vat_rate)),
+                None => breakdown.unpriced += 1,
+            }
+        }
+        breakdown
+    }
+
+    pub fn total(&self) -> Rate {
+        self.servers.values().copied().sum::<Rate>() + self.snapshots + self.traffic
+    }
+}
+impl AddAssign<&CostBreakdown> for CostBreakdown {
+    fn add_assign(&mut self, other: &CostBreakdown) {
+        for (location, rate) in &other.servers {
+            *self.servers.entry(location.clone()).or_default() += *rate;
+        }
+        self.snapshots += other.snapshots;
+        self.traffic += other.traffic;
+        self.unpriced += other.unpriced;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Amount, CostBreakdown};
+    use crate::utils::{empty_server, empty_snapshot, prices, Application};
+    use hcloud::models::{Price, PricePerTime, Server};
+
+    fn priced_server(id: i32, location: &str) -> Server {
+        let price = |net: &str, gross: &str| {
+            Box::new(Price {
+                net: net.to_string(),
+                gross: gross.to_string(),
+            })
+        };
+        let mut server = empty_server();
+        server.id = id;
+        server.datacenter.location.name = location.to_string();
+        server.server_type.prices = vec![PricePerTime {
+            location: location.to_string(),
+            price_hourly: price("0.0070", "0.0083"),
+            price_monthly: price("4.0000", "4.7600"),
+        }];
+        server
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn server_monthly() {
+        let mut server = empty_server();
+        server.datacenter.location.name = "fsn1".to_string();
+        assert_eq!(super::server_monthly(&server), None);
+
+        server.server_type.prices = prices("fsn1", "4.5100000000");
+        assert_eq!(super::server_monthly(&server), Some(4.51));
+    }
+
+    #[test]
+    fn image_monthly() {
+        let mut image = empty_snapshot();
+        assert_eq!(super::image_monthly(&image), None);
+
+        image.image_size = Some(100.0);
+        let monthly = super::image_monthly(&image).unwrap();
+        assert!((monthly - 1.19).abs() < 1e-6);
+    }
+
+    #[test]
+    fn vat_rate() {
+        let server = priced_server(1, "fsn1");
+        assert!(close(super::vat_rate(&server.server_type.prices), 0.19));
+        let net_only = prices("fsn1", "4.51");
+        assert_eq!(super::vat_rate(&net_only), super::DEFAULT_VAT_RATE);
+    }
+
+    #[test]
+    fn breakdown() {
+        let mut traffic = priced_server(2, "nbg1");
+        traffic.included_traffic = Some(20_000_000_000_000);
+        traffic.outgoing_traffic = Some(22_000_000_000_000);
+        let mut snapshot = empty_snapshot();
+        snapshot.image_size = Some(100.0);
+        let mut application = Application::new();
+        application.add_server(&priced_server(1, "fsn1"));
+        application.add_image(&snapshot);
+        application.add_image(&empty_snapshot());
+        application.add_server(&traffic);
+        application.add_server(&priced_server(3, "nbg1"));
+        application.add_server(&empty_server());
+
+        let breakdown = CostBreakdown::of(&application, 0.19);
+        assert_eq!(breakdown.servers.len(), 2);
+        assert!(close(breakdown.servers["nbg1"].monthly.net, 8.0));
+        assert!(close(breakdown.servers["nbg1"].hourly.gross, 0.0166));
+        assert!(close(breakdown.snapshots.monthly.net, 1.19));
+        assert!(close(breakdown.traffic.monthly.net, 2.0));
+        assert!(close(breakdown.traffic.monthly.gross, 2.38));
+        // The server without prices and the snapshot without size
+        assert_eq!(breakdown.unpriced, 2);
+
+        let total = breakdown.total();
+        assert!(close(total.monthly.net, 12.0 + 1.19 + 2.0));
+        let mut project = CostBreakdown::default();
+        project += &breakdown;
+        project += &breakdown;
+        assert!(close(
+            project.total().monthly.get(false),
+            2.0 * total.monthly.net
+        ));
+        assert_eq!(project.unpriced, 4);
+    }
+
+    #[test]
+    fn amount() {
+        let amount = Amount::from_net(10.0, 0.2);
+        assert!(close(amount.get(true), 12.0));
+        assert!(close(amount.get(false), 10.0));
+    }
+}
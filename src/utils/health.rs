@@ -0,0 +1,299 @@
+use super::{hooks::SSH_PROGRAM, ShutdownHooks};
+use serde::{Deserialize, Serialize};
+use std::{io, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::Instant,
+};
+
+/// Origin of health checks run outside of a restore, they don't keep their application busy
+pub const HEALTH_ORIGIN: &str = "health";
+/// Longest a single attempt may take, the deadline of the checks cuts it shorter
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Only the status line and the start of the body are of interest
+const MAX_RESPONSE_SIZE: u64 = 64 * 1024;
+
+/// A way to tell whether the service of a restored server is up
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HealthCheck {
+    /// The port accepts connections
+    Tcp { port: u16 },
+    /// A plain HTTP GET answers with the status, and with a body containing `body` if set
+    Http {
+        port: u16,
+        path: String,
+        status: u16,
+        body: String,
+    },
+    /// The command exits with status 0 when run over SSH
+    Ssh {
+        user: String,
+        port: u16,
+        /// Private key to log in with, the servers are never asked for a password
+        key_path: String,
+        command: String,
+    },
+}
+impl HealthCheck {
+    pub fn tcp() -> Self {
+        Self::Tcp { port: 25565 }
+    }
+
+    pub fn http() -> Self {
+        Self::Http {
+            port: 80,
+            path: "/".to_string(),
+            status: 200,
+            body: String::new(),
+        }
+    }
+
+    pub fn ssh() -> Self {
+        Self::Ssh {
+            user: "root".to_string(),
+            port: 22,
+            key_path: String::new(),
+            command: "systemctl is-active game".to_string(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Self::Tcp { port } => format!("TCP port {}", port),
+            Self::Http { port, path, .. } => format!("HTTP GET :{}{}", port, path),
+            Self::Ssh { command, .. } => format!("SSH \"{}\"", command),
+        }
+    }
+
+    async fn probe_with(&self, ssh: &str, host: &str) -> Result<(), String> {
+        match self {
+            Self::Tcp { port } => TcpStream::connect((host, *port))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Self::Http {
+                port,
+                path,
+                status,
+                body,
+            } => {
+                let (got, text) = http_get(host, *port, path)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if got != *status {
+                    Err(format!("status {} instead of {}", got, status))
+                } else if !text.contains(body.as_str()) {
+                    Err(format!("body doesn't contain \"{}\"", body))
+                } else {
+                    Ok(())
+                }
+            }
+            Self::Ssh {
+                user,
+                port,
+                key_path,
+                command,
+            } => {
+                let login = ShutdownHooks {
+                    user: user.clone(),
+                    port: *port,
+                    key_path: key_path.clone(),
+                    commands: Vec::new(),
+                    timeout_secs: ATTEMPT_TIMEOUT.as_secs(),
+                };
+                login.run_command(ssh, host, command).await
+            }
+        }
+    }
+}
+
+/// Checks run on every server of an application after it is restored, each one retried
+/// until it passes or the deadline is reached
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HealthChecks {
+    pub checks: Vec<HealthCheck>,
+    /// Pause between two attempts of a failing check
+    pub retry_secs: u64,
+    /// For all checks together, counted from the server running
+    pub deadline_secs: u64,
+}
+impl Default for HealthChecks {
+    fn default() -> Self {
+        Self {
+            checks: Vec::new(),
+            retry_secs: 5,
+            deadline_secs: 300,
+        }
+    }
+}
+impl HealthChecks {
+    /// Runs every check in order on the given host, failing with the last error of the
+    /// first check that didn't pass in time
+    pub async fn run(&self, host: &str) -> Result<(), String> {
+        self.run_with(SSH_PROGRAM, host).await
+    }
+
+    async fn run_with(&self, ssh: &str, host: &str) -> Result<(), String> {
+        let deadline = Instant::now() + Duration::from_secs(self.deadline_secs);
+        let retry = Duration::from_secs(self.retry_secs);
+        for check in &self.checks {
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let attempt = tokio::time::timeout(
+                    remaining.min(ATTEMPT_TIMEOUT),
+                    check.probe_with(ssh, host),
+                );
+                let error = match attempt.await {
+                    Ok(Ok(())) => break,
+                    Ok(Err(e)) => e,
+                    Err(_) => "timed out".to_string(),
+                };
+                if Instant::now() + retry >= deadline {
+                    return Err(format!(
+                        "Health check {} on {} failed after {} attempts in {}s: {}",
+                        check.label(),
+                        host,
+                        attempts,
+                        self.deadline_secs,
+                        error
+                    ));
+                }
+                tokio::time::sleep(retry).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends a GET request, returning the status code and the start of the body
+async fn http_get(host: &str, port: u16, path: &str) -> io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect((host, port)).await?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: server-cruncher\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    stream
+        .take(MAX_RESPONSE_SIZE)
+        .read_to_end(&mut response)
+        .await?;
+    parse_response(&String::from_utf8_lossy(&response))
+}
+
+fn parse_response(response: &str) -> io::Result<(u16, String)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response");
+    let status = response
+        .lines()
+        .next()
+        .filter(|line| line.starts_with("HTTP/"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(invalid)?;
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    Ok((status, body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_response, HealthCheck, HealthChecks};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Answers every request with the given response, and closes the connection
+    async fn http_server(response: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        port
+    }
+
+    fn checks(checks: Vec<HealthCheck>) -> HealthChecks {
+        HealthChecks {
+            checks,
+            retry_secs: 1,
+            deadline_secs: 2,
+        }
+    }
+
+    #[test]
+    fn response() {
+        let (status, body) =
+            parse_response("HTTP/1.1 503 Unavailable\r\nA: b\r\n\r\nstarting").unwrap();
+        assert_eq!(status, 503);
+        assert_eq!(body, "starting");
+        assert!(parse_response("SSH-2.0-OpenSSH_9.0\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let open = checks(vec![HealthCheck::Tcp { port }]);
+        assert_eq!(open.run("127.0.0.1").await, Ok(()));
+
+        drop(listener);
+        let closed = open.run("127.0.0.1").await.unwrap_err();
+        assert!(
+            closed.starts_with(&format!(
+                "Health check TCP port {} on 127.0.0.1 failed after 2 attempts",
+                port
+            )),
+            "{}",
+            closed
+        );
+    }
+
+    #[tokio::test]
+    async fn http() {
+        let port = http_server("HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\n{\"up\":1}\n").await;
+        let check = |status, body: &str| HealthCheck::Http {
+            port,
+            path: "/health".to_string(),
+            status,
+            body: body.to_string(),
+        };
+        let passing = checks(vec![check(200, "\"up\":1"), check(200, "")]);
+        assert_eq!(passing.run("127.0.0.1").await, Ok(()));
+
+        let error = checks(vec![check(204, "")]).run("127.0.0.1").await;
+        assert!(error.unwrap_err().ends_with("status 200 instead of 204"));
+        let error = checks(vec![check(200, "down")]).run("127.0.0.1").await;
+        assert!(error
+            .unwrap_err()
+            .ends_with("body doesn't contain \"down\""));
+    }
+
+    #[tokio::test]
+    async fn ssh() {
+        let check = checks(vec![HealthCheck::ssh()]);
+        let error = check
+            .run_with("/nonexistent/ssh", "127.0.0.1")
+            .await
+            .unwrap_err();
+        assert!(
+            error.contains("SSH \"systemctl is-active game\""),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("Failed to run /nonexistent/ssh"),
+            "{}",
+            error
+        );
+    }
+}
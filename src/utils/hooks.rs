@@ -0,0 +1,227 @@
+use super::RconShutdown;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, process::Stdio, time::Duration};
+use tokio::process::Command;
+
+/// OpenSSH client used to reach the servers
+pub(super) const SSH_PROGRAM: &str = "ssh";
+/// Seconds to wait for the SSH connection itself, on top of the command timeout
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// Commands run over SSH on every server of an application before it is shut down,
+/// e.g. to stop a service or save a world. The crunch is aborted if any of them fails.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ShutdownHooks {
+    pub user: String,
+    pub port: u16,
+    /// Private key to log in with, the servers are never asked for a password
+    pub key_path: String,
+    /// Run in order, one SSH session each
+    pub commands: Vec<String>,
+    /// Per command, including connecting
+    pub timeout_secs: u64,
+}
+impl Default for ShutdownHooks {
+    fn default() -> Self {
+        Self {
+            user: "root".to_string(),
+            port: 22,
+            key_path: String::new(),
+            commands: Vec::new(),
+            timeout_secs: 60,
+        }
+    }
+}
+impl ShutdownHooks {
+    /// Runs every command on the given host, stopping at the first failure
+    pub async fn run(&self, host: &str) -> Result<(), String> {
+        self.run_with(SSH_PROGRAM, host).await
+    }
+
+    async fn run_with(&self, program: &str, host: &str) -> Result<(), String> {
+        for command in &self.commands {
+            self.run_command(program, host, command)
+                .await
+                .map_err(|e| format!("Shutdown hook {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn args(&self, host: &str, command: &str) -> Vec<String> {
+        let mut args: Vec<String> = vec!["-o".into(), "BatchMode=yes".into()];
+        args.extend(["-o".into(), "StrictHostKeyChecking=accept-new".into()]);
+        args.extend([
+            "-o".into(),
+            format!("ConnectTimeout={}", CONNECT_TIMEOUT_SECS),
+        ]);
+        args.extend(["-p".into(), self.port.to_string()]);
+        if !self.key_path.is_empty() {
+            args.extend(["-i".into(), self.key_path.clone()]);
+        }
+        args.push(format!("{}@{}", self.user, host));
+        args.push("--".into());
+        args.push(command.to_string());
+        args
+    }
+
+    /// Runs a single command with the login of the hooks, errors name the command and host
+    pub(super) async fn run_command(
+        &self,
+        program: &str,
+        host: &str,
+        command: &str,
+    ) -> Result<(), String> {
+        let child = Command::new(program)
+            .args(self.args(host, command))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+        let timeout = Duration::from_secs(self.timeout_secs);
+        let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => output.map_err(|e| format!("Failed to run {}: {}", program, e))?,
+            Err(_) => {
+                return Err(format!(
+                    "\"{}\" on {} timed out after {}s",
+                    command, host, self.timeout_secs
+                ))
+            }
+        };
+        if output.status.success() {
+            return Ok(());
+        }
+        let status = match output.status.code() {
+            Some(code) => format!("exit status {}", code),
+            None => "a signal".to_string(),
+        };
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!(
+            "\"{}\" on {} failed with {}: {}",
+            command,
+            host,
+            status,
+            stderr.trim()
+        ))
+    }
+}
+
+/// Everything to do on the servers of an application before they are shut down
+#[derive(Debug, Clone, Default)]
+pub struct PreShutdown {
+    /// Saves and stops the game first
+    pub rcon: Option<RconShutdown>,
+    pub hooks: Option<ShutdownHooks>,
+    /// Public IPv4 by server ID
+    pub hosts: HashMap<i32, Option<String>>,
+}
+impl PreShutdown {
+    pub fn is_empty(&self) -> bool {
+        self.rcon.is_none() && self.hooks.is_none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ShutdownHooks;
+    use std::path::PathBuf;
+
+    /// Stands in for ssh by running the command locally, after checking the login
+    fn fake_ssh() -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cruncher-{}-ssh", std::process::id()));
+        let script = "#!/bin/sh\n\
+            for arg; do last=\"$arg\"; done\n\
+            case \"$*\" in *\"admin@127.0.0.1 --\"*) ;; *) echo \"bad login: $*\" >&2; exit 255;; esac\n\
+            exec sh -c \"$last\"\n";
+        std::fs::write(&path, script).unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn hooks(commands: &[&str]) -> ShutdownHooks {
+        ShutdownHooks {
+            user: "admin".to_string(),
+            commands: commands.iter().map(|command| command.to_string()).collect(),
+            timeout_secs: 1,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn run() {
+        let ssh = fake_ssh();
+        let ssh = ssh.to_str().unwrap();
+        let marker = std::env::temp_dir().join(format!("cruncher-{}-hook", std::process::id()));
+        let marker = marker.to_str().unwrap();
+
+        let saved = hooks(&["true", &format!("echo saved > {}", marker)]);
+        assert_eq!(saved.run_with(ssh, "127.0.0.1").await, Ok(()));
+        assert_eq!(std::fs::read_to_string(marker).unwrap(), "saved\n");
+
+        // Later commands are skipped after a failure
+        std::fs::remove_file(marker).unwrap();
+        let failing = hooks(&[
+            "echo flush failed >&2; exit 3",
+            &format!("touch {}", marker),
+        ]);
+        let error = failing.run_with(ssh, "127.0.0.1").await.unwrap_err();
+        assert!(error.contains("exit status 3: flush failed"), "{}", error);
+        assert!(std::fs::metadata(marker).is_err());
+
+        let hanging = hooks(&["sleep 5"]);
+        let error = hanging.run_with(ssh, "127.0.0.1").await.unwrap_err();
+        assert!(error.contains("timed out after 1s"), "{}", error);
+
+        let missing = hooks(&["true"])
+            .run_with("/nonexistent/ssh", "127.0.0.1")
+            .await;
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn args() {
+        let mut hooks = hooks(&[]);
+        hooks.key_path = "/keys/id_ed25519".to_string();
+        hooks.port = 2222;
+        let args = hooks.args("192.0.2.1", "systemctl stop game");
+        assert_eq!(
+            args[args.len() - 5..],
+            [
+                "-i",
+                "/keys/id_ed25519",
+                "admin@192.0.2.1",
+                "--",
+                "systemctl stop game"
+            ][..]
+        );
+        assert!(args.contains(&"BatchMode=yes".to_string()));
+        assert!(args.windows(2).any(|pair| pair == ["-p", "2222"]));
+    }
+
+    /// Runs against a real sshd, e.g. `SSH_HOOK_HOST=127.0.0.1 SSH_HOOK_PORT=2222
+    /// SSH_HOOK_KEY=~/.ssh/id_ed25519 cargo test -- --ignored local_sshd`
+    #[ignore = "needs a local sshd"]
+    #[tokio::test]
+    async fn local_sshd() {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let hooks = ShutdownHooks {
+            user: std::env::var("SSH_HOOK_USER").unwrap_or_else(|_| var("USER")),
+            port: var("SSH_HOOK_PORT").parse().unwrap_or(22),
+            key_path: var("SSH_HOOK_KEY"),
+            commands: vec!["true".to_string()],
+            timeout_secs: 10,
+        };
+        let host = std::env::var("SSH_HOOK_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+        assert_eq!(hooks.run(&host).await, Ok(()));
+
+        let failing = ShutdownHooks {
+            commands: vec!["exit 7".to_string()],
+            ..hooks
+        };
+        let error = failing.run(&host).await.unwrap_err();
+        assert!(error.contains("exit status 7"), "{}", error);
+    }
+}
@@ -0,0 +1,139 @@
+use super::{Application, Resource};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Remembers which application each server and image belonged to, so an application keeps its
+/// ID while its resources change, e.g. when a crunch replaces the server with a snapshot or a
+/// restore adds a new server.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Identities {
+    ids: HashMap<Resource, String>,
+}
+impl Identities {
+    /// Hands out previously seen IDs to the applications and records their current resources.
+    /// Resources missing from a `complete` list are forgotten.
+    pub fn apply(&mut self, applications: &mut [Application], complete: bool) {
+        let mut used = HashSet::new();
+        for application in applications.iter_mut() {
+            let known = known_by(application)
+                .iter()
+                .filter_map(|resource| self.ids.get(resource))
+                .find(|id| !used.contains(*id))
+                .cloned();
+            if let Some(id) = known {
+                application.set_id(id);
+            }
+            used.insert(application.id().to_string());
+        }
+
+        if complete {
+            self.ids.clear();
+        }
+        for application in applications.iter() {
+            for resource in application.resources() {
+                self.ids.insert(resource, application.id().to_string());
+            }
+        }
+    }
+}
+
+/// Resources of an application along with the servers its snapshots were taken from, as a
+/// crunch replaces the server with a snapshot the list has never seen before
+fn known_by(application: &Application) -> Vec<Resource> {
+    let sources = application
+        .images
+        .iter()
+        .flatten()
+        .filter_map(|image| image.created_from.as_ref())
+        .map(|source| Resource::Server(source.id));
+    application.resources().into_iter().chain(sources).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Identities;
+    use crate::utils::{
+        empty_server, empty_snapshot, generate_application_list, Resource, ServerSpec,
+        DEFAULT_APP_LABEL,
+    };
+    use hcloud::models::{CreatedFrom, Image, ListImagesResponse, ListServersResponse, Server};
+    use std::collections::HashMap;
+
+    fn list(servers: Vec<Server>, images: Vec<Image>) -> Vec<crate::utils::Application> {
+        let servers = ListServersResponse {
+            meta: None,
+            servers,
+        };
+        let images = ListImagesResponse { meta: None, images };
+        generate_application_list(&servers, &images, DEFAULT_APP_LABEL)
+    }
+
+    #[test]
+    fn crunch_and_restore() {
+        let mut identities = Identities::default();
+        let server = Server {
+            id: 1,
+            name: "game".to_string(),
+            ..empty_server()
+        };
+        let mut applications = list(vec![server.clone()], vec![]);
+        identities.apply(&mut applications, true);
+        assert_eq!(applications[0].id(), "server:1");
+
+        // Crunched without a refresh in between, the snapshot is labeled by the crunch
+        let snapshot = Image {
+            id: 2,
+            created_from: Some(Box::new(CreatedFrom {
+                id: 1,
+                name: "game".to_string(),
+            })),
+            labels: ServerSpec::from(&server).labels(DEFAULT_APP_LABEL, "game"),
+            ..empty_snapshot()
+        };
+        let mut applications = list(vec![], vec![snapshot.clone()]);
+        assert_eq!(applications[0].id(), "label:game");
+        identities.apply(&mut applications, true);
+        assert_eq!(applications[0].id(), "server:1");
+
+        // Restored servers are labeled and would be identified by their label otherwise
+        let restored = Server {
+            id: 3,
+            name: "game".to_string(),
+            labels: HashMap::from([(DEFAULT_APP_LABEL.to_string(), "game".to_string())]),
+            ..empty_server()
+        };
+        let mut applications = list(vec![restored.clone()], vec![snapshot]);
+        assert_eq!(applications[0].id(), "label:game");
+        identities.apply(&mut applications, true);
+        assert_eq!(applications[0].id(), "server:1");
+
+        // The snapshot may be pruned once the server is back
+        let mut applications = list(vec![restored], vec![]);
+        identities.apply(&mut applications, true);
+        assert_eq!(applications[0].id(), "server:1");
+        let id = |id| {
+            identities
+                .ids
+                .get(&Resource::Server(id))
+                .map(String::as_str)
+        };
+        assert_eq!(id(3), Some("server:1"));
+        assert_eq!(id(1), None);
+    }
+
+    #[test]
+    fn partial() {
+        let mut identities = Identities::default();
+        let server = Server {
+            id: 1,
+            ..empty_server()
+        };
+        let mut applications = list(vec![server], vec![]);
+        identities.apply(&mut applications, true);
+
+        let mut applications = list(vec![], vec![]);
+        identities.apply(&mut applications, false);
+        let id = identities.ids.get(&Resource::Server(1));
+        assert_eq!(id.map(String::as_str), Some("server:1"));
+    }
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use hcloud::models::{Metrics, MetricsTimeSeriesValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Metric types requested for idle detection
+pub const METRIC_TYPES: &str = "cpu,network";
+/// Resolution of the requested metrics in seconds
+pub const METRICS_STEP: i64 = 60;
+
+/// CPU and network usage of a server at one point in time
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Sample {
+    pub at: DateTime<Utc>,
+    /// Percent, 100 per core
+    pub cpu: f64,
+    /// Bytes per second of all interfaces, in and out combined
+    pub network: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ServerMetrics {
+    pub server: i32,
+    /// Seconds each sample covers
+    pub step: i64,
+    /// Oldest first
+    pub samples: Vec<Sample>,
+}
+impl ServerMetrics {
+    /// Combines the CPU series with the bandwidth of every network interface. Samples
+    /// without a value, e.g. while the server was off, are left out.
+    pub fn from_metrics(server: i32, metrics: &Metrics) -> Result<Self, String> {
+        if !metrics.time_series.contains_key("cpu") {
+            return Err(format!("No CPU metrics for server {}", server));
+        }
+        // CPU usage and bandwidth by timestamp
+        let mut samples: BTreeMap<i64, (Option<f64>, f64)> = BTreeMap::new();
+        for (name, series) in &metrics.time_series {
+            let cpu = name == "cpu";
+            if !(cpu || name.starts_with("network.") && name.contains(".bandwidth.")) {
+                continue;
+            }
+            for (at, value) in series.values.iter().filter_map(|value| parse_value(value)) {
+                let sample = samples.entry(at).or_default();
+                match cpu {
+                    true => sample.0 = Some(value),
+                    false => sample.1 += value,
+                }
+            }
+        }
+        let samples = samples
+            .into_iter()
+            .filter_map(|(at, (cpu, network))| {
+                Some(Sample {
+                    // Samples at out of range timestamps are dropped
+                    at: Utc.timestamp_opt(at, 0).single()?,
+                    cpu: cpu?,
+                    network,
+                })
+            })
+            .collect();
+        Ok(Self {
+            server,
+            step: metrics.step as i64,
+            samples,
+        })
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.last()
+    }
+}
+
+/// Timestamp and value of a sample, values are sent as strings
+fn parse_value(value: &[MetricsTimeSeriesValue]) -> Option<(i64, f64)> {
+    let at = value.first()?.as_f64()? as i64;
+    let value = value.get(1)?;
+    let value = match value.as_str() {
+        Some(value) => value.parse().ok()?,
+        None => value.as_f64()?,
+    };
+    f64::is_finite(value).then(|| (at, value))
+}
+
+/// When an application counts as unused
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct IdleRule {
+    /// Percent
+    pub max_cpu: f64,
+    /// Kilobytes per second, in and out combined
+    pub max_network: f64,
+    pub minutes: u32,
+    /// Crunch without asking instead of proposing it
+    pub automatic: bool,
+}
+impl Default for IdleRule {
+    fn default() -> Self {
+        Self {
+            max_cpu: 5.0,
+            max_network: 10.0,
+            minutes: 60,
+            automatic: false,
+        }
+    }
+}
+impl IdleRule {
+    fn is_quiet(&self, sample: &Sample) -> bool {
+        sample.cpu < self.max_cpu && sample.network < self.max_network * 1000.0
+    }
+
+    /// How long every server has been below the thresholds up to its latest sample,
+    /// `None` without samples to tell
+    pub fn idle_for(&self, metrics: &[ServerMetrics]) -> Option<Duration> {
+        let mut idle: Option<Duration> = None;
+        for server in metrics {
+            let latest = server.latest()?;
+            let quiet = server
+                .samples
+                .iter()
+                .rev()
+                .take_while(|sample| self.is_quiet(sample))
+                .last();
+            let server_idle = match quiet {
+                Some(since) => latest.at - since.at + Duration::seconds(server.step),
+                None => Duration::zero(),
+            };
+            idle = Some(idle.map_or(server_idle, |idle| idle.min(server_idle)));
+        }
+        idle
+    }
+
+    pub fn is_idle(&self, metrics: &[ServerMetrics]) -> bool {
+        self.idle_for(metrics)
+            .map_or(false, |idle| idle >= Duration::minutes(self.minutes as i64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IdleRule, ServerMetrics};
+    use hcloud::models::{Metrics, MetricsTimeSeries, MetricsTimeSeriesValue};
+    use std::collections::HashMap;
+
+    fn series(values: &[(i64, &str)]) -> MetricsTimeSeries {
+        let values = values
+            .iter()
+            .map(|(at, value)| {
+                vec![
+                    MetricsTimeSeriesValue::from(*at),
+                    MetricsTimeSeriesValue::from(*value),
+                ]
+            })
+            .collect();
+        MetricsTimeSeries::new(values)
+    }
+
+    /// One sample per minute for the given CPU usages, with 1 kB/s in and out
+    fn metrics(cpu: &[&str]) -> Metrics {
+        let cpu: Vec<(i64, &str)> = cpu
+            .iter()
+            .enumerate()
+            .map(|(minute, value)| (minute as i64 * 60, *value))
+            .collect();
+        let network: Vec<(i64, &str)> = cpu.iter().map(|(at, _)| (*at, "1000")).collect();
+        let time_series = HashMap::from([
+            ("cpu".to_string(), series(&cpu)),
+            ("network.0.bandwidth.in".to_string(), series(&network)),
+            ("network.0.bandwidth.out".to_string(), series(&network)),
+            ("network.0.pps.in".to_string(), series(&network)),
+        ]);
+        Metrics::new("".to_string(), "".to_string(), 60, time_series)
+    }
+
+    #[test]
+    fn from_metrics() {
+        let parsed = ServerMetrics::from_metrics(1, &metrics(&["3.5", "NaN", "1"])).unwrap();
+        assert_eq!(parsed.samples.len(), 2);
+        let latest = parsed.latest().unwrap();
+        assert_eq!(latest.at.timestamp(), 120);
+        assert_eq!(latest.cpu, 1.0);
+        assert_eq!(latest.network, 2000.0);
+
+        let mut no_cpu = metrics(&["1"]);
+        no_cpu.time_series.remove("cpu");
+        assert!(ServerMetrics::from_metrics(1, &no_cpu).is_err());
+
+        let mut out_of_range = metrics(&["1"]);
+        let cpu = series(&[(0, "1"), (i64::MAX, "2")]);
+        out_of_range.time_series.insert("cpu".to_string(), cpu);
+        let parsed = ServerMetrics::from_metrics(1, &out_of_range).unwrap();
+        assert_eq!(parsed.samples.len(), 1);
+    }
+
+    #[test]
+    fn idle_for() {
+        let rule = IdleRule {
+            minutes: 3,
+            ..Default::default()
+        };
+        let busy = ServerMetrics::from_metrics(1, &metrics(&["1", "50", "1", "2"])).unwrap();
+        let quiet = ServerMetrics::from_metrics(2, &metrics(&["1", "1", "1", "1"])).unwrap();
+        let both = [quiet, busy];
+        assert_eq!(rule.idle_for(&both[..1]).unwrap().num_minutes(), 4);
+        assert!(rule.is_idle(&both[..1]));
+        // Every server has to be idle
+        assert_eq!(rule.idle_for(&both).unwrap().num_minutes(), 2);
+        assert!(!rule.is_idle(&both[1..]));
+
+        let chatty = IdleRule {
+            max_network: 1.0,
+            ..rule
+        };
+        assert!(!chatty.is_idle(&both[..1]));
+        assert_eq!(rule.idle_for(&[]), None);
+    }
+}
@@ -0,0 +1,182 @@
+use super::{Step, Timestamp, WorkflowUpdate};
+use serde::{Deserialize, Serialize};
+
+/// Storage key of the journal, kept apart from the app state so it can be written on its own
+pub const JOURNAL_KEY: &str = "workflow_journal";
+
+/// Record of the last step a workflow entered for a single server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub origin: String,
+    pub server_id: i32,
+    pub step: Step,
+    pub image_id: Option<i32>,
+    pub action_id: Option<i32>,
+    pub updated_at: Timestamp,
+}
+
+/// What to do with a workflow that was interrupted by the app closing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// The server was stopped, but no snapshot is known: power it back on
+    RollBack { server_id: i32 },
+    /// A snapshot was requested: wait for it to be confirmed before deleting the server
+    ConfirmAndDelete {
+        server_id: i32,
+        image_id: i32,
+        action_id: Option<i32>,
+    },
+    /// Deletion was already requested and must not be sent again
+    VerifyDeleted { server_id: i32 },
+    /// A server was created from a snapshot: wait for it to run
+    AwaitRunning { server_id: i32 },
+}
+
+impl JournalEntry {
+    pub fn resume(&self) -> Resume {
+        let server_id = self.server_id;
+        match (self.step, self.image_id) {
+            (Step::Snapshot | Step::AwaitSnapshot, Some(image_id)) => Resume::ConfirmAndDelete {
+                server_id,
+                image_id,
+                action_id: self.action_id,
+            },
+            (
+                Step::Rcon
+                | Step::Hooks
+                | Step::Shutdown
+                | Step::AwaitOff
+                | Step::Snapshot
+                | Step::AwaitSnapshot,
+                _,
+            ) => Resume::RollBack { server_id },
+            (Step::Delete | Step::Done, _) => Resume::VerifyDeleted { server_id },
+            (Step::Create | Step::AwaitRunning | Step::HealthCheck, _) => {
+                Resume::AwaitRunning { server_id }
+            }
+        }
+    }
+}
+
+/// Persisted log of running multi-step workflows
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+}
+impl Journal {
+    /// Records a step as entered, keeping image and action IDs of earlier steps
+    pub fn record(&mut self, origin: &str, update: &WorkflowUpdate) {
+        let action_id = update.action.as_ref().map(|action| action.id);
+        let existing = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.origin == origin && entry.server_id == update.server_id);
+
+        match existing {
+            Some(entry) => {
+                entry.step = update.step;
+                entry.image_id = update.image_id.or(entry.image_id);
+                entry.action_id = action_id.or(entry.action_id);
+                entry.updated_at = Timestamp::now();
+            }
+            None => self.entries.push(JournalEntry {
+                origin: origin.to_string(),
+                server_id: update.server_id,
+                step: update.step,
+                image_id: update.image_id,
+                action_id,
+                updated_at: Timestamp::now(),
+            }),
+        }
+    }
+
+    /// Removes all entries of a finished or aborted workflow
+    pub fn finish(&mut self, origin: &str) {
+        self.entries.retain(|entry| entry.origin != origin);
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Journal, Resume};
+    use crate::utils::{Step, WorkflowUpdate};
+    use hcloud::models::Action;
+
+    const ORIGIN: &str = "journaled";
+
+    fn action(id: i32) -> Action {
+        Action {
+            id,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn record() {
+        let mut journal = Journal::default();
+        journal.record(ORIGIN, &WorkflowUpdate::new(Step::Shutdown, 1));
+        journal.record(
+            ORIGIN,
+            &WorkflowUpdate::new(Step::Snapshot, 1)
+                .with_image(2)
+                .with_action(action(3)),
+        );
+        journal.record(
+            ORIGIN,
+            &WorkflowUpdate::new(Step::AwaitSnapshot, 1).with_image(2),
+        );
+        journal.record(ORIGIN, &WorkflowUpdate::new(Step::Shutdown, 4));
+
+        assert_eq!(journal.entries().len(), 2);
+        let entry = &journal.entries()[0];
+        assert_eq!(entry.step, Step::AwaitSnapshot);
+        assert_eq!(entry.image_id, Some(2));
+        assert_eq!(entry.action_id, Some(3));
+
+        journal.finish(ORIGIN);
+        assert!(journal.is_empty());
+    }
+
+    #[test]
+    fn resume() {
+        let mut journal = Journal::default();
+        let resume = |journal: &Journal| journal.entries()[0].resume();
+
+        journal.record(ORIGIN, &WorkflowUpdate::new(Step::AwaitOff, 1));
+        assert_eq!(resume(&journal), Resume::RollBack { server_id: 1 });
+
+        // A snapshot without a known image can't be confirmed
+        journal.record(ORIGIN, &WorkflowUpdate::new(Step::Snapshot, 1));
+        assert_eq!(resume(&journal), Resume::RollBack { server_id: 1 });
+
+        journal.record(
+            ORIGIN,
+            &WorkflowUpdate::new(Step::AwaitSnapshot, 1)
+                .with_image(2)
+                .with_action(action(3)),
+        );
+        assert_eq!(
+            resume(&journal),
+            Resume::ConfirmAndDelete {
+                server_id: 1,
+                image_id: 2,
+                action_id: Some(3)
+            }
+        );
+
+        journal.record(ORIGIN, &WorkflowUpdate::new(Step::Delete, 1));
+        assert_eq!(resume(&journal), Resume::VerifyDeleted { server_id: 1 });
+
+        journal.finish(ORIGIN);
+        journal.record(ORIGIN, &WorkflowUpdate::new(Step::Create, 5));
+        assert_eq!(resume(&journal), Resume::AwaitRunning { server_id: 5 });
+    }
+}
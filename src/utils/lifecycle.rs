@@ -0,0 +1,245 @@
+use super::{Application, Step, WorkflowUpdate};
+use hcloud::models::{image, server, Action};
+
+/// Where an application stands, computed from its servers, images and in-flight actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifecycle {
+    /// Servers are running, without health checks or before they passed
+    Running,
+    /// Running and every health check passed
+    Ready,
+    Stopped,
+    Crunching,
+    /// Only snapshots are left, taken from a known server
+    Crunched,
+    Restoring,
+    /// Servers disagree on their state, or something failed along the way
+    Degraded,
+    /// Snapshots that can't be traced back to any server
+    Orphaned,
+}
+impl Lifecycle {
+    pub fn of(
+        application: &Application,
+        workflow: Option<&WorkflowUpdate>,
+        actions: &[Action],
+    ) -> Self {
+        if let Some(update) = workflow {
+            return match update.step {
+                Step::Create | Step::AwaitRunning | Step::HealthCheck => Self::Restoring,
+                _ => Self::Crunching,
+            };
+        }
+        // Snapshots and servers requested outside of a workflow
+        for action in actions {
+            match action.command.as_str() {
+                "create_image" | "delete_server" => return Self::Crunching,
+                "create_server" => return Self::Restoring,
+                _ => (),
+            }
+        }
+
+        let servers = application.servers.iter().flatten();
+        let mut states = servers.map(|server| Self::of_server(server.status));
+        if let Some(first) = states.next() {
+            return match states.all(|state| state == first) {
+                true => first,
+                false => Self::Degraded,
+            };
+        }
+
+        let images: Vec<_> = application.images.iter().flatten().collect();
+        if images
+            .iter()
+            .any(|image| image.status == image::Status::Creating)
+        {
+            Self::Crunching
+        } else if !images
+            .iter()
+            .any(|image| image.status == image::Status::Available)
+        {
+            Self::Degraded
+        } else if images
+            .iter()
+            .any(|image| image.created_from.is_some() || !image.labels.is_empty())
+        {
+            Self::Crunched
+        } else {
+            Self::Orphaned
+        }
+    }
+
+    /// Running applications become ready once their health checks passed, and degraded
+    /// when they failed
+    pub fn with_health(self, healthy: Option<bool>) -> Self {
+        match (self, healthy) {
+            (Self::Running, Some(true)) => Self::Ready,
+            (Self::Running, Some(false)) => Self::Degraded,
+            (lifecycle, _) => lifecycle,
+        }
+    }
+
+    fn of_server(status: server::Status) -> Self {
+        use server::Status::*;
+        match status {
+            Running | Starting | Migrating | Rebuilding => Self::Running,
+            Off | Stopping => Self::Stopped,
+            Initializing => Self::Restoring,
+            Deleting => Self::Crunching,
+            Unknown => Self::Degraded,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Running => "Running",
+            Self::Ready => "Ready",
+            Self::Stopped => "Stopped",
+            Self::Crunching => "Crunching",
+            Self::Crunched => "Crunched",
+            Self::Restoring => "Restoring",
+            Self::Degraded => "Degraded",
+            Self::Orphaned => "Orphaned",
+        }
+    }
+
+    /// Whether servers can be shut down, snapshotted and deleted
+    pub fn can_crunch(&self) -> bool {
+        matches!(
+            self,
+            Self::Running | Self::Ready | Self::Stopped | Self::Degraded
+        )
+    }
+
+    /// Whether a server can be provisioned from a snapshot
+    pub fn can_restore(&self) -> bool {
+        matches!(self, Self::Crunched | Self::Orphaned)
+    }
+
+    /// Whether power actions may be sent to the servers
+    pub fn can_power(&self) -> bool {
+        matches!(
+            self,
+            Self::Running | Self::Ready | Self::Stopped | Self::Degraded
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Lifecycle;
+    use crate::utils::{
+        empty_server, empty_snapshot, generate_application_list, Application, Step, WorkflowUpdate,
+        DEFAULT_APP_LABEL,
+    };
+    use hcloud::models::{
+        image, server, Action, CreatedFrom, Image, ListImagesResponse, ListServersResponse, Server,
+    };
+    use std::collections::HashMap;
+
+    fn application(servers: Vec<Server>, images: Vec<Image>) -> Application {
+        let servers = ListServersResponse {
+            meta: None,
+            servers,
+        };
+        let images = ListImagesResponse { meta: None, images };
+        generate_application_list(&servers, &images, DEFAULT_APP_LABEL)
+            .pop()
+            .unwrap()
+    }
+
+    fn labeled_server(id: i32, status: server::Status) -> Server {
+        Server {
+            id,
+            status,
+            labels: HashMap::from([(DEFAULT_APP_LABEL.to_string(), "app".to_string())]),
+            ..empty_server()
+        }
+    }
+
+    #[test]
+    fn servers() {
+        let running = labeled_server(1, server::Status::Running);
+        let off = labeled_server(2, server::Status::Off);
+        let lifecycle = |servers| Lifecycle::of(&application(servers, vec![]), None, &[]);
+
+        assert_eq!(lifecycle(vec![running.clone()]), Lifecycle::Running);
+        assert_eq!(lifecycle(vec![off.clone()]), Lifecycle::Stopped);
+        assert_eq!(lifecycle(vec![running, off]), Lifecycle::Degraded);
+    }
+
+    #[test]
+    fn images() {
+        let crunched = Image {
+            created_from: Some(Box::new(CreatedFrom {
+                id: 1,
+                name: "crunched".to_string(),
+            })),
+            ..empty_snapshot()
+        };
+        let lifecycle = |image| Lifecycle::of(&application(vec![], vec![image]), None, &[]);
+
+        assert_eq!(lifecycle(crunched.clone()), Lifecycle::Crunched);
+        assert_eq!(lifecycle(empty_snapshot()), Lifecycle::Orphaned);
+        let creating = Image {
+            status: image::Status::Creating,
+            ..crunched.clone()
+        };
+        assert_eq!(lifecycle(creating), Lifecycle::Crunching);
+        let unavailable = Image {
+            status: image::Status::Unavailable,
+            ..crunched
+        };
+        assert_eq!(lifecycle(unavailable), Lifecycle::Degraded);
+    }
+
+    #[test]
+    fn in_flight() {
+        let application = application(vec![empty_server()], vec![]);
+        let crunching = WorkflowUpdate::new(Step::AwaitSnapshot, 0);
+        let restoring = WorkflowUpdate::new(Step::AwaitRunning, 0);
+        let snapshot = Action {
+            command: "create_image".to_string(),
+            ..Default::default()
+        };
+        let reboot = Action {
+            command: "reboot_server".to_string(),
+            ..Default::default()
+        };
+
+        let lifecycle = Lifecycle::of(&application, Some(&crunching), &[]);
+        assert_eq!(lifecycle, Lifecycle::Crunching);
+        assert!(!lifecycle.can_crunch());
+        assert!(!lifecycle.can_restore());
+        let lifecycle = Lifecycle::of(&application, Some(&restoring), &[]);
+        assert_eq!(lifecycle, Lifecycle::Restoring);
+        assert!(!lifecycle.can_power());
+
+        let snapshot = [snapshot];
+        assert_eq!(
+            Lifecycle::of(&application, None, &snapshot),
+            Lifecycle::Crunching
+        );
+        let lifecycle = Lifecycle::of(&application, None, &[reboot]);
+        assert_eq!(lifecycle, Lifecycle::Running);
+        assert!(lifecycle.can_crunch());
+
+        let checking = WorkflowUpdate::new(Step::HealthCheck, 0);
+        let lifecycle = Lifecycle::of(&application, Some(&checking), &[]);
+        assert_eq!(lifecycle.with_health(Some(true)), Lifecycle::Restoring);
+    }
+
+    #[test]
+    fn health() {
+        let running = labeled_server(1, server::Status::Running);
+        let lifecycle = Lifecycle::of(&application(vec![running], vec![]), None, &[]);
+        assert_eq!(lifecycle.with_health(None), Lifecycle::Running);
+        assert_eq!(lifecycle.with_health(Some(true)), Lifecycle::Ready);
+        assert_eq!(lifecycle.with_health(Some(false)), Lifecycle::Degraded);
+        assert!(Lifecycle::Ready.can_crunch() && Lifecycle::Ready.can_power());
+        assert_eq!(
+            Lifecycle::Stopped.with_health(Some(true)),
+            Lifecycle::Stopped
+        );
+    }
+}
@@ -1,15 +1,80 @@
 use chrono::Utc;
+use hcloud::models::{Action, Image};
+use std::collections::HashMap;
 
 mod timestamp;
 use serde::{Deserialize, Serialize};
 pub use timestamp::Timestamp;
 
+mod action_tracker;
+pub use action_tracker::ActionTracker;
+
 mod application;
-pub use application::{generate_application_list, Application};
+pub use application::{
+    generate_application_list, label_value, Application, Resource, RestoreSpec, ServerSpec,
+    DEFAULT_APP_LABEL,
+};
+
+pub mod cost;
+
+mod calendar;
+pub use calendar::CalendarSource;
+
+mod budget;
+pub use budget::{BudgetStatus, Budgets, OverBudget, Spend};
+
+mod idle;
+#[cfg(test)]
+pub use idle::Sample;
+pub use idle::{IdleRule, ServerMetrics, METRICS_STEP, METRIC_TYPES};
+
+mod health;
+pub use health::{HealthCheck, HealthChecks, HEALTH_ORIGIN};
+
+mod hooks;
+pub use hooks::{PreShutdown, ShutdownHooks};
+
+mod identities;
+pub use identities::Identities;
+
+mod journal;
+pub use journal::{Journal, JournalEntry, Resume, JOURNAL_KEY};
+
+mod overrides;
+pub use overrides::Overrides;
+
+mod lifecycle;
+pub use lifecycle::Lifecycle;
+
+mod plan;
+pub use plan::{Plan, PlanKind};
+
+mod query;
+pub use query::{GameQuery, Occupancy, QueryProtocol, ServerStatus, QUERY_ORIGIN};
+
+mod rcon;
+#[cfg(test)]
+pub use rcon::test::{fake_rcon_server, RCON_PASSWORD};
+pub use rcon::RconShutdown;
+
+mod retention;
+pub use retention::{PrunePreview, RetentionPolicy};
+
+mod savings;
+pub use savings::{SavingsLedger, Transition};
+
+mod schedule;
+pub use schedule::{Schedule, ScheduledAction, ScheduledRun, Schedules, Trigger, Zone};
 
 mod secret;
 pub use secret::{Key, Secret};
 
+mod wake;
+pub use wake::{WakeMode, WakeProxy};
+
+mod workflow;
+pub use workflow::{Step, WorkflowUpdate};
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RemoteData {
     pub data: Data,
@@ -30,9 +95,52 @@ impl RemoteData {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Data {
     Application(Vec<Application>),
+    /// Applications built from only one of servers or images, the other half failed to load
+    Partial {
+        applications: Vec<Application>,
+        error: String,
+    },
+    Action(Action),
+    /// An image that was changed without an action to track, e.g. a converted backup
+    Image(Image),
+    /// IDs of the snapshots deleted by a prune
+    Pruned(Vec<i32>),
+    Listing(ListProgress),
+    Workflow(WorkflowUpdate),
+    /// A scheduled crunch or restore is due
+    Scheduled(ScheduledRun),
+    /// Calendars of the schedules by application ID, after any of their files changed
+    Calendars(HashMap<String, CalendarSource>),
+    /// Recent metrics of every server of the application of the given ID
+    Metrics {
+        application: String,
+        metrics: Result<Vec<ServerMetrics>, String>,
+    },
+    /// A command sent to a game server over RCON along with its output
+    Rcon(String),
+    /// Who is online on every server of the application of the given ID
+    Players {
+        application: String,
+        statuses: Vec<(i32, Result<ServerStatus, String>)>,
+    },
+    /// A client connected to the proxy of the crunched application of the given ID
+    Wake(String),
+    /// Outcome of the health checks of the application of the given ID
+    Health {
+        application: String,
+        health: Result<(), String>,
+    },
     Error(String),
 }
 
+/// Number of items fetched so far while requesting a paginated list
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ListProgress {
+    pub what: String,
+    pub fetched: usize,
+    pub total: Option<usize>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Error {
     pub error: String,
@@ -50,4 +158,4 @@ impl Error {
 #[cfg(test)]
 mod testing;
 #[cfg(test)]
-pub use testing::{empty_server, empty_snapshot};
+pub use testing::{empty_server, empty_snapshot, prices};
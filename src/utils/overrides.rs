@@ -0,0 +1,217 @@
+use super::{Application, Resource};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Manual grouping of servers and images, for whenever labels and names don't tell the
+/// applications apart. Keyed by hcloud IDs so they survive renames.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Overrides {
+    servers: HashMap<i32, String>,
+    images: HashMap<i32, String>,
+}
+impl Overrides {
+    /// Moves a resource into the application of the given name, creating it if needed
+    pub fn assign(&mut self, resource: Resource, application: &str) {
+        let application = application.to_string();
+        match resource {
+            Resource::Server(id) => self.servers.insert(id, application),
+            Resource::Image(id) => self.images.insert(id, application),
+        };
+    }
+
+    /// Leaves a resource to the automatic grouping again
+    pub fn reset(&mut self, resource: Resource) {
+        match resource {
+            Resource::Server(id) => self.servers.remove(&id),
+            Resource::Image(id) => self.images.remove(&id),
+        };
+    }
+
+    pub fn get(&self, resource: Resource) -> Option<&str> {
+        match resource {
+            Resource::Server(id) => self.servers.get(&id),
+            Resource::Image(id) => self.images.get(&id),
+        }
+        .map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty() && self.images.is_empty()
+    }
+
+    /// Regroups applications, moving every overridden resource into its assigned application.
+    /// Applications left without any resources are dropped.
+    pub fn apply(&self, applications: Vec<Application>) -> Vec<Application> {
+        if self.is_empty() {
+            return applications;
+        }
+
+        let mut regrouped: Vec<Application> = Vec::new();
+        let mut moved_servers = Vec::new();
+        let mut moved_images = Vec::new();
+        let mut backups = Vec::new();
+        for application in applications {
+            let name = application.name.clone().unwrap_or_default();
+            let mut kept = Application::new().named(&name).with_id(application.id());
+            let target = |resource| self.get(resource).filter(|target| *target != name);
+
+            for server in application.servers.iter().flatten() {
+                match target(Resource::Server(server.id)) {
+                    Some(target) => moved_servers.push((target, server.clone())),
+                    None => kept.add_server(server),
+                }
+            }
+            for image in application.images.iter().flatten() {
+                match target(Resource::Image(image.id)) {
+                    Some(target) => moved_images.push((target, image.clone())),
+                    None => kept.add_image(image),
+                }
+            }
+
+            // Untouched applications keep their ID, so their windows stay in place
+            if kept.resources().len() == application.resources().len() {
+                regrouped.push(application);
+                continue;
+            }
+            // Backups follow their server wherever it was moved
+            backups.extend(application.backups.into_iter().flatten());
+            if !kept.resources().is_empty() {
+                regrouped.push(kept);
+            }
+        }
+
+        for (target, server) in moved_servers {
+            find_or_insert(&mut regrouped, target).add_server(&server);
+        }
+        for (target, image) in moved_images {
+            find_or_insert(&mut regrouped, target).add_image(&image);
+        }
+        for backup in backups {
+            let bound = regrouped.iter_mut().find(|app| {
+                app.servers
+                    .iter()
+                    .flatten()
+                    .any(|server| Some(server.id) == backup.bound_to)
+            });
+            if let Some(app) = bound {
+                app.add_backup(&backup);
+            }
+        }
+
+        regrouped
+    }
+}
+
+fn find_or_insert<'a>(applications: &'a mut Vec<Application>, name: &str) -> &'a mut Application {
+    match applications
+        .iter()
+        .position(|app| app.name.as_deref() == Some(name))
+    {
+        Some(index) => &mut applications[index],
+        None => {
+            let id = format!("name:{}", name);
+            applications.push(Application::new().named(name).with_id(&id));
+            applications.last_mut().unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Overrides;
+    use crate::utils::{
+        empty_server, empty_snapshot, generate_application_list, Resource, DEFAULT_APP_LABEL,
+    };
+    use hcloud::models::{Image, ListImagesResponse, ListServersResponse, Server};
+
+    fn applications() -> Vec<crate::utils::Application> {
+        let servers = ListServersResponse {
+            meta: None,
+            servers: vec![
+                Server {
+                    id: 1,
+                    name: "web".to_string(),
+                    ..empty_server()
+                },
+                Server {
+                    id: 2,
+                    name: "db".to_string(),
+                    ..empty_server()
+                },
+            ],
+        };
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![Image {
+                id: 3,
+                ..empty_snapshot()
+            }],
+        };
+        generate_application_list(&servers, &images, DEFAULT_APP_LABEL)
+    }
+
+    fn names(applications: &[crate::utils::Application]) -> Vec<String> {
+        applications
+            .iter()
+            .map(|app| app.name.clone().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn assign_orphan() {
+        let mut overrides = Overrides::default();
+        assert_eq!(overrides.apply(applications()).len(), 3);
+
+        overrides.assign(Resource::Image(3), "web");
+        let applications = overrides.apply(applications());
+        assert_eq!(names(&applications), vec!["web", "db"]);
+        assert_eq!(applications[0].resources().len(), 2);
+        assert_eq!(applications[0].images.clone().unwrap().len(), 1);
+
+        overrides.reset(Resource::Image(3));
+        assert!(overrides.is_empty());
+    }
+
+    #[test]
+    fn rename_and_merge() {
+        let mut overrides = Overrides::default();
+        overrides.assign(Resource::Server(1), "game");
+        overrides.assign(Resource::Server(2), "game");
+        let applications = overrides.apply(applications());
+        assert_eq!(applications.len(), 2);
+        let game = applications
+            .iter()
+            .find(|app| app.name.as_deref() == Some("game"));
+        assert_eq!(game.unwrap().servers.clone().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn backups_follow_server() {
+        let mut applications = applications();
+        let backup = Image {
+            id: 4,
+            r#type: hcloud::models::image::Type::Backup,
+            bound_to: Some(2),
+            ..empty_snapshot()
+        };
+        applications[1].add_backup(&backup);
+
+        let mut overrides = Overrides::default();
+        overrides.assign(Resource::Server(2), "web");
+        let applications = overrides.apply(applications);
+        assert_eq!(names(&applications), vec!["web", "unnamed 3"]);
+        assert_eq!(applications[0].backups, Some(vec![backup]));
+    }
+
+    #[test]
+    fn split() {
+        let mut overrides = Overrides::default();
+        overrides.assign(Resource::Server(1), "game");
+        overrides.assign(Resource::Server(2), "game");
+        overrides.assign(Resource::Server(2), "database");
+        let applications = overrides.apply(applications());
+        assert_eq!(applications.len(), 3);
+        assert_eq!(overrides.get(Resource::Server(2)), Some("database"));
+        assert!(names(&applications).contains(&"database".to_string()));
+    }
+}
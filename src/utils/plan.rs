@@ -0,0 +1,444 @@
+use super::{cost, label_value, Application, PreShutdown, PrunePreview, RestoreSpec, ServerSpec};
+use hcloud::models::{Image, PricePerTime};
+
+/// What a plan will do once confirmed
+#[derive(Debug, Clone)]
+pub enum PlanKind {
+    Crunch(Application),
+    Restore(RestoreSpec),
+    /// Delete the snapshots of the given IDs
+    Prune(Vec<i32>),
+}
+
+/// A single request the endpoint is going to send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedCall {
+    pub method: &'static str,
+    pub path: String,
+    pub description: String,
+}
+impl PlannedCall {
+    fn new(method: &'static str, path: String, description: impl Into<String>) -> Self {
+        Self {
+            method,
+            path,
+            description: description.into(),
+        }
+    }
+}
+
+/// Dry run of a destructive operation, listing the calls it would make and what it would cost.
+/// Built from local application data only, so no endpoint needs to be configured.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub origin: String,
+    pub kind: PlanKind,
+    pub calls: Vec<PlannedCall>,
+    /// Change of the monthly net cost in EUR, `None` if prices are unknown
+    pub monthly_cost_change: Option<f64>,
+}
+impl Plan {
+    pub fn crunch(
+        origin: &str,
+        application: &Application,
+        app_label: &str,
+        prepare: &PreShutdown,
+    ) -> Self {
+        let mut calls = Vec::new();
+        let mut cost_change = Some(0.0);
+
+        // Every server is prepared before the first one is shut down
+        for server in application.servers.iter().flatten() {
+            calls.extend(pre_shutdown_calls(prepare, server.id, &server.name));
+        }
+
+        for server in application.servers.iter().flatten() {
+            let id = server.id;
+            let spec = ServerSpec::from(server);
+            calls.push(PlannedCall::new(
+                "POST",
+                format!("/servers/{}/actions/shutdown", id),
+                format!("Gracefully shut down server \"{}\"", server.name),
+            ));
+            calls.push(PlannedCall::new(
+                "GET",
+                format!("/servers/{}", id),
+                "Wait until the server is off",
+            ));
+            calls.push(PlannedCall::new(
+                "POST",
+                format!("/servers/{}/actions/create_image", id),
+                format!(
+                    "Create snapshot \"{}\" labeled {}",
+                    spec.name,
+                    format_labels(&spec, app_label, origin)
+                ),
+            ));
+            calls.push(PlannedCall::new(
+                "GET",
+                "/actions/{snapshot action}".to_string(),
+                "Wait until the snapshot is available",
+            ));
+            let backups = application
+                .backups
+                .iter()
+                .flatten()
+                .filter(|backup| backup.bound_to == Some(id))
+                .count();
+            let description = match backups {
+                0 => format!("Delete server \"{}\"", server.name),
+                n => format!(
+                    "Delete server \"{}\", its {} automatic backup(s) are deleted with it",
+                    server.name, n
+                ),
+            };
+            calls.push(PlannedCall::new(
+                "DELETE",
+                format!("/servers/{}", id),
+                description,
+            ));
+
+            // Snapshots are billed by their actual size, the disk size is an upper bound
+            let snapshot = server.primary_disk_size as f64 * cost::SNAPSHOT_PRICE_PER_GB;
+            cost_change = cost_change
+                .zip(cost::server_monthly(server))
+                .map(|(change, server)| change - server + snapshot);
+        }
+
+        Self {
+            origin: origin.to_string(),
+            kind: PlanKind::Crunch(application.clone()),
+            calls,
+            monthly_cost_change: cost_change,
+        }
+    }
+
+    /// The cost change includes the storage of the snapshot, which is only paid for instead of
+    /// the server while it's crunched
+    pub fn restore(
+        origin: &str,
+        spec: &RestoreSpec,
+        snapshot: Option<&Image>,
+        app_label: &str,
+        prices: Option<&[PricePerTime]>,
+    ) -> Self {
+        let server = &spec.server;
+        let calls = vec![
+            PlannedCall::new(
+                "POST",
+                "/servers".to_string(),
+                format!(
+                    "Create server \"{}\" of type {} in {} from image {}, labeled {}={}",
+                    server.name,
+                    server.server_type,
+                    server.location,
+                    spec.image_id,
+                    app_label,
+                    label_value(origin)
+                ),
+            ),
+            PlannedCall::new(
+                "GET",
+                "/actions/{create action}".to_string(),
+                "Wait until the server is created and started",
+            ),
+            PlannedCall::new(
+                "GET",
+                "/servers/{new server}".to_string(),
+                "Wait until the server is running",
+            ),
+        ];
+
+        Self {
+            origin: origin.to_string(),
+            kind: PlanKind::Restore(spec.clone()),
+            calls,
+            monthly_cost_change: prices
+                .and_then(|prices| cost::monthly_price(prices, &server.location))
+                .zip(snapshot.and_then(cost::image_monthly))
+                .map(|(server, snapshot)| server - snapshot),
+        }
+    }
+}
+
+impl Plan {
+    pub fn prune(origin: &str, preview: &PrunePreview) -> Self {
+        let calls = preview
+            .prune
+            .iter()
+            .map(|image| {
+                let size = match image.image_size {
+                    Some(size) => format!("{:.1} GB", size),
+                    None => "unknown size".to_string(),
+                };
+                PlannedCall::new(
+                    "DELETE",
+                    format!("/images/{}", image.id),
+                    format!(
+                        "Delete snapshot \"{}\" from {} ({})",
+                        image.description, image.created, size
+                    ),
+                )
+            })
+            .collect();
+
+        Self {
+            origin: origin.to_string(),
+            kind: PlanKind::Prune(preview.image_ids()),
+            calls,
+            monthly_cost_change: Some(-preview.monthly_savings),
+        }
+    }
+}
+
+/// RCON commands and shutdown hooks run on a server before it is shut down
+fn pre_shutdown_calls(prepare: &PreShutdown, id: i32, name: &str) -> Vec<PlannedCall> {
+    let mut calls = Vec::new();
+    let host = prepare
+        .hosts
+        .get(&id)
+        .cloned()
+        .flatten()
+        .unwrap_or_else(|| "{no public IPv4}".to_string());
+    if let Some(rcon) = &prepare.rcon {
+        let address = format!("{}:{}", host, rcon.port);
+        let mut countdown = rcon.countdown.clone();
+        countdown.sort_unstable_by(|a, b| b.cmp(a));
+        countdown.dedup();
+        for remaining in countdown {
+            let broadcast = rcon.broadcast.replace("{}", &remaining.to_string());
+            let description = format!("Warn the players on \"{}\": {}", name, broadcast);
+            calls.push(PlannedCall::new("RCON", address.clone(), description));
+        }
+        for command in &rcon.commands {
+            let description = format!("Run \"{}\" on \"{}\"", command, name);
+            calls.push(PlannedCall::new("RCON", address.clone(), description));
+        }
+    }
+    if let Some(hooks) = &prepare.hooks {
+        let address = format!("{}@{}:{}", hooks.user, host, hooks.port);
+        for command in &hooks.commands {
+            let description = format!("Run shutdown hook \"{}\" on \"{}\"", command, name);
+            calls.push(PlannedCall::new("SSH", address.clone(), description));
+        }
+    }
+    calls
+}
+
+fn format_labels(spec: &ServerSpec, app_label: &str, application: &str) -> String {
+    let mut labels: Vec<String> = spec
+        .labels(app_label, application)
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    labels.sort();
+    labels.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Plan, PlanKind};
+    use crate::utils::{
+        empty_server, empty_snapshot, prices, Application, PreShutdown, RconShutdown, RestoreSpec,
+        RetentionPolicy, ServerSpec, ShutdownHooks, DEFAULT_APP_LABEL,
+    };
+    use hcloud::models::{Image, ListImagesResponse, ListServersResponse, Server};
+    use std::collections::HashMap;
+
+    fn application(servers: Vec<Server>) -> Application {
+        let servers = ListServersResponse {
+            meta: None,
+            servers,
+        };
+        let images = ListImagesResponse {
+            meta: None,
+            images: vec![],
+        };
+        crate::utils::generate_application_list(&servers, &images, DEFAULT_APP_LABEL)
+            .pop()
+            .unwrap()
+    }
+
+    #[test]
+    fn crunch() {
+        let mut server = empty_server();
+        server.id = 42;
+        server.name = "plannable".to_string();
+        server.primary_disk_size = 20;
+        server.datacenter.location.name = "nbg1".to_string();
+        server.server_type.prices = prices("nbg1", "4.5100000000");
+
+        let application = application(vec![server]);
+        let plan = Plan::crunch(
+            "origin",
+            &application,
+            DEFAULT_APP_LABEL,
+            &PreShutdown::default(),
+        );
+        assert!(matches!(plan.kind, PlanKind::Crunch(_)));
+        let paths: Vec<(&str, &str)> = plan
+            .calls
+            .iter()
+            .map(|call| (call.method, call.path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("POST", "/servers/42/actions/shutdown"),
+                ("GET", "/servers/42"),
+                ("POST", "/servers/42/actions/create_image"),
+                ("GET", "/actions/{snapshot action}"),
+                ("DELETE", "/servers/42"),
+            ]
+        );
+        let description = &plan.calls[2].description;
+        assert!(description.contains("cruncher.app=origin"));
+        assert!(description.contains("cruncher.name=plannable"));
+
+        let change = plan.monthly_cost_change.unwrap();
+        assert!((change - (20.0 * 0.0119 - 4.51)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn crunch_backups() {
+        let mut application = application(vec![empty_server()]);
+        let backup = Image {
+            bound_to: Some(0),
+            ..empty_snapshot()
+        };
+        application.backups = Some(vec![backup]);
+        let plan = Plan::crunch(
+            "origin",
+            &application,
+            DEFAULT_APP_LABEL,
+            &PreShutdown::default(),
+        );
+        let description = &plan.calls[4].description;
+        assert!(
+            description.contains("1 automatic backup(s) are deleted"),
+            "{}",
+            description
+        );
+    }
+
+    /// Servers are saved and prepared before any of them is shut down
+    #[test]
+    fn crunch_pre_shutdown() {
+        let server = |id| Server {
+            id,
+            name: "game".to_string(),
+            ..empty_server()
+        };
+        let application = application(vec![server(1), server(2)]);
+        let prepare = PreShutdown {
+            rcon: Some(RconShutdown {
+                countdown: vec![10, 60],
+                ..Default::default()
+            }),
+            hooks: Some(ShutdownHooks {
+                commands: vec!["systemctl stop game".to_string()],
+                ..Default::default()
+            }),
+            hosts: HashMap::from([(1, Some("10.0.0.1".to_string())), (2, None)]),
+        };
+        let plan = Plan::crunch("origin", &application, DEFAULT_APP_LABEL, &prepare);
+        let paths: Vec<(&str, &str)> = plan
+            .calls
+            .iter()
+            .take(11)
+            .map(|call| (call.method, call.path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("RCON", "10.0.0.1:25575"),
+                ("RCON", "10.0.0.1:25575"),
+                ("RCON", "10.0.0.1:25575"),
+                ("RCON", "10.0.0.1:25575"),
+                ("SSH", "root@10.0.0.1:22"),
+                ("RCON", "{no public IPv4}:25575"),
+                ("RCON", "{no public IPv4}:25575"),
+                ("RCON", "{no public IPv4}:25575"),
+                ("RCON", "{no public IPv4}:25575"),
+                ("SSH", "root@{no public IPv4}:22"),
+                ("POST", "/servers/1/actions/shutdown"),
+            ]
+        );
+        assert!(plan.calls[0].description.contains("in 60 seconds"));
+        assert!(plan.calls[1].description.contains("in 10 seconds"));
+        assert!(plan.calls[3].description.contains("\"stop\""));
+        assert!(plan.calls[4].description.contains("systemctl stop game"));
+    }
+
+    #[test]
+    fn crunch_unknown_price() {
+        let plan = Plan::crunch(
+            "origin",
+            &application(vec![empty_server()]),
+            DEFAULT_APP_LABEL,
+            &PreShutdown::default(),
+        );
+        assert_eq!(plan.monthly_cost_change, None);
+    }
+
+    #[test]
+    fn restore() {
+        let spec = RestoreSpec {
+            image_id: 7,
+            server: ServerSpec {
+                name: "restorable".to_string(),
+                server_type: "cx11".to_string(),
+                location: "fsn1".to_string(),
+            },
+        };
+        let snapshot = Image {
+            id: 7,
+            image_size: Some(20.0),
+            ..empty_snapshot()
+        };
+        let plan = Plan::restore("origin", &spec, Some(&snapshot), DEFAULT_APP_LABEL, None);
+        assert_eq!(plan.calls[0].path, "/servers");
+        assert!(plan.calls[0].description.contains("from image 7"));
+        assert!(plan.calls[0].description.contains("cruncher.app=origin"));
+        assert_eq!(plan.monthly_cost_change, None);
+
+        // The snapshot storage isn't paid for instead of the server anymore
+        let prices = prices("fsn1", "3.2900000000");
+        let plan = Plan::restore(
+            "origin",
+            &spec,
+            Some(&snapshot),
+            DEFAULT_APP_LABEL,
+            Some(&prices),
+        );
+        let change = plan.monthly_cost_change.unwrap();
+        assert!((change - (3.29 - 20.0 * 0.0119)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn prune() {
+        let snapshot = |id, created: &str| Image {
+            id,
+            created: created.to_string(),
+            image_size: Some(50.0),
+            ..empty_snapshot()
+        };
+        let images = vec![
+            snapshot(1, "2022-11-01T00:00:00+00:00"),
+            snapshot(2, "2022-10-01T00:00:00+00:00"),
+        ];
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            ..Default::default()
+        };
+        let preview = policy.preview(&images, chrono::Utc::now());
+
+        let plan = Plan::prune("origin", &preview);
+        assert!(matches!(plan.kind, PlanKind::Prune(ref ids) if ids == &[2]));
+        assert_eq!(plan.calls[0].method, "DELETE");
+        assert_eq!(plan.calls[0].path, "/images/2");
+        assert!(plan.calls[0].description.contains("50.0 GB"));
+        let change = plan.monthly_cost_change.unwrap();
+        assert!((change + 50.0 * 0.0119).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,437 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+};
+
+/// Origin of probe results, they aren't tied to any request of an application
+pub const QUERY_ORIGIN: &str = "query";
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+/// Status responses carry the server icon, anything larger is garbage
+const MAX_STATUS_SIZE: usize = 1024 * 1024;
+const A2S_INFO: &[u8] = b"\xFF\xFF\xFF\xFFTSource Engine Query\0";
+const A2S_CHALLENGE: u8 = 0x41;
+const A2S_INFO_RESPONSE: u8 = 0x49;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum QueryProtocol {
+    /// Server List Ping over TCP
+    Minecraft,
+    /// A2S_INFO over UDP, answered by Source engine and many other Steam games
+    Source,
+}
+impl QueryProtocol {
+    pub const ALL: [QueryProtocol; 2] = [QueryProtocol::Minecraft, QueryProtocol::Source];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            QueryProtocol::Minecraft => "Minecraft",
+            QueryProtocol::Source => "Source (A2S)",
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            QueryProtocol::Minecraft => 25565,
+            QueryProtocol::Source => 27015,
+        }
+    }
+}
+
+/// How to ask the game servers of an application who is online
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GameQuery {
+    pub protocol: QueryProtocol,
+    pub port: u16,
+    /// Crunch once every server has been empty this long
+    pub empty_minutes: Option<u32>,
+    /// Crunch without asking instead of proposing it
+    pub automatic: bool,
+}
+impl Default for GameQuery {
+    fn default() -> Self {
+        Self {
+            protocol: QueryProtocol::Minecraft,
+            port: QueryProtocol::Minecraft.default_port(),
+            empty_minutes: None,
+            automatic: false,
+        }
+    }
+}
+impl GameQuery {
+    pub async fn probe(&self, host: &str) -> Result<ServerStatus, String> {
+        let status = async {
+            match self.protocol {
+                QueryProtocol::Minecraft => server_list_ping(host, self.port).await,
+                QueryProtocol::Source => a2s_info(host, self.port).await,
+            }
+        };
+        let status = tokio::time::timeout(QUERY_TIMEOUT, status)
+            .await
+            .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()));
+        status.map_err(|e| format!("Failed to query {}:{}: {}", host, self.port, e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServerStatus {
+    /// Without bots
+    pub players: u32,
+    pub max_players: u32,
+    /// Message of the day, or the server name for Source games
+    pub motd: String,
+}
+
+fn invalid(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        match value {
+            0 => return buffer.push(byte),
+            _ => buffer.push(byte | 0x80),
+        }
+    }
+}
+
+async fn read_varint(stream: &mut TcpStream) -> io::Result<i32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = stream.read_u8().await?;
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(invalid("VarInt too long"))
+}
+
+/// Reads a VarInt from the start of a buffer, along with its length in bytes
+fn parse_varint(buffer: &[u8]) -> io::Result<(i32, usize)> {
+    let mut value = 0u32;
+    for (i, byte) in buffer.iter().take(5).enumerate() {
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value as i32, i + 1));
+        }
+    }
+    Err(invalid("truncated VarInt"))
+}
+
+fn framed(packet: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(packet.len() + 5);
+    write_varint(&mut framed, packet.len() as i32);
+    framed.extend(packet);
+    framed
+}
+
+async fn server_list_ping(host: &str, port: u16) -> io::Result<ServerStatus> {
+    let mut stream = TcpStream::connect((host, port)).await?;
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, 0x00);
+    // Any protocol version will do to ask for the status
+    write_varint(&mut handshake, -1);
+    write_varint(&mut handshake, host.len() as i32);
+    handshake.extend(host.as_bytes());
+    handshake.extend(port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+    stream.write_all(&framed(&handshake)).await?;
+    stream.write_all(&framed(&[0x00])).await?;
+
+    let size = read_varint(&mut stream).await?;
+    let size = usize::try_from(size).map_err(|_| invalid("negative status size"))?;
+    if size > MAX_STATUS_SIZE {
+        return Err(invalid("status response too large"));
+    }
+    let mut packet = vec![0; size];
+    stream.read_exact(&mut packet).await?;
+    parse_status(status_json(&packet)?)
+}
+
+/// JSON string of a status response packet
+fn status_json(packet: &[u8]) -> io::Result<&[u8]> {
+    let (id, id_size) = parse_varint(packet)?;
+    if id != 0x00 {
+        return Err(invalid("unexpected status packet"));
+    }
+    let (length, length_size) = parse_varint(&packet[id_size..])?;
+    let length = usize::try_from(length).map_err(|_| invalid("negative status length"))?;
+    let start = id_size + length_size;
+    let end = start
+        .checked_add(length)
+        .ok_or_else(|| invalid("status length out of range"))?;
+    packet
+        .get(start..end)
+        .ok_or_else(|| invalid("truncated status"))
+}
+
+fn parse_status(json: &[u8]) -> io::Result<ServerStatus> {
+    let status: serde_json::Value = serde_json::from_slice(json)?;
+    let count = |key: &str| status["players"][key].as_u64().unwrap_or_default() as u32;
+    Ok(ServerStatus {
+        players: count("online"),
+        max_players: count("max"),
+        motd: strip_formatting(&chat_text(&status["description"])),
+    })
+}
+
+/// Plain text of a chat component, which is either a string or an object with children
+fn chat_text(component: &serde_json::Value) -> String {
+    if let Some(text) = component.as_str() {
+        return text.to_string();
+    }
+    let mut text = component["text"].as_str().unwrap_or_default().to_string();
+    for child in component["extra"].as_array().into_iter().flatten() {
+        text.push_str(&chat_text(child));
+    }
+    text
+}
+
+/// Removes `§` color and style codes
+fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '§' => {
+                chars.next();
+            }
+            c => stripped.push(c),
+        }
+    }
+    stripped.trim().to_string()
+}
+
+async fn a2s_info(host: &str, port: u16) -> io::Result<ServerStatus> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect((host, port)).await?;
+    let mut buffer = [0; 1400];
+    socket.send(A2S_INFO).await?;
+    let mut size = socket.recv(&mut buffer).await?;
+    // Newer servers ask to repeat the request with a challenge, against spoofed addresses
+    if size >= 9 && buffer[4] == A2S_CHALLENGE {
+        let mut request = A2S_INFO.to_vec();
+        request.extend(&buffer[5..9]);
+        socket.send(&request).await?;
+        size = socket.recv(&mut buffer).await?;
+    }
+    parse_info(&buffer[..size])
+}
+
+fn parse_info(response: &[u8]) -> io::Result<ServerStatus> {
+    if response.len() < 6 || response[..4] != [0xFF; 4] || response[4] != A2S_INFO_RESPONSE {
+        return Err(invalid("unexpected A2S_INFO response"));
+    }
+    // Protocol version, then the name, map, folder and game as null terminated strings
+    let mut strings = response[6..].splitn(5, |byte| *byte == 0);
+    let name = String::from_utf8_lossy(strings.next().unwrap_or_default()).into_owned();
+    let rest = strings
+        .nth(3)
+        .ok_or_else(|| invalid("truncated A2S_INFO response"))?;
+    // Steam app ID, then players, max players and bots
+    match rest.get(2..5) {
+        Some([players, max_players, bots]) => Ok(ServerStatus {
+            players: players.saturating_sub(*bots) as u32,
+            max_players: *max_players as u32,
+            motd: name,
+        }),
+        _ => Err(invalid("truncated A2S_INFO response")),
+    }
+}
+
+/// Latest probe results of the servers of an application, and since when nobody is online
+#[derive(Debug, Clone, Default)]
+pub struct Occupancy {
+    /// By server ID
+    pub statuses: Vec<(i32, Result<ServerStatus, String>)>,
+    empty_since: Option<DateTime<Utc>>,
+}
+impl Occupancy {
+    /// Servers that didn't answer may be down or still starting, they neither count as
+    /// empty nor as occupied
+    pub fn observe(
+        &mut self,
+        statuses: Vec<(i32, Result<ServerStatus, String>)>,
+        now: DateTime<Utc>,
+    ) {
+        let answered: Vec<&ServerStatus> = statuses
+            .iter()
+            .filter_map(|(_, status)| status.as_ref().ok())
+            .collect();
+        if answered.iter().any(|status| status.players > 0) {
+            self.empty_since = None;
+        } else if !answered.is_empty() && answered.len() == statuses.len() {
+            self.empty_since = self.empty_since.or(Some(now));
+        }
+        self.statuses = statuses;
+    }
+
+    pub fn players(&self) -> u32 {
+        self.answered().map(|status| status.players).sum()
+    }
+
+    pub fn max_players(&self) -> u32 {
+        self.answered().map(|status| status.max_players).sum()
+    }
+
+    fn answered(&self) -> impl Iterator<Item = &ServerStatus> {
+        self.statuses
+            .iter()
+            .filter_map(|(_, status)| status.as_ref().ok())
+    }
+
+    pub fn empty_for(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.empty_since.map(|since| now - since)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        framed, parse_info, parse_status, read_varint, write_varint, GameQuery, Occupancy,
+        QueryProtocol, ServerStatus, A2S_INFO,
+    };
+    use chrono::{Duration, TimeZone, Utc};
+    use std::io;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, UdpSocket},
+    };
+
+    const STATUS: &str = r#"{"version":{"name":"1.19.2","protocol":760},
+        "players":{"max":20,"online":3},
+        "description":{"text":"§aCrunchy ","extra":[{"text":"§lcraft"}]}}"#;
+
+    /// Info response of a server named "Crunchy" with 5 players, 2 of them bots
+    fn info() -> Vec<u8> {
+        let mut info = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x49, 17];
+        info.extend(b"Crunchy\0de_dust2\0csgo\0Counter-Strike\0");
+        info.extend([218, 2, 5, 16, 2]);
+        info.extend(b"dl\0");
+        info
+    }
+
+    #[tokio::test]
+    async fn server_list_ping() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // Handshake and status request
+            for _ in 0..2 {
+                let size = read_varint(&mut stream).await.unwrap();
+                let mut packet = vec![0; size as usize];
+                stream.read_exact(&mut packet).await.unwrap();
+            }
+            let mut response = vec![0x00];
+            write_varint(&mut response, STATUS.len() as i32);
+            response.extend(STATUS.as_bytes());
+            stream.write_all(&framed(&response)).await.unwrap();
+        });
+
+        let query = GameQuery {
+            port,
+            ..Default::default()
+        };
+        let status = query.probe("127.0.0.1").await.unwrap();
+        assert_eq!(
+            status,
+            ServerStatus {
+                players: 3,
+                max_players: 20,
+                motd: "Crunchy craft".to_string()
+            }
+        );
+        assert_eq!(parse_status(br#"{"description":"Hi"}"#).unwrap().motd, "Hi");
+    }
+
+    #[test]
+    fn status_json() {
+        let mut packet = vec![0x00];
+        write_varint(&mut packet, 2);
+        packet.extend(b"{}");
+        assert_eq!(super::status_json(&packet).unwrap(), b"{}");
+
+        // Lengths that are negative or beyond the packet are refused
+        for length in [-1, i32::MAX, 3] {
+            let mut packet = vec![0x00];
+            write_varint(&mut packet, length);
+            packet.extend(b"{}");
+            let error = super::status_json(&packet).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[tokio::test]
+    async fn a2s_info() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buffer = [0; 1400];
+            let (size, from) = socket.recv_from(&mut buffer).await.unwrap();
+            assert_eq!(&buffer[..size], A2S_INFO);
+            let challenge = [0xFF, 0xFF, 0xFF, 0xFF, 0x41, 1, 2, 3, 4];
+            socket.send_to(&challenge, from).await.unwrap();
+            let (size, from) = socket.recv_from(&mut buffer).await.unwrap();
+            assert_eq!(buffer[size - 4..size], [1, 2, 3, 4]);
+            socket.send_to(&info(), from).await.unwrap();
+        });
+
+        let query = GameQuery {
+            protocol: QueryProtocol::Source,
+            port,
+            ..Default::default()
+        };
+        let status = query.probe("127.0.0.1").await.unwrap();
+        assert_eq!(status.players, 3);
+        assert_eq!(status.max_players, 16);
+        assert_eq!(status.motd, "Crunchy");
+        assert!(parse_info(&info()[..20]).is_err());
+    }
+
+    #[tokio::test]
+    async fn unreachable() {
+        // Nothing answers on the discard port, the probe is refused or times out
+        let query = GameQuery {
+            protocol: QueryProtocol::Source,
+            port: 9,
+            ..Default::default()
+        };
+        assert!(query.probe("127.0.0.1").await.is_err());
+    }
+
+    #[test]
+    fn occupancy() {
+        let status = |players| {
+            Ok(ServerStatus {
+                players,
+                max_players: 10,
+                motd: String::new(),
+            })
+        };
+        let start = Utc.with_ymd_and_hms(2022, 10, 10, 20, 0, 0).unwrap();
+        let mut occupancy = Occupancy::default();
+        occupancy.observe(vec![(1, status(2)), (2, status(1))], start);
+        assert_eq!(occupancy.players(), 3);
+        assert_eq!(occupancy.max_players(), 20);
+        assert_eq!(occupancy.empty_for(start), None);
+
+        occupancy.observe(vec![(1, status(0)), (2, status(0))], start);
+        let later = start + Duration::minutes(10);
+        // Unanswered probes leave the count alone
+        occupancy.observe(vec![(1, status(0)), (2, Err("down".to_string()))], later);
+        assert_eq!(occupancy.empty_for(later), Some(Duration::minutes(10)));
+        assert_eq!(occupancy.players(), 0);
+
+        occupancy.observe(vec![(1, status(1)), (2, Err("down".to_string()))], later);
+        assert_eq!(occupancy.empty_for(later), None);
+        occupancy.observe(vec![(1, Err("down".to_string()))], later);
+        assert_eq!(occupancy.empty_for(later), None);
+    }
+}
@@ -0,0 +1,358 @@
+use super::{Key, Secret};
+use serde::{Deserialize, Serialize};
+use std::{future::Future, io, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+// Packet types of the Source RCON protocol, the same numbers mean different things each way
+const AUTH: i32 = 3;
+const AUTH_RESPONSE: i32 = 2;
+const EXEC_COMMAND: i32 = 2;
+const RESPONSE_VALUE: i32 = 0;
+/// ID and type fields, and the two terminating null bytes
+const PACKET_OVERHEAD: i32 = 10;
+/// Guards against reading garbage as a length, servers split long responses at 4 kB
+const MAX_PACKET_SIZE: i32 = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+/// Client of the Source RCON protocol, as spoken by Source engine and Minecraft servers
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+    timeout: Duration,
+}
+impl RconClient {
+    pub async fn connect(address: &str, password: &str, timeout: Duration) -> Result<Self, String> {
+        let stream = with_timeout(timeout, TcpStream::connect(address))
+            .await
+            .map_err(|e| format!("Failed to connect to RCON at {}: {}", address, e))?;
+        let mut client = Self {
+            stream,
+            next_id: 1,
+            timeout,
+        };
+        let id = client.next_id();
+        let authenticated = async {
+            client.send(id, AUTH, password).await?;
+            // Source servers send an empty response value ahead of the auth response
+            loop {
+                match client.receive().await? {
+                    Some(packet) if packet.kind == AUTH_RESPONSE => return Ok(packet.id == id),
+                    Some(_) => continue,
+                    None => return Err(io::ErrorKind::UnexpectedEof.into()),
+                }
+            }
+        };
+        match with_timeout(timeout, authenticated).await {
+            Ok(true) => Ok(client),
+            Ok(false) => Err(format!("RCON at {} refused the password", address)),
+            Err(e) => Err(format!("Failed to log in to RCON at {}: {}", address, e)),
+        }
+    }
+
+    /// Runs a command and returns its output, or `None` if the server closed the
+    /// connection instead of answering, as Minecraft does on `stop`
+    pub async fn exec(&mut self, command: &str) -> Result<Option<String>, String> {
+        let id = self.next_id();
+        let timeout = self.timeout;
+        let response = async {
+            self.send(id, EXEC_COMMAND, command).await?;
+            loop {
+                match self.receive().await? {
+                    Some(packet) if packet.id == id && packet.kind == RESPONSE_VALUE => {
+                        return Ok(Some(packet.body))
+                    }
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            }
+        };
+        with_timeout(timeout, response)
+            .await
+            .map_err(|e| format!("RCON command \"{}\" failed: {}", command, e))
+    }
+
+    fn next_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        id
+    }
+
+    async fn send(&mut self, id: i32, kind: i32, body: &str) -> io::Result<()> {
+        self.stream.write_all(&encode(id, kind, body)).await
+    }
+
+    /// `None` once the server closed the connection between two packets
+    async fn receive(&mut self) -> io::Result<Option<Packet>> {
+        let mut size = [0; 4];
+        match self.stream.read_exact(&mut size).await {
+            Ok(_) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let size = i32::from_le_bytes(size);
+        if !(PACKET_OVERHEAD..=MAX_PACKET_SIZE).contains(&size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid packet size {}", size),
+            ));
+        }
+        let mut packet = vec![0; size as usize];
+        self.stream.read_exact(&mut packet).await?;
+        Ok(Some(decode(&packet)))
+    }
+}
+
+fn encode(id: i32, kind: i32, body: &str) -> Vec<u8> {
+    let size = body.len() as i32 + PACKET_OVERHEAD;
+    let mut packet = Vec::with_capacity(size as usize + 4);
+    packet.extend(size.to_le_bytes());
+    packet.extend(id.to_le_bytes());
+    packet.extend(kind.to_le_bytes());
+    packet.extend(body.as_bytes());
+    packet.extend([0, 0]);
+    packet
+}
+
+/// Decodes a packet without its size, which has at least the fixed overhead
+fn decode(packet: &[u8]) -> Packet {
+    let field = |at: usize| {
+        i32::from_le_bytes([packet[at], packet[at + 1], packet[at + 2], packet[at + 3]])
+    };
+    let body = &packet[8..packet.len() - 2];
+    let body = body.split(|byte| *byte == 0).next().unwrap_or_default();
+    Packet {
+        id: field(0),
+        kind: field(4),
+        body: String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    future: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+}
+
+/// Game server commands sent over RCON before the servers of an application are shut down,
+/// after warning the players with a countdown
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RconShutdown {
+    pub port: u16,
+    /// Encrypted while stored
+    pub password: Secret,
+    /// Seconds before the shutdown at which the players are warned
+    pub countdown: Vec<u32>,
+    /// Sent at every warning, with `{}` replaced by the remaining seconds
+    pub broadcast: String,
+    /// Sent after the countdown, the last one usually stops the server
+    pub commands: Vec<String>,
+    /// Per command, including connecting
+    pub timeout_secs: u64,
+}
+impl Default for RconShutdown {
+    fn default() -> Self {
+        Self {
+            port: 25575,
+            password: Secret::Unencrypted(Key::default()),
+            countdown: vec![60, 30, 10],
+            broadcast: "say Server shutting down in {} seconds".to_string(),
+            commands: vec!["save-all".to_string(), "stop".to_string()],
+            timeout_secs: 10,
+        }
+    }
+}
+impl RconShutdown {
+    pub async fn connect(&self, host: &str) -> Result<RconClient, String> {
+        let password = Key::from(self.password.clone()).0;
+        let address = format!("{}:{}", host, self.port);
+        RconClient::connect(&address, &password, Duration::from_secs(self.timeout_secs)).await
+    }
+
+    /// Runs a single command, e.g. from the console, with any failure as its output
+    pub async fn send(&self, host: &str, command: &str) -> String {
+        let mut client = match self.connect(host).await {
+            Ok(client) => client,
+            Err(e) => return e,
+        };
+        match client.exec(command).await {
+            Ok(Some(response)) => format_output(command, &response),
+            Ok(None) => format!("> {}\nConnection closed", command),
+            Err(e) => e,
+        }
+    }
+
+    /// Counts down and runs the commands, handing every command and its output to `output`
+    pub async fn run(&self, host: &str, mut output: impl FnMut(String)) -> Result<(), String> {
+        let mut client = self.connect(host).await?;
+        let mut countdown = self.countdown.clone();
+        countdown.sort_unstable_by(|a, b| b.cmp(a));
+        countdown.dedup();
+        for (i, remaining) in countdown.iter().enumerate() {
+            let broadcast = self.broadcast.replace("{}", &remaining.to_string());
+            match client.exec(&broadcast).await? {
+                Some(response) => output(format_output(&broadcast, &response)),
+                None => return Err(closed(host, &broadcast)),
+            }
+            let next = countdown.get(i + 1).copied().unwrap_or_default();
+            tokio::time::sleep(Duration::from_secs((remaining - next) as u64)).await;
+        }
+
+        for (i, command) in self.commands.iter().enumerate() {
+            match client.exec(command).await? {
+                Some(response) => output(format_output(command, &response)),
+                None if i + 1 == self.commands.len() => {
+                    output(format!("> {}\nConnection closed", command))
+                }
+                None => return Err(closed(host, command)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn closed(host: &str, command: &str) -> String {
+    format!("RCON at {} closed the connection on \"{}\"", host, command)
+}
+
+fn format_output(command: &str, response: &str) -> String {
+    match response.trim_end() {
+        "" => format!("> {}", command),
+        response => format!("> {}\n{}", command, response),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{decode, encode, Packet, RconShutdown, AUTH, AUTH_RESPONSE, RESPONSE_VALUE};
+    use crate::utils::{Key, Secret};
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    pub const RCON_PASSWORD: &str = "hunter2";
+
+    async fn read_packet(stream: &mut TcpStream) -> Option<Packet> {
+        let mut size = [0; 4];
+        stream.read_exact(&mut size).await.ok()?;
+        let mut packet = vec![0; i32::from_le_bytes(size) as usize];
+        stream.read_exact(&mut packet).await.ok()?;
+        Some(decode(&packet))
+    }
+
+    /// Listens like a Minecraft server on a free local port, answering every command with
+    /// its echo and closing the connection on `stop`. Returns the port and the commands
+    /// received so far.
+    pub async fn fake_rcon_server() -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let auth = read_packet(&mut stream).await.unwrap();
+                    assert_eq!(auth.kind, AUTH);
+                    let id = match auth.body == RCON_PASSWORD {
+                        true => auth.id,
+                        false => -1,
+                    };
+                    stream
+                        .write_all(&encode(auth.id, RESPONSE_VALUE, ""))
+                        .await
+                        .unwrap();
+                    stream
+                        .write_all(&encode(id, AUTH_RESPONSE, ""))
+                        .await
+                        .unwrap();
+                    while let Some(command) = read_packet(&mut stream).await {
+                        log.lock().unwrap().push(command.body.clone());
+                        if command.body == "stop" {
+                            return;
+                        }
+                        let response = format!("echo {}", command.body);
+                        let packet = encode(command.id, RESPONSE_VALUE, &response);
+                        stream.write_all(&packet).await.unwrap();
+                    }
+                });
+            }
+        });
+        (port, received)
+    }
+
+    pub fn shutdown(port: u16) -> RconShutdown {
+        RconShutdown {
+            port,
+            password: Secret::Unencrypted(Key(RCON_PASSWORD.to_string())),
+            countdown: vec![0, 1],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn packets() {
+        let packet = encode(7, 2, "list");
+        assert_eq!(packet.len(), 4 + 14);
+        assert_eq!(packet[..4], 14i32.to_le_bytes());
+        let decoded = decode(&packet[4..]);
+        assert_eq!(
+            decoded,
+            Packet {
+                id: 7,
+                kind: 2,
+                body: "list".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn run() {
+        let (port, received) = fake_rcon_server().await;
+        let mut output = Vec::new();
+        shutdown(port)
+            .run("127.0.0.1", |line| output.push(line))
+            .await
+            .unwrap();
+        assert_eq!(
+            *received.lock().unwrap(),
+            [
+                "say Server shutting down in 1 seconds",
+                "say Server shutting down in 0 seconds",
+                "save-all",
+                "stop"
+            ]
+        );
+        assert_eq!(output[2], "> save-all\necho save-all");
+        assert_eq!(output[3], "> stop\nConnection closed");
+        assert_eq!(
+            shutdown(port).send("127.0.0.1", "list").await,
+            "> list\necho list"
+        );
+
+        // Commands after the server went away can't be run
+        let mut early_stop = shutdown(port);
+        early_stop.countdown.clear();
+        early_stop.commands = vec!["stop".to_string(), "save-all".to_string()];
+        let error = early_stop.run("127.0.0.1", |_| ()).await.unwrap_err();
+        assert!(error.contains("closed the connection"), "{}", error);
+
+        let mut wrong = shutdown(port);
+        wrong.password = Secret::Unencrypted(Key("wrong".to_string()));
+        let error = wrong.run("127.0.0.1", |_| ()).await.unwrap_err();
+        assert!(error.contains("refused the password"), "{}", error);
+    }
+}
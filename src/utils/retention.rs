@@ -0,0 +1,167 @@
+use super::{application::parse_created, cost};
+use chrono::{DateTime, Duration, Utc};
+use hcloud::models::{image, Image};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Which snapshots of an application to keep. The newest snapshot is always kept.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RetentionPolicy {
+    /// Number of most recent snapshots to keep
+    pub keep_last: usize,
+    /// Number of past weeks to keep the newest snapshot of
+    pub keep_weekly: usize,
+    /// Prune without asking whenever the application list is refreshed
+    pub automatic: bool,
+}
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: 3,
+            keep_weekly: 0,
+            automatic: false,
+        }
+    }
+}
+
+/// Snapshots a policy would delete
+#[derive(Debug, Clone, Default)]
+pub struct PrunePreview {
+    pub prune: Vec<Image>,
+    /// Monthly savings in EUR, ignoring snapshots of unknown size
+    pub monthly_savings: f64,
+}
+impl PrunePreview {
+    pub fn image_ids(&self) -> Vec<i32> {
+        self.prune.iter().map(|image| image.id).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prune.is_empty()
+    }
+}
+
+impl RetentionPolicy {
+    /// Evaluates the policy against snapshots. Backups, snapshots still being created, with an
+    /// unreadable creation date or protected against deletion are never pruned.
+    pub fn preview(&self, images: &[Image], now: DateTime<Utc>) -> PrunePreview {
+        let mut snapshots: Vec<(&Image, DateTime<Utc>)> = images
+            .iter()
+            .filter(|image| image.r#type == image::Type::Snapshot)
+            .filter(|image| image.status == image::Status::Available)
+            .filter(|image| !image.protection.delete)
+            .filter_map(|image| Some((image, parse_created(&image.created)?.into())))
+            .collect();
+        snapshots.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let mut keep: HashSet<i32> = snapshots
+            .iter()
+            .take(self.keep_last.max(1))
+            .map(|(image, _)| image.id)
+            .collect();
+        for week in 0..self.keep_weekly {
+            let end = now - Duration::weeks(week as i64);
+            let start = end - Duration::weeks(1);
+            // Sorted newest first, so the first match is the newest of that week
+            let newest = snapshots
+                .iter()
+                .find(|(_, created)| *created > start && *created <= end);
+            if let Some((image, _)) = newest {
+                keep.insert(image.id);
+            }
+        }
+
+        let prune: Vec<Image> = snapshots
+            .into_iter()
+            .filter(|(image, _)| !keep.contains(&image.id))
+            .map(|(image, _)| image.clone())
+            .collect();
+        let monthly_savings = prune.iter().filter_map(cost::image_monthly).sum();
+        PrunePreview {
+            prune,
+            monthly_savings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RetentionPolicy;
+    use crate::utils::empty_snapshot;
+    use chrono::{DateTime, Duration, Utc};
+    use hcloud::models::{image, Image, Protection};
+
+    fn snapshot(id: i32, created: DateTime<Utc>) -> Image {
+        Image {
+            id,
+            created: created.to_rfc3339(),
+            image_size: Some(10.0),
+            ..empty_snapshot()
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2022-11-01T12:00:00+00:00")
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn keep_last() {
+        let images: Vec<Image> = (0..5)
+            .map(|days| snapshot(days, now() - Duration::days(days as i64)))
+            .collect();
+        let policy = RetentionPolicy {
+            keep_last: 2,
+            ..Default::default()
+        };
+
+        let preview = policy.preview(&images, now());
+        assert_eq!(preview.image_ids(), vec![2, 3, 4]);
+        assert!((preview.monthly_savings - 3.0 * 10.0 * 0.0119).abs() < 1e-9);
+    }
+
+    #[test]
+    fn never_prune_newest() {
+        let images = vec![snapshot(1, now()), snapshot(2, now() - Duration::days(1))];
+        let policy = RetentionPolicy {
+            keep_last: 0,
+            ..Default::default()
+        };
+        assert_eq!(policy.preview(&images, now()).image_ids(), vec![2]);
+    }
+
+    #[test]
+    fn keep_weekly() {
+        let images = vec![
+            snapshot(1, now() - Duration::days(1)),
+            snapshot(2, now() - Duration::days(2)),
+            snapshot(3, now() - Duration::days(8)),
+            snapshot(4, now() - Duration::days(10)),
+            snapshot(5, now() - Duration::days(30)),
+        ];
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            keep_weekly: 2,
+            automatic: false,
+        };
+        assert_eq!(policy.preview(&images, now()).image_ids(), vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn respects_protection_and_state() {
+        let mut protected = snapshot(2, now() - Duration::days(2));
+        protected.protection = Box::new(Protection { delete: true });
+        let mut creating = snapshot(3, now() - Duration::days(3));
+        creating.status = image::Status::Creating;
+        let mut backup = snapshot(4, now() - Duration::days(4));
+        backup.r#type = image::Type::Backup;
+        let images = vec![snapshot(1, now()), protected, creating, backup];
+
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            ..Default::default()
+        };
+        assert!(policy.preview(&images, now()).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+use super::cost::{self, HOURS_PER_MONTH};
+use super::Application;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TransitionKind {
+    Crunched,
+    Restored,
+}
+
+/// An application being crunched or restored. Prices are net EUR.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    /// Application ID, stays the same across crunches and restores
+    pub application: String,
+    /// Application name at the time, for display
+    pub name: String,
+    pub at: DateTime<Utc>,
+    /// Hourly price of the servers that were deleted
+    pub hourly_price: f64,
+    /// Monthly storage price of the snapshots that were taken
+    pub snapshot_monthly: f64,
+}
+impl Transition {
+    /// Crunch of every server of the application, priced as they are now
+    pub fn crunched(application: &Application, at: DateTime<Utc>) -> Self {
+        let servers = application.servers.iter().flatten();
+        Self {
+            kind: TransitionKind::Crunched,
+            application: application.id().to_string(),
+            name: application.name.clone().unwrap_or_default(),
+            at,
+            hourly_price: servers.clone().filter_map(cost::server_hourly).sum(),
+            // Snapshots are billed by their actual size, the disk size is an upper bound
+            snapshot_monthly: servers
+                .map(|server| server.primary_disk_size as f64 * cost::SNAPSHOT_PRICE_PER_GB)
+                .sum(),
+        }
+    }
+
+    pub fn restored(application: &Application, at: DateTime<Utc>) -> Self {
+        Self {
+            kind: TransitionKind::Restored,
+            application: application.id().to_string(),
+            name: application.name.clone().unwrap_or_default(),
+            at,
+            hourly_price: 0.0,
+            snapshot_monthly: 0.0,
+        }
+    }
+}
+
+/// Hours spent crunched and what that saved
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Savings {
+    pub hours: f64,
+    /// Server cost that wasn't paid
+    pub saved: f64,
+    /// Snapshot storage paid instead
+    pub storage: f64,
+}
+impl Savings {
+    pub fn net(&self) -> f64 {
+        self.saved - self.storage
+    }
+
+    fn add(&mut self, other: Savings) {
+        self.hours += other.hours;
+        self.saved += other.saved;
+        self.storage += other.storage;
+    }
+}
+
+/// Time between a crunch and the following restore, or now if still crunched
+#[derive(Debug, Clone, PartialEq)]
+struct Period<'a> {
+    crunch: &'a Transition,
+    end: DateTime<Utc>,
+}
+impl Period<'_> {
+    fn savings(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Savings {
+        let hours = (end - start).num_seconds().max(0) as f64 / 3600.0;
+        Savings {
+            hours,
+            saved: hours * self.crunch.hourly_price,
+            storage: hours * self.crunch.snapshot_monthly / HOURS_PER_MONTH,
+        }
+    }
+}
+
+/// Every crunch and restore, in the order they happened
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SavingsLedger {
+    transitions: Vec<Transition>,
+}
+impl SavingsLedger {
+    pub fn record(&mut self, transition: Transition) {
+        self.transitions.push(transition);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    fn periods(&self, now: DateTime<Utc>) -> Vec<Period<'_>> {
+        let mut open: BTreeMap<&str, &Transition> = BTreeMap::new();
+        let mut periods = Vec::new();
+        for transition in &self.transitions {
+            match transition.kind {
+                TransitionKind::Crunched => {
+                    open.insert(&transition.application, transition);
+                }
+                TransitionKind::Restored => {
+                    let crunch = open.remove(transition.application.as_str()).or_else(|| {
+                        // Crunches recorded while a crunch still changed the application ID
+                        let (id, _) = open
+                            .iter()
+                            .find(|(_, crunch)| crunch.name == transition.name)?;
+                        let id = *id;
+                        open.remove(id)
+                    });
+                    if let Some(crunch) = crunch {
+                        periods.push(Period {
+                            crunch,
+                            end: transition.at,
+                        });
+                    }
+                }
+            }
+        }
+        periods.extend(open.into_values().map(|crunch| Period { crunch, end: now }));
+        periods
+    }
+
+    /// Savings of each application by name, up to now
+    pub fn per_application(&self, now: DateTime<Utc>) -> BTreeMap<String, Savings> {
+        let mut savings: BTreeMap<String, Savings> = BTreeMap::new();
+        for period in self.periods(now) {
+            let name = period.crunch.name.clone();
+            let period_savings = period.savings(period.crunch.at, period.end);
+            savings.entry(name).or_default().add(period_savings);
+        }
+        savings
+    }
+
+    /// Savings of each application by calendar month, periods spanning months are split
+    pub fn monthly(&self, now: DateTime<Utc>) -> BTreeMap<(i32, u32), BTreeMap<String, Savings>> {
+        let mut months: BTreeMap<(i32, u32), BTreeMap<String, Savings>> = BTreeMap::new();
+        for period in self.periods(now) {
+            let mut start = period.crunch.at;
+            while start < period.end {
+                let end = next_month(start).map_or(period.end, |next| next.min(period.end));
+                months
+                    .entry((start.year(), start.month()))
+                    .or_default()
+                    .entry(period.crunch.name.clone())
+                    .or_default()
+                    .add(period.savings(start, end));
+                start = end;
+            }
+        }
+        months
+    }
+
+    /// Monthly savings per application as CSV, amounts in net EUR
+    pub fn to_csv(&self, now: DateTime<Utc>) -> String {
+        let mut csv = String::from("month,application,hours_crunched,saved,storage,net\n");
+        for ((year, month), applications) in self.monthly(now) {
+            for (name, savings) in applications {
+                let _ = writeln!(
+                    csv,
+                    "{}-{:02},{},{:.2},{:.2},{:.2},{:.2}",
+                    year,
+                    month,
+                    escape(&name),
+                    savings.hours,
+                    savings.saved,
+                    savings.storage,
+                    savings.net()
+                );
+            }
+        }
+        csv
+    }
+}
+
+/// Start of the month after the given time, `None` past the last date chrono can represent
+pub(super) fn next_month(at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let (year, month) = match at.month() {
+        12 => (at.year() + 1, 1),
+        month => (at.year(), month + 1),
+    };
+    Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()
+}
+
+/// Quotes a CSV field if needed
+fn escape(field: &str) -> String {
+    match field.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SavingsLedger, Transition, TransitionKind};
+    use crate::utils::{
+        empty_server, empty_snapshot, generate_application_list, Application, Identities,
+        ServerSpec, DEFAULT_APP_LABEL,
+    };
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use hcloud::models::{CreatedFrom, Image, ListImagesResponse, ListServersResponse, Server};
+
+    fn transition(kind: TransitionKind, name: &str, at: DateTime<Utc>) -> Transition {
+        Transition {
+            kind,
+            application: format!("label:{}", name),
+            name: name.to_string(),
+            at,
+            hourly_price: 0.01,
+            snapshot_monthly: 0.73,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn per_application() {
+        let mut ledger = SavingsLedger::default();
+        let day = |day| Utc.with_ymd_and_hms(2022, 10, day, 0, 0, 0).unwrap();
+        ledger.record(transition(TransitionKind::Crunched, "game", day(1)));
+        ledger.record(transition(TransitionKind::Crunched, "web", day(2)));
+        ledger.record(transition(TransitionKind::Restored, "game", day(3)));
+        // Restores without a recorded crunch are ignored
+        ledger.record(transition(TransitionKind::Restored, "db", day(3)));
+
+        let savings = ledger.per_application(day(4));
+        assert_eq!(savings.len(), 2);
+        let game = savings["game"];
+        assert!(close(game.hours, 48.0));
+        assert!(close(game.saved, 0.48));
+        assert!(close(game.storage, 48.0 * 0.001));
+        assert!(close(game.net(), 0.48 - 0.048));
+        // Still crunched
+        assert!(close(savings["web"].hours, 48.0));
+    }
+
+    #[test]
+    fn crunch_and_restore() {
+        let mut identities = Identities::default();
+        let mut list = |servers, images| {
+            let servers = ListServersResponse {
+                meta: None,
+                servers,
+            };
+            let images = ListImagesResponse { meta: None, images };
+            let mut applications = generate_application_list(&servers, &images, DEFAULT_APP_LABEL);
+            identities.apply(&mut applications, true);
+            applications.remove(0)
+        };
+        let server = Server {
+            id: 1,
+            name: "game".to_string(),
+            ..empty_server()
+        };
+        let snapshot = Image {
+            id: 2,
+            created_from: Some(Box::new(CreatedFrom {
+                id: 1,
+                name: "game".to_string(),
+            })),
+            labels: ServerSpec::from(&server).labels(DEFAULT_APP_LABEL, "game"),
+            ..empty_snapshot()
+        };
+
+        let crunched = Utc.with_ymd_and_hms(2022, 10, 1, 0, 0, 0).unwrap();
+        let running: Application = list(vec![server], vec![]);
+        let snapshot_only: Application = list(vec![], vec![snapshot]);
+        assert_eq!(running.id(), snapshot_only.id());
+        let mut ledger = SavingsLedger::default();
+        ledger.record(Transition::crunched(&running, crunched));
+        ledger.record(Transition::restored(
+            &snapshot_only,
+            crunched + Duration::hours(5),
+        ));
+        assert!(close(ledger.per_application(Utc::now())["game"].hours, 5.0));
+
+        // Restores recorded under another ID still end the crunch of the same name
+        let mut legacy = transition(TransitionKind::Restored, "game", crunched);
+        legacy.at = crunched + Duration::hours(5);
+        let mut ledger = SavingsLedger::default();
+        ledger.record(Transition::crunched(&running, crunched));
+        ledger.record(legacy);
+        assert!(close(ledger.per_application(Utc::now())["game"].hours, 5.0));
+    }
+
+    #[test]
+    fn monthly() {
+        let mut ledger = SavingsLedger::default();
+        let crunched = Utc.with_ymd_and_hms(2022, 10, 31, 12, 0, 0).unwrap();
+        let restored = Utc.with_ymd_and_hms(2022, 11, 1, 6, 0, 0).unwrap();
+        ledger.record(transition(TransitionKind::Crunched, "game", crunched));
+        ledger.record(transition(TransitionKind::Restored, "game", restored));
+
+        let months = ledger.monthly(Utc::now());
+        let hours: Vec<f64> = months.values().map(|apps| apps["game"].hours).collect();
+        assert_eq!(
+            months.keys().copied().collect::<Vec<_>>(),
+            vec![(2022, 10), (2022, 11)]
+        );
+        assert_eq!(hours, vec![12.0, 6.0]);
+    }
+
+    #[test]
+    fn to_csv() {
+        let mut ledger = SavingsLedger::default();
+        let crunched = Utc.with_ymd_and_hms(2022, 10, 1, 0, 0, 0).unwrap();
+        let restored = Utc.with_ymd_and_hms(2022, 10, 2, 0, 0, 0).unwrap();
+        ledger.record(transition(TransitionKind::Crunched, "game, v2", crunched));
+        ledger.record(transition(TransitionKind::Restored, "game, v2", restored));
+
+        let csv = ledger.to_csv(Utc::now());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "month,application,hours_crunched,saved,storage,net"
+        );
+        assert_eq!(lines[1], "2022-10,\"game, v2\",24.00,0.24,0.02,0.22");
+    }
+}
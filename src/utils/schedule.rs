@@ -0,0 +1,660 @@
+use super::calendar::CalendarSource;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDateTime, NaiveTime, TimeZone, Timelike,
+    Utc, Weekday,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How far ahead to look for the next run of a cron expression
+const MAX_DAYS_AHEAD: i64 = 366 * 4;
+/// How far ahead to look for the next event of a calendar
+const CALENDAR_DAYS_AHEAD: i64 = 366;
+/// Upper bound of runs reported at once, e.g. after the app was closed for a long time
+const MAX_RUNS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ScheduledAction {
+    Crunch,
+    Restore,
+}
+impl ScheduledAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScheduledAction::Crunch => "Crunch",
+            ScheduledAction::Restore => "Restore",
+        }
+    }
+}
+
+/// A crunch or restore that is due
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ScheduledRun {
+    /// Application ID
+    pub application: String,
+    pub action: ScheduledAction,
+    pub at: DateTime<Utc>,
+}
+
+/// Time zone schedules are evaluated in. The local and named zones follow daylight saving
+/// time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Zone {
+    Utc,
+    Local,
+    /// Offset east of UTC in seconds
+    Fixed(i32),
+    /// IANA time zone like `Europe/Berlin`
+    Named(Tz),
+}
+impl Zone {
+    /// Parses `UTC`, `Local`, an offset like `+02:00` or an IANA name like `Europe/Berlin`
+    pub fn parse(zone: &str) -> Result<Self, String> {
+        let zone = zone.trim();
+        if zone.eq_ignore_ascii_case("utc") {
+            return Ok(Self::Utc);
+        }
+        if zone.eq_ignore_ascii_case("local") {
+            return Ok(Self::Local);
+        }
+        if let Ok(tz) = zone.parse() {
+            return Ok(Self::Named(tz));
+        }
+        let invalid = || {
+            format!(
+                "Invalid time zone \"{}\", use UTC, Local, +HH:MM or a name like Europe/Berlin",
+                zone
+            )
+        };
+        let (sign, offset) = match zone.chars().next() {
+            Some('+') => (1, &zone[1..]),
+            Some('-') => (-1, &zone[1..]),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 14 || minutes >= 60 {
+            return Err(invalid());
+        }
+        Ok(Self::Fixed(sign * (hours * 3600 + minutes * 60)))
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Zone::Utc => "UTC".to_string(),
+            Zone::Local => "Local".to_string(),
+            Zone::Fixed(offset) => {
+                let sign = if *offset < 0 { '-' } else { '+' };
+                let offset = offset.abs();
+                format!("{}{:02}:{:02}", sign, offset / 3600, offset % 3600 / 60)
+            }
+            Zone::Named(tz) => tz.name().to_string(),
+        }
+    }
+
+    pub(super) fn to_local(self, at: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Utc => at.naive_utc(),
+            Zone::Local => at.with_timezone(&Local).naive_local(),
+            Zone::Fixed(offset) => at.naive_utc() + Duration::seconds(offset as i64),
+            Zone::Named(tz) => at.with_timezone(&tz).naive_local(),
+        }
+    }
+
+    /// `None` for local times skipped by a daylight saving time change
+    pub(super) fn to_utc(self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::Utc => Some(Utc.from_utc_datetime(&local)),
+            Zone::Local => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|at| at.with_timezone(&Utc)),
+            Zone::Fixed(offset) => FixedOffset::east_opt(offset)?
+                .from_local_datetime(&local)
+                .single()
+                .map(|at| at.with_timezone(&Utc)),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|at| at.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// Five field cron expression: minute, hour, day of month, month and day of week.
+/// Fields take `*`, values, ranges, steps and lists, e.g. `0 18 * * 1-5` or `*/30 8-20 * * *`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day of month and day of week were restricted, a day matching either one counts
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "\"{}\" needs five fields: minute hour day month weekday",
+                expression
+            ));
+        }
+        let weekdays = parse_field(fields[4], 0, 7)?;
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            // Both 0 and 7 are Sunday
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        })
+    }
+
+    fn matches_day(&self, date: chrono::NaiveDate) -> bool {
+        if self.months & 1 << date.month() == 0 {
+            return false;
+        }
+        let day = self.days & 1 << date.day() != 0;
+        let weekday = self.weekdays & 1 << date.weekday().num_days_from_sunday() != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    /// First matching minute strictly after the given time
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        for offset in 0..MAX_DAYS_AHEAD {
+            let date = start.date() + Duration::days(offset);
+            if !self.matches_day(date) {
+                continue;
+            }
+            for hour in (0..24).filter(|hour| self.hours & 1 << hour != 0) {
+                for minute in (0..60).filter(|minute| self.minutes & 1 << minute != 0) {
+                    let time = date.and_hms_opt(hour, minute, 0)?;
+                    if time >= start {
+                        return Some(time);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Parses one cron field into a bit set of the allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = || format!("Invalid cron field \"{}\"", field);
+    let value = |value: &str| -> Result<u32, String> {
+        // Weekdays may also be given by name
+        let name = (max == 7).then(|| weekday_number(value)).flatten();
+        name.or_else(|| value.parse().ok())
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (first, last) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((first, last)) => (value(first)?, value(last)?),
+                // `5/15` runs every 15 starting at 5
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        for value in (first..=last).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn weekday_number(name: &str) -> Option<u32> {
+    name.parse::<Weekday>()
+        .ok()
+        .map(|weekday| weekday.num_days_from_sunday())
+}
+
+/// When an application should be running
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Trigger {
+    /// Running from `start` until `end` on the given weekdays, starting Monday, crunched
+    /// otherwise. Windows ending before they start end the next day.
+    Window {
+        days: [bool; 7],
+        start: NaiveTime,
+        end: NaiveTime,
+    },
+    /// Cron expressions of when to restore and when to crunch
+    Cron { restore: String, crunch: String },
+    /// Running during the events of an iCalendar file
+    Calendar(CalendarSource),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Schedule {
+    pub trigger: Trigger,
+    pub zone: Zone,
+}
+impl Schedule {
+    /// Cron expressions of the restores and crunches
+    pub fn crons(&self) -> Result<(Cron, Cron), String> {
+        match &self.trigger {
+            Trigger::Cron { restore, crunch } => Ok((Cron::parse(restore)?, Cron::parse(crunch)?)),
+            Trigger::Calendar(_) => Err("Calendars have no cron expressions".to_string()),
+            Trigger::Window { days, start, end } => {
+                let days: Vec<u32> = (0..7).filter(|day| days[*day as usize]).collect();
+                if days.is_empty() {
+                    return Err("Pick at least one day".to_string());
+                }
+                // Monday is 1 in cron, Sunday 0 or 7
+                let list = |shift: u32| {
+                    let days: Vec<String> = days
+                        .iter()
+                        .map(|day| ((day + 1 + shift) % 7).to_string())
+                        .collect();
+                    days.join(",")
+                };
+                let overnight = end <= start;
+                let restore = format!("{} {} * * {}", start.minute(), start.hour(), list(0));
+                let crunch = format!(
+                    "{} {} * * {}",
+                    end.minute(),
+                    end.hour(),
+                    list(overnight as u32)
+                );
+                Ok((Cron::parse(&restore)?, Cron::parse(&crunch)?))
+            }
+        }
+    }
+
+    /// Next run of either action strictly after the given time
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<(DateTime<Utc>, ScheduledAction)> {
+        if let Trigger::Calendar(source) = &self.trigger {
+            let until = after + Duration::days(CALENDAR_DAYS_AHEAD);
+            let (start, end) = source
+                .intervals(after, until, self.zone)
+                .into_iter()
+                .find(|(_, end)| *end > after)?;
+            return match start > after {
+                true => Some((start, ScheduledAction::Restore)),
+                false => Some((end, ScheduledAction::Crunch)),
+            };
+        }
+        let (restore, crunch) = self.crons().ok()?;
+        let restore = self.next_of(&restore, after);
+        let crunch = self.next_of(&crunch, after);
+        match (restore, crunch) {
+            (Some(restore), Some(crunch)) if crunch <= restore => {
+                Some((crunch, ScheduledAction::Crunch))
+            }
+            (Some(restore), _) => Some((restore, ScheduledAction::Restore)),
+            (None, Some(crunch)) => Some((crunch, ScheduledAction::Crunch)),
+            (None, None) => None,
+        }
+    }
+
+    fn next_of(&self, cron: &Cron, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut local = self.zone.to_local(after);
+        // Skips local times that don't exist because of daylight saving time
+        for _ in 0..MAX_RUNS {
+            local = cron.next_after(local)?;
+            if let Some(at) = self.zone.to_utc(local).filter(|at| *at > after) {
+                return Some(at);
+            }
+        }
+        None
+    }
+
+    /// Runs after `after` up to and including `until`, oldest first
+    pub fn runs_between(
+        &self,
+        after: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Vec<(DateTime<Utc>, ScheduledAction)> {
+        let mut runs = Vec::new();
+        let mut last = after;
+        while let Some((at, action)) = self.next_after(last).filter(|(at, _)| *at <= until) {
+            runs.push((at, action));
+            last = at;
+            if runs.len() >= MAX_RUNS {
+                break;
+            }
+        }
+        runs
+    }
+}
+
+/// Schedules of all applications, along with the runs missed while the app was closed
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Schedules {
+    /// By application ID
+    pub schedules: HashMap<String, Schedule>,
+    /// Time up to which runs were triggered
+    pub checked_until: Option<DateTime<Utc>>,
+    pub missed: Vec<ScheduledRun>,
+}
+impl Schedules {
+    /// Runs of every application after `after` up to and including `until`, oldest first
+    pub fn runs_between(&self, after: DateTime<Utc>, until: DateTime<Utc>) -> Vec<ScheduledRun> {
+        let mut runs: Vec<ScheduledRun> = self
+            .schedules
+            .iter()
+            .flat_map(|(application, schedule)| {
+                schedule
+                    .runs_between(after, until)
+                    .into_iter()
+                    .map(|(at, action)| ScheduledRun {
+                        application: application.clone(),
+                        action,
+                        at,
+                    })
+            })
+            .collect();
+        runs.sort_by_key(|run| run.at);
+        runs
+    }
+
+    /// Next run of any application
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<ScheduledRun> {
+        self.schedules
+            .iter()
+            .filter_map(|(application, schedule)| {
+                let (at, action) = schedule.next_after(after)?;
+                Some(ScheduledRun {
+                    application: application.clone(),
+                    action,
+                    at,
+                })
+            })
+            .min_by_key(|run| run.at)
+    }
+
+    /// Reads calendar files that changed since they were last read, returns whether any was
+    pub fn reload_calendars(&mut self) -> bool {
+        let mut reloaded = false;
+        for schedule in self.schedules.values_mut() {
+            if let Trigger::Calendar(source) = &mut schedule.trigger {
+                reloaded |= source.reload();
+            }
+        }
+        reloaded
+    }
+
+    /// Calendars by application ID
+    pub fn calendars(&self) -> HashMap<String, CalendarSource> {
+        self.schedules
+            .iter()
+            .filter_map(|(application, schedule)| match &schedule.trigger {
+                Trigger::Calendar(source) => Some((application.clone(), source.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Takes over calendars read elsewhere, unless their schedule was changed meanwhile
+    pub fn adopt_calendars(&mut self, calendars: HashMap<String, CalendarSource>) {
+        for (application, calendar) in calendars {
+            if let Some(schedule) = self.schedules.get_mut(&application) {
+                match &mut schedule.trigger {
+                    Trigger::Calendar(source) if *source == calendar => *source = calendar,
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Records the runs since the last check as missed
+    pub fn record_missed(&mut self, now: DateTime<Utc>) -> Vec<ScheduledRun> {
+        let missed = match self.checked_until {
+            Some(since) => self.runs_between(since, now),
+            None => Vec::new(),
+        };
+        self.missed.extend(missed.iter().cloned());
+        let excess = self.missed.len().saturating_sub(MAX_RUNS);
+        self.missed.drain(..excess);
+        self.checked_until = Some(now);
+        missed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CalendarSource, Cron, Schedule, ScheduledAction, Schedules, Trigger, Zone};
+    use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+    use std::collections::HashMap;
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at).unwrap().into()
+    }
+
+    #[test]
+    fn cron() {
+        // 2022-10-14 is a Friday
+        let friday = NaiveDate::from_ymd_opt(2022, 10, 14)
+            .unwrap()
+            .and_hms_opt(17, 30, 0)
+            .unwrap();
+        let cron = Cron::parse("0 18 * * 1-5").unwrap();
+        assert_eq!(
+            cron.next_after(friday),
+            Some(friday.date().and_hms_opt(18, 0, 0).unwrap())
+        );
+        let monday = NaiveDate::from_ymd_opt(2022, 10, 17)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+        assert_eq!(
+            cron.next_after(friday.date().and_hms_opt(18, 0, 0).unwrap()),
+            Some(monday)
+        );
+
+        let cron = Cron::parse("*/20 9 * * sat,sun").unwrap();
+        let saturday = NaiveDate::from_ymd_opt(2022, 10, 15).unwrap();
+        assert_eq!(
+            cron.next_after(friday),
+            Some(saturday.and_hms_opt(9, 0, 0).unwrap())
+        );
+        assert_eq!(
+            cron.next_after(saturday.and_hms_opt(9, 0, 0).unwrap()),
+            Some(saturday.and_hms_opt(9, 20, 0).unwrap())
+        );
+
+        // Day of month or day of week
+        let cron = Cron::parse("0 0 1 * 0").unwrap();
+        assert_eq!(
+            cron.next_after(friday),
+            Some(
+                NaiveDate::from_ymd_opt(2022, 10, 16)
+                    .unwrap()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+            )
+        );
+        let cron = Cron::parse("0 0 29 2 *").unwrap();
+        assert_eq!(
+            cron.next_after(friday),
+            Some(
+                NaiveDate::from_ymd_opt(2024, 2, 29)
+                    .unwrap()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+            )
+        );
+
+        assert!(Cron::parse("0 18 * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("0 5-1 * * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn zone() {
+        assert_eq!(Zone::parse("utc"), Ok(Zone::Utc));
+        assert_eq!(Zone::parse("Local"), Ok(Zone::Local));
+        assert_eq!(Zone::parse("+02:00"), Ok(Zone::Fixed(7200)));
+        assert_eq!(Zone::parse("-05:30"), Ok(Zone::Fixed(-19800)));
+        assert_eq!(Zone::Fixed(-19800).label(), "-05:30");
+        let berlin = Zone::parse("Europe/Berlin").unwrap();
+        assert_eq!(berlin.label(), "Europe/Berlin");
+        assert!(Zone::parse("Mars/Olympus").is_err());
+
+        // Named zones follow daylight saving time, which ended on 2022-10-30 in Berlin
+        let schedule = Schedule {
+            trigger: Trigger::Cron {
+                restore: "0 18 * * *".to_string(),
+                crunch: "0 22 * * *".to_string(),
+            },
+            zone: berlin,
+        };
+        let runs = schedule.runs_between(
+            utc("2022-10-29T12:00:00+00:00"),
+            utc("2022-10-30T18:00:00+00:00"),
+        );
+        assert_eq!(
+            runs,
+            vec![
+                (utc("2022-10-29T18:00:00+02:00"), ScheduledAction::Restore),
+                (utc("2022-10-29T22:00:00+02:00"), ScheduledAction::Crunch),
+                (utc("2022-10-30T18:00:00+01:00"), ScheduledAction::Restore),
+            ]
+        );
+    }
+
+    #[test]
+    fn window() {
+        let mut days = [false; 7];
+        days[4] = true; // Friday
+        let schedule = Schedule {
+            trigger: Trigger::Window {
+                days,
+                start: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            },
+            zone: Zone::Fixed(2 * 3600),
+        };
+
+        let runs = schedule.runs_between(
+            utc("2022-10-14T00:00:00+00:00"),
+            utc("2022-10-21T23:00:00+00:00"),
+        );
+        assert_eq!(
+            runs,
+            vec![
+                (utc("2022-10-14T18:00:00+02:00"), ScheduledAction::Restore),
+                // Overnight windows end on the next day
+                (utc("2022-10-15T02:00:00+02:00"), ScheduledAction::Crunch),
+                (utc("2022-10-21T18:00:00+02:00"), ScheduledAction::Restore),
+            ]
+        );
+    }
+
+    #[test]
+    fn calendar() {
+        let path =
+            std::env::temp_dir().join(format!("cruncher-{}-schedule.ics", std::process::id()));
+        let events = "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+DTSTART:20221014T180000Z\r
+DTEND:20221014T200000Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20221014T203000Z\r
+DTEND:20221014T220000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        std::fs::write(&path, events).unwrap();
+        let mut schedules = Schedules::default();
+        let source = CalendarSource::new(path.to_str().unwrap(), 15, 30);
+        let schedule = Schedule {
+            trigger: Trigger::Calendar(source),
+            zone: Zone::Utc,
+        };
+        schedules
+            .schedules
+            .insert("label:game".to_string(), schedule);
+        let mut unread = schedules.clone();
+        let mut changed = schedules.clone();
+        assert!(schedules.reload_calendars());
+        std::fs::remove_file(&path).unwrap();
+
+        // Events closer than lead and trail time are merged
+        let runs = schedules.schedules["label:game"].runs_between(
+            utc("2022-10-14T00:00:00+00:00"),
+            utc("2022-10-15T00:00:00+00:00"),
+        );
+        assert_eq!(
+            runs,
+            vec![
+                (utc("2022-10-14T17:45:00+00:00"), ScheduledAction::Restore),
+                (utc("2022-10-14T22:30:00+00:00"), ScheduledAction::Crunch),
+            ]
+        );
+
+        // Calendars read by the scheduler are only taken over by unchanged schedules
+        let calendar = |schedules: &Schedules| match &schedules.schedules["label:game"].trigger {
+            Trigger::Calendar(source) => source.calendar().is_ok(),
+            _ => false,
+        };
+        unread.adopt_calendars(schedules.calendars());
+        assert!(calendar(&unread));
+        if let Some(schedule) = changed.schedules.get_mut("label:game") {
+            schedule.trigger = Trigger::Calendar(CalendarSource::new("other.ics", 15, 30));
+        }
+        changed.adopt_calendars(schedules.calendars());
+        assert!(!calendar(&changed));
+    }
+
+    #[test]
+    fn missed() {
+        let schedule = Schedule {
+            trigger: Trigger::Cron {
+                restore: "0 8 * * *".to_string(),
+                crunch: "0 20 * * *".to_string(),
+            },
+            zone: Zone::Utc,
+        };
+        let mut schedules = Schedules {
+            schedules: HashMap::from([("label:game".to_string(), schedule)]),
+            ..Default::default()
+        };
+        let start = Utc.with_ymd_and_hms(2022, 10, 14, 12, 0, 0).unwrap();
+        assert!(schedules.record_missed(start).is_empty());
+
+        let missed = schedules.record_missed(start + chrono::Duration::days(1));
+        let actions: Vec<ScheduledAction> = missed.iter().map(|run| run.action).collect();
+        assert_eq!(
+            actions,
+            vec![ScheduledAction::Crunch, ScheduledAction::Restore]
+        );
+        assert_eq!(schedules.missed, missed);
+        assert_eq!(
+            schedules.next_after(start).map(|run| run.at),
+            Some(Utc.with_ymd_and_hms(2022, 10, 14, 20, 0, 0).unwrap())
+        );
+    }
+}
@@ -4,7 +4,7 @@ use serde_encrypt::{
     EncryptedMessage, Error,
 };
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Key(pub String);
 impl SerdeEncryptSharedKey for Key {
     type S = BincodeSerializer<Self>;
@@ -18,7 +18,7 @@ impl From<Secret> for Key {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Secret {
     Unencrypted(Key),
     Encrypted(Vec<u8>),
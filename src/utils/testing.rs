@@ -2,8 +2,8 @@ use hcloud::models::{
     image::{self, OsFlavor, Type},
     server,
     server_type::{CpuType, StorageType},
-    Datacenter, DatacenterServerTypes, Image, Location, Protection, Server, ServerProtection,
-    ServerPublicNet, ServerType,
+    Datacenter, DatacenterServerTypes, Image, Location, Price, PricePerTime, Protection, Server,
+    ServerProtection, ServerPublicNet, ServerType,
 };
 use std::collections::HashMap;
 
@@ -93,3 +93,18 @@ pub fn empty_server() -> Server {
         volumes: None,
     }
 }
+
+/// Prices of a server type at a single location, with net prices only
+pub fn prices(location: &str, monthly: &str) -> Vec<PricePerTime> {
+    vec![PricePerTime {
+        location: location.to_string(),
+        price_hourly: Box::new(Price {
+            gross: String::new(),
+            net: String::new(),
+        }),
+        price_monthly: Box::new(Price {
+            gross: String::new(),
+            net: monthly.to_string(),
+        }),
+    }]
+}
@@ -37,8 +37,8 @@ impl fmt::Display for Timestamp {
 
 #[cfg(test)]
 mod test {
-    use super::{DateTime, Timestamp, Utc};
-    use chrono::NaiveDateTime;
+    use super::{Timestamp, Utc};
+    use chrono::TimeZone;
     use regex::Regex;
 
     #[test]
@@ -47,13 +47,9 @@ mod test {
         const MINS: i64 = 2;
         const HOURS: i64 = 1;
         let ts = Timestamp::new(Utc::now());
-        let offset = DateTime::<Utc>::from_utc(
-            NaiveDateTime::from_timestamp(
-                ts.utc.timestamp() + (SECS + MINS * 60 + HOURS * 60 * 60),
-                0,
-            ),
-            Utc,
-        );
+        let offset = Utc
+            .timestamp_opt(ts.utc.timestamp() + (SECS + MINS * 60 + HOURS * 60 * 60), 0)
+            .unwrap();
         assert_eq!(ts.get_smh(&offset), (SECS, MINS, HOURS));
     }
 
@@ -62,22 +58,15 @@ mod test {
         let ts = Timestamp::new(Utc::now());
         assert_eq!(ts.to_string_with_dt(&ts.utc), "Just now");
 
-        let offset_7_secs = DateTime::<Utc>::from_utc(
-            NaiveDateTime::from_timestamp(ts.utc.timestamp() + 7, 0),
-            Utc,
-        );
+        let offset_7_secs = Utc.timestamp_opt(ts.utc.timestamp() + 7, 0).unwrap();
         assert_eq!(ts.to_string_with_dt(&offset_7_secs), "7s ago");
 
-        let offset_5_mins = DateTime::<Utc>::from_utc(
-            NaiveDateTime::from_timestamp(ts.utc.timestamp() + 5 * 60, 0),
-            Utc,
-        );
+        let offset_5_mins = Utc.timestamp_opt(ts.utc.timestamp() + 5 * 60, 0).unwrap();
         assert_eq!(ts.to_string_with_dt(&offset_5_mins), "5m 0s ago");
 
-        let offset_3_hours_4_mins_1_sec = DateTime::<Utc>::from_utc(
-            NaiveDateTime::from_timestamp(ts.utc.timestamp() + (1 + 4 * 60 + 3 * 60 * 60), 0),
-            Utc,
-        );
+        let offset_3_hours_4_mins_1_sec = Utc
+            .timestamp_opt(ts.utc.timestamp() + (1 + 4 * 60 + 3 * 60 * 60), 0)
+            .unwrap();
         assert_eq!(
             ts.to_string_with_dt(&offset_3_hours_4_mins_1_sec),
             "3h 4m 1s ago"
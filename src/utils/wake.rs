@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// What clients of a crunched application see while it is restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WakeMode {
+    /// Keep the connection open and forward it once the server is up
+    Hold,
+    /// Send the message and close the connection
+    Reject,
+}
+impl WakeMode {
+    pub const ALL: [WakeMode; 2] = [WakeMode::Hold, WakeMode::Reject];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            WakeMode::Hold => "Hold until running",
+            WakeMode::Reject => "Reject with message",
+        }
+    }
+}
+
+/// Local port that restores a crunched application when a client connects to it, and
+/// forwards traffic to its server once it runs
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WakeProxy {
+    /// Address to listen on, e.g. `0.0.0.0:25565`
+    pub listen: String,
+    /// Port of the application on its server
+    pub target_port: u16,
+    pub mode: WakeMode,
+    /// How long held clients wait for the server before they get the message
+    pub hold_secs: u64,
+    /// Sent to clients that aren't forwarded, followed by a line break
+    pub message: String,
+}
+impl Default for WakeProxy {
+    fn default() -> Self {
+        Self {
+            listen: "0.0.0.0:25565".to_string(),
+            target_port: 25565,
+            mode: WakeMode::Hold,
+            hold_secs: 300,
+            message: "The server is starting, please try again in a minute".to_string(),
+        }
+    }
+}
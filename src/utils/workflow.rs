@@ -0,0 +1,73 @@
+use hcloud::models::Action;
+use serde::{Deserialize, Serialize};
+
+/// Individual steps of a crunch or restore, in the order they are executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Step {
+    // Crunch
+    /// Game saved and stopped over RCON, the server is untouched so far
+    Rcon,
+    /// Shutdown hooks running over SSH
+    Hooks,
+    Shutdown,
+    AwaitOff,
+    Snapshot,
+    AwaitSnapshot,
+    Delete,
+    // Restore
+    Create,
+    AwaitRunning,
+    /// Waiting for the health checks of the running server to pass
+    HealthCheck,
+    Done,
+}
+impl Step {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Step::Rcon => "Saving and stopping game",
+            Step::Hooks => "Running shutdown hooks",
+            Step::Shutdown => "Shutting down",
+            Step::AwaitOff => "Waiting for server to stop",
+            Step::Snapshot => "Creating snapshot",
+            Step::AwaitSnapshot => "Waiting for snapshot",
+            Step::Delete => "Deleting server",
+            Step::Create => "Creating server",
+            Step::AwaitRunning => "Waiting for server to start",
+            Step::HealthCheck => "Waiting for health checks",
+            Step::Done => "Done",
+        }
+    }
+}
+
+/// Progress report of a running workflow, sent whenever a step is entered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowUpdate {
+    pub step: Step,
+    pub server_id: i32,
+    pub image_id: Option<i32>,
+    pub action: Option<Action>,
+}
+impl WorkflowUpdate {
+    pub fn new(step: Step, server_id: i32) -> Self {
+        Self {
+            step,
+            server_id,
+            image_id: None,
+            action: None,
+        }
+    }
+
+    pub fn with_image(mut self, image_id: i32) -> Self {
+        self.image_id = Some(image_id);
+        self
+    }
+
+    pub fn with_action(mut self, action: Action) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.step == Step::Done
+    }
+}